@@ -0,0 +1,19 @@
+/// The kind of a change of council.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    MemberAdded,
+    MemberRemoved,
+}
+
+/// Whether a pending change of an account and a new change of the same account
+/// cancel each other out, so neither of them needs to be applied to DAO contracts.
+///
+/// They are cancelled only if they are opposite to each other and the pending one
+/// has not been applied to (or submitted to) any DAO contract.
+pub fn can_coalesce(
+    pending: ChangeKind,
+    is_pending_waiting_for_applying: bool,
+    new: ChangeKind,
+) -> bool {
+    is_pending_waiting_for_applying && pending != new
+}
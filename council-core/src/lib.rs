@@ -0,0 +1,19 @@
+//! The chain-agnostic rules of council keeper.
+//!
+//! This crate contains the pure data structures and deterministic algorithms for
+//! ranking validators by stake, selecting council members from the ranking,
+//! diffing the council into change histories and measuring the decentralization
+//! of stakes. The storage is abstracted by traits, so that the same rules are used
+//! by the contract and the off-chain tools.
+mod history;
+mod ranking;
+mod selection;
+mod stats;
+
+pub use history::{can_coalesce, ChangeKind};
+pub use ranking::{
+    append_by_rank_value, merge_by_rank_value, merge_step, reposition_by_rank_value,
+    updated_total_stake, MergeByRankValue, MergeCursor, MergeStep, RankedStore,
+};
+pub use selection::{diff_council, select_council_members, CouncilDiff};
+pub use stats::{exceeds_nakamoto_threshold, share_in_bps, GiniSums};
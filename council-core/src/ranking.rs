@@ -0,0 +1,236 @@
+use std::iter::Peekable;
+
+/// A list of members ordered by rank value descending, which can be backed by any storage.
+///
+/// The index of a member in the list is the rank of it, the members with the same
+/// rank value keep the order in which they were ranked.
+pub trait RankedStore<T> {
+    /// The number of members in the list.
+    fn len(&self) -> u32;
+    /// Whether there is no member in the list.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The member at the index.
+    fn get(&self, index: u32) -> Option<T>;
+    /// Put the member at the index, and record the index as the rank of the member.
+    /// The list is extended by one if the index is at the end of it.
+    fn put(&mut self, index: u32, member: &T);
+    /// The value which the member is ranked by.
+    fn rank_value_of(&self, member: &T) -> u128;
+}
+
+/// Append a member to the end of the list, and move it up by its rank value.
+/// Return the new rank of the member.
+pub fn append_by_rank_value<T, S: RankedStore<T>>(store: &mut S, member: &T) -> u32 {
+    let index = store.len();
+    store.put(index, member);
+    move_up_by_rank_value(store, member, index)
+}
+
+/// Put a member at the index it is ranked, and move it up or down by its (changed) rank value.
+/// Return the new rank of the member.
+pub fn reposition_by_rank_value<T, S: RankedStore<T>>(
+    store: &mut S,
+    member: &T,
+    index: u32,
+) -> u32 {
+    assert!(index < store.len(), "Index is out of bound of the array.");
+    store.put(index, member);
+    let new_index = move_up_by_rank_value(store, member, index);
+    match new_index == index {
+        true => move_down_by_rank_value(store, member, index),
+        false => new_index,
+    }
+}
+
+/// The total stake of a validator after the stake in one of the appchains is changed.
+pub fn updated_total_stake(total_stake: u128, old_stake: u128, new_stake: u128) -> u128 {
+    total_stake - old_stake + new_stake
+}
+
+//
+fn move_up_by_rank_value<T, S: RankedStore<T>>(store: &mut S, member: &T, index: u32) -> u32 {
+    let rank_value = store.rank_value_of(member);
+    let mut current_index = index;
+    while current_index > 0 {
+        let previous_index = current_index - 1;
+        let previous_member = store.get(previous_index).unwrap();
+        if rank_value <= store.rank_value_of(&previous_member) {
+            break;
+        }
+        store.put(previous_index, member);
+        store.put(current_index, &previous_member);
+        current_index = previous_index;
+    }
+    current_index
+}
+
+//
+fn move_down_by_rank_value<T, S: RankedStore<T>>(store: &mut S, member: &T, index: u32) -> u32 {
+    let rank_value = store.rank_value_of(member);
+    let mut current_index = index;
+    while current_index + 1 < store.len() {
+        let next_index = current_index + 1;
+        let next_member = store.get(next_index).unwrap();
+        if rank_value >= store.rank_value_of(&next_member) {
+            break;
+        }
+        store.put(next_index, member);
+        store.put(current_index, &next_member);
+        current_index = next_index;
+    }
+    current_index
+}
+
+/// The progress of a bottom-up merge sort between two buffers, which sorts the records
+/// by rank value descending. The merge is stable, so the records with the same rank value
+/// keep their previous order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MergeCursor {
+    pub count: u32,
+    // the width of the sorted runs in the source buffer
+    pub width: u32,
+    // the buffer (0 or 1) which the runs are merged from
+    pub source: u8,
+    pub run_start: u32,
+    // the next index of the left run and the right run in the source buffer
+    pub left: u32,
+    pub right: u32,
+    // the next index in the other buffer
+    pub out: u32,
+}
+
+impl MergeCursor {
+    /// Start sorting the `count` records in buffer 0.
+    pub fn new(count: u32) -> Self {
+        Self {
+            count,
+            width: 1,
+            source: 0,
+            run_start: 0,
+            left: 0,
+            right: count.min(1),
+            out: 0,
+        }
+    }
+}
+
+/// A step of the merge sort.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeStep {
+    /// Copy the record at `from` of the source buffer to `to` of the other buffer.
+    Move {
+        from: u32,
+        to: u32,
+        next: MergeCursor,
+    },
+    /// Nothing to copy, continue with the next cursor.
+    Continue(MergeCursor),
+    /// The records are sorted in the `source` buffer.
+    Finished { source: u8 },
+}
+
+/// Decide the next step of the merge sort, `rank_value_at` returns the rank value
+/// of the record at the index of the source buffer of the cursor.
+pub fn merge_step<F: Fn(u32) -> u128>(cursor: MergeCursor, rank_value_at: F) -> MergeStep {
+    let MergeCursor {
+        count,
+        width,
+        source,
+        run_start,
+        left,
+        right,
+        out,
+    } = cursor;
+    if width >= count {
+        return MergeStep::Finished { source };
+    }
+    if run_start >= count {
+        let width = width * 2;
+        return MergeStep::Continue(MergeCursor {
+            count,
+            width,
+            source: 1 - source,
+            run_start: 0,
+            left: 0,
+            right: count.min(width),
+            out: 0,
+        });
+    }
+    let middle = count.min(run_start + width);
+    let end = count.min(run_start + width * 2);
+    if out >= end {
+        return MergeStep::Continue(MergeCursor {
+            run_start: end,
+            left: end,
+            right: count.min(end + width),
+            out: end,
+            ..cursor
+        });
+    }
+    let take_left = if left >= middle {
+        false
+    } else if right >= end {
+        true
+    } else {
+        rank_value_at(left) >= rank_value_at(right)
+    };
+    let (from, left, right) = match take_left {
+        true => (left, left + 1, right),
+        false => (right, left, right + 1),
+    };
+    MergeStep::Move {
+        from,
+        to: out,
+        next: MergeCursor {
+            left,
+            right,
+            out: out + 1,
+            ..cursor
+        },
+    }
+}
+
+/// Merge two lists of members which are both ordered by rank value descending, into one
+/// list ordered by rank value descending. The members of the first list are placed before
+/// the members of the second list with the same rank value. The lists are consumed lazily.
+pub fn merge_by_rank_value<T, A, B>(
+    first: A,
+    second: B,
+) -> MergeByRankValue<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator<Item = (T, u128)>,
+    B: IntoIterator<Item = (T, u128)>,
+{
+    MergeByRankValue {
+        first: first.into_iter().peekable(),
+        second: second.into_iter().peekable(),
+    }
+}
+
+/// The iterator returned by `merge_by_rank_value`.
+pub struct MergeByRankValue<A: Iterator, B: Iterator> {
+    first: Peekable<A>,
+    second: Peekable<B>,
+}
+
+impl<T, A, B> Iterator for MergeByRankValue<A, B>
+where
+    A: Iterator<Item = (T, u128)>,
+    B: Iterator<Item = (T, u128)>,
+{
+    type Item = (T, u128);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let take_first = match (self.first.peek(), self.second.peek()) {
+            (Some(first), Some(second)) => first.1 >= second.1,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        match take_first {
+            true => self.first.next(),
+            false => self.second.next(),
+        }
+    }
+}
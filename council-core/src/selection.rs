@@ -0,0 +1,51 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// The members added to and removed from the council.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CouncilDiff<T> {
+    // in the order of the selected members
+    pub added: Vec<T>,
+    // in the order of the current members
+    pub removed: Vec<T>,
+}
+
+impl<T> CouncilDiff<T> {
+    /// Whether the council is not changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Select the council members from the members ordered by rank, by skipping the
+/// excluded ones until the seats are filled. The ranked members are consumed lazily,
+/// so only the members ranked before the last selected one are read.
+pub fn select_council_members<T, I, F>(ranked_members: I, max_seats: u32, is_excluded: F) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    F: Fn(&T) -> bool,
+{
+    ranked_members
+        .into_iter()
+        .filter(|member| !is_excluded(member))
+        .take(max_seats as usize)
+        .collect()
+}
+
+/// The difference between the current council members and the selected ones.
+pub fn diff_council<T: Eq + Hash + Clone>(current: &[T], selected: &[T]) -> CouncilDiff<T> {
+    let current_set: HashSet<&T> = current.iter().collect();
+    let selected_set: HashSet<&T> = selected.iter().collect();
+    CouncilDiff {
+        added: selected
+            .iter()
+            .filter(|member| !current_set.contains(member))
+            .cloned()
+            .collect(),
+        removed: current
+            .iter()
+            .filter(|member| !selected_set.contains(member))
+            .cloned()
+            .collect(),
+    }
+}
@@ -0,0 +1,56 @@
+/// The sums of stakes for calculating the Gini coefficient in one pass,
+/// the stakes must be added in descending order.
+///
+/// For `n` stakes `x` in descending order with 0-based rank `r`, the Gini coefficient is
+/// `((n - 1) * sum(x) - 2 * sum(r * x)) / (n * sum(x))`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GiniSums {
+    // the number of non-zero stakes
+    pub count: u64,
+    // the sum of stakes
+    pub total: u128,
+    // the sum of stakes weighted by their rank
+    pub weighted_total: u128,
+}
+
+impl GiniSums {
+    /// Add the next stake, the zero stakes are not counted.
+    pub fn add(&mut self, stake: u128) {
+        if stake == 0 {
+            return;
+        }
+        self.weighted_total = self
+            .weighted_total
+            .saturating_add(u128::from(self.count).saturating_mul(stake));
+        self.total = self.total.saturating_add(stake);
+        self.count += 1;
+    }
+    /// The Gini coefficient of the added stakes in basis points, 0 for no stake.
+    pub fn coefficient_in_bps(&self) -> u32 {
+        if self.count == 0 {
+            return 0;
+        }
+        let numerator = u128::from(self.count - 1)
+            .saturating_mul(self.total)
+            .saturating_sub(self.weighted_total.saturating_mul(2));
+        share_in_bps(numerator, u128::from(self.count).saturating_mul(self.total))
+    }
+}
+
+/// Whether the stake held by the top validators is enough to halt the network,
+/// that is more than one third of the total stake. The Nakamoto coefficient is
+/// the least number of top validators whose stake reaches this threshold.
+pub fn exceeds_nakamoto_threshold(cumulative_stake: u128, total_stake: u128) -> bool {
+    cumulative_stake > total_stake / 3
+}
+
+/// The share of `part` in `whole` in basis points, `part` must not be greater than `whole`.
+pub fn share_in_bps(part: u128, whole: u128) -> u32 {
+    if whole == 0 {
+        return 0;
+    }
+    match part.checked_mul(10_000) {
+        Some(value) => (value / whole) as u32,
+        None => (part / (whole / 10_000)) as u32,
+    }
+}
@@ -0,0 +1,187 @@
+use council_core::{
+    append_by_rank_value, can_coalesce, diff_council, exceeds_nakamoto_threshold,
+    merge_by_rank_value, merge_step, reposition_by_rank_value, select_council_members,
+    share_in_bps, ChangeKind, CouncilDiff, GiniSums, MergeCursor, MergeStep, RankedStore,
+};
+use std::collections::HashMap;
+
+// A ranking held in memory, the rank value of a member is its stake.
+#[derive(Default)]
+struct MemoryRanking {
+    members: Vec<&'static str>,
+    stakes: HashMap<&'static str, u128>,
+    ranks: HashMap<&'static str, u32>,
+}
+
+impl RankedStore<&'static str> for MemoryRanking {
+    fn len(&self) -> u32 {
+        self.members.len() as u32
+    }
+    fn get(&self, index: u32) -> Option<&'static str> {
+        self.members.get(index as usize).cloned()
+    }
+    fn put(&mut self, index: u32, member: &&'static str) {
+        match (index as usize) < self.members.len() {
+            true => self.members[index as usize] = member,
+            false => self.members.push(member),
+        }
+        self.ranks.insert(member, index);
+    }
+    fn rank_value_of(&self, member: &&'static str) -> u128 {
+        self.stakes[member]
+    }
+}
+
+impl MemoryRanking {
+    fn stake(&mut self, member: &'static str, stake: u128) -> u32 {
+        let is_ranked = self.stakes.insert(member, stake).is_some();
+        match is_ranked {
+            true => {
+                let index = self.ranks[member];
+                reposition_by_rank_value(self, &member, index)
+            }
+            false => append_by_rank_value(self, &member),
+        }
+    }
+}
+
+#[test]
+fn test_ranking_by_rank_value() {
+    let mut ranking = MemoryRanking::default();
+    assert_eq!(ranking.stake("alice", 100), 0);
+    assert_eq!(ranking.stake("bob", 300), 0);
+    assert_eq!(ranking.stake("charlie", 200), 1);
+    // the members with the same rank value keep the order in which they were ranked
+    assert_eq!(ranking.stake("dave", 200), 2);
+    assert_eq!(ranking.members, vec!["bob", "charlie", "dave", "alice"]);
+    // move down and move up
+    assert_eq!(ranking.stake("bob", 150), 2);
+    assert_eq!(ranking.stake("alice", 500), 0);
+    assert_eq!(ranking.members, vec!["alice", "charlie", "dave", "bob"]);
+    for (index, member) in ranking.members.iter().enumerate() {
+        assert_eq!(ranking.ranks[member], index as u32);
+    }
+}
+
+#[test]
+fn test_merge_sort_is_stable() {
+    let records: Vec<(&str, u128)> = vec![
+        ("a", 5),
+        ("b", 9),
+        ("c", 5),
+        ("d", 1),
+        ("e", 9),
+        ("f", 7),
+        ("g", 5),
+    ];
+    let mut buffers = [records.clone(), records.clone()];
+    let mut cursor = MergeCursor::new(records.len() as u32);
+    let sorted_source = loop {
+        let source = cursor.source as usize;
+        match merge_step(cursor, |index| buffers[source][index as usize].1) {
+            MergeStep::Move { from, to, next } => {
+                buffers[1 - source][to as usize] = buffers[source][from as usize];
+                cursor = next;
+            }
+            MergeStep::Continue(next) => cursor = next,
+            MergeStep::Finished { source } => break source as usize,
+        }
+    };
+    let mut expected = records.clone();
+    expected.sort_by_key(|record| std::cmp::Reverse(record.1));
+    assert_eq!(buffers[sorted_source], expected);
+    // nothing to sort
+    assert_eq!(
+        merge_step(MergeCursor::new(1), |_| 0),
+        MergeStep::Finished { source: 0 }
+    );
+}
+
+#[test]
+fn test_merge_by_rank_value() {
+    let ranked = vec![("alice", 9), ("bob", 5), ("charlie", 5), ("dave", 1)];
+    let repositioned = vec![("eve", 7), ("frank", 5), ("grace", 0)];
+    let merged: Vec<&str> = merge_by_rank_value(ranked, repositioned)
+        .map(|(member, _)| member)
+        .collect();
+    // the members of the first list are placed before the ones with the same rank value
+    assert_eq!(
+        merged,
+        vec!["alice", "eve", "bob", "charlie", "frank", "dave", "grace"]
+    );
+    assert_eq!(
+        merge_by_rank_value(Vec::new(), vec![("alice", 1)]).count(),
+        1
+    );
+}
+
+#[test]
+fn test_selection_and_diff() {
+    let ranked = vec!["alice", "bob", "charlie", "dave", "eve"];
+    let selected = select_council_members(ranked.iter().cloned(), 3, |member| *member == "bob");
+    assert_eq!(selected, vec!["alice", "charlie", "dave"]);
+    assert_eq!(
+        select_council_members(ranked.iter().cloned(), 10, |_| false),
+        ranked
+    );
+    //
+    let diff = diff_council(&["dave", "bob", "alice"], &selected);
+    assert_eq!(
+        diff,
+        CouncilDiff {
+            added: vec!["charlie"],
+            removed: vec!["bob"],
+        }
+    );
+    assert!(diff_council(&selected, &selected).is_empty());
+}
+
+#[test]
+fn test_coalescing_of_changes() {
+    assert!(can_coalesce(
+        ChangeKind::MemberAdded,
+        true,
+        ChangeKind::MemberRemoved
+    ));
+    assert!(can_coalesce(
+        ChangeKind::MemberRemoved,
+        true,
+        ChangeKind::MemberAdded
+    ));
+    // the pending change has been submitted to DAO contracts
+    assert!(!can_coalesce(
+        ChangeKind::MemberAdded,
+        false,
+        ChangeKind::MemberRemoved
+    ));
+    assert!(!can_coalesce(
+        ChangeKind::MemberAdded,
+        true,
+        ChangeKind::MemberAdded
+    ));
+}
+
+#[test]
+fn test_decentralization_stats() {
+    let gini_of = |stakes: &[u128]| {
+        let mut sums = GiniSums::default();
+        stakes.iter().for_each(|stake| sums.add(*stake));
+        sums.coefficient_in_bps()
+    };
+    assert_eq!(gini_of(&[]), 0);
+    assert_eq!(gini_of(&[100, 100, 100, 100]), 0);
+    // all stake held by one of four validators, the zero stakes are not counted
+    assert_eq!(gini_of(&[400, 1, 1, 1]), 7425);
+    assert_eq!(gini_of(&[400, 0, 0, 0]), 0);
+    // the sum of |x_i - x_j| over all ordered pairs is 8, divided by 2 * n^2 * mean = 36
+    assert_eq!(gini_of(&[3, 2, 1]), 2222);
+    //
+    assert!(!exceeds_nakamoto_threshold(100, 300));
+    assert!(exceeds_nakamoto_threshold(101, 300));
+    assert!(exceeds_nakamoto_threshold(100, 299));
+    //
+    assert_eq!(share_in_bps(1, 3), 3333);
+    assert_eq!(share_in_bps(0, 0), 0);
+    // the whole is scaled down instead of the part when the part overflows
+    assert_eq!(share_in_bps(u128::MAX / 2, u128::MAX), 5000);
+}
@@ -0,0 +1,168 @@
+use crate::IndexerState;
+use council_keeper::types::{CouncilChangeAction, CouncilChangeHistory};
+use near_sdk::serde_json;
+use std::io::{self, Write};
+
+/// Write the ranked validators in CSV, with header
+/// `rank,validator_id,total_stake,appchain_id,stake_in_appchain`.
+/// There is a row for each appchain the validator staked in.
+pub fn write_validators_csv<W: Write>(state: &IndexerState, writer: &mut W) -> io::Result<()> {
+    writeln!(
+        writer,
+        "rank,validator_id,total_stake,appchain_id,stake_in_appchain"
+    )?;
+    for (rank, validator) in state.ranked_validators().into_iter().enumerate() {
+        for (appchain_id, stake) in &validator.stake_in_appchains {
+            write_csv_row(
+                writer,
+                &[
+                    &rank.to_string(),
+                    validator.validator_id.as_str(),
+                    &validator.total_stake.0.to_string(),
+                    appchain_id,
+                    &stake.0.to_string(),
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the council members in CSV, with header `account_id`.
+pub fn write_council_members_csv<W: Write>(state: &IndexerState, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "account_id")?;
+    for account_id in state.council_members() {
+        write_csv_row(writer, &[account_id.as_str()])?;
+    }
+    Ok(())
+}
+
+/// Write the change histories in CSV, with header
+/// `index,action,account_id,timestamp,running_hash,dao_contract_account,state`.
+/// There is a row for each DAO contract the change is applied to, and the state is in JSON.
+pub fn write_change_histories_csv<W: Write>(
+    state: &IndexerState,
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "index,action,account_id,timestamp,running_hash,dao_contract_account,state"
+    )?;
+    for change_history in state.change_histories() {
+        for (dao_contract_account, target_state) in sorted_target_states(change_history) {
+            write_csv_row(
+                writer,
+                &[
+                    &change_history.index.0.to_string(),
+                    action_name_of(&change_history.action),
+                    change_history.action.account_id().as_str(),
+                    &change_history.timestamp.0.to_string(),
+                    &change_history.running_hash,
+                    &dao_contract_account,
+                    &target_state,
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a SQL script which creates the tables of the rebuilt state and inserts the rows,
+/// it can be loaded to a SQLite database by `sqlite3 <database> < <script>`.
+///
+/// The stakes are stored as text, as they may not fit in the integer of SQLite.
+pub fn write_sqlite_script<W: Write>(state: &IndexerState, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "BEGIN TRANSACTION;")?;
+    writeln!(writer, "DROP TABLE IF EXISTS validator_stakes;")?;
+    writeln!(writer, "DROP TABLE IF EXISTS council_members;")?;
+    writeln!(writer, "DROP TABLE IF EXISTS change_history_states;")?;
+    writeln!(
+        writer,
+        "CREATE TABLE validator_stakes (rank INTEGER NOT NULL, validator_id TEXT NOT NULL, total_stake TEXT NOT NULL, appchain_id TEXT NOT NULL, stake_in_appchain TEXT NOT NULL, PRIMARY KEY (validator_id, appchain_id));"
+    )?;
+    writeln!(
+        writer,
+        "CREATE TABLE council_members (account_id TEXT PRIMARY KEY);"
+    )?;
+    writeln!(
+        writer,
+        "CREATE TABLE change_history_states (history_index INTEGER NOT NULL, action TEXT NOT NULL, account_id TEXT NOT NULL, timestamp INTEGER NOT NULL, running_hash TEXT NOT NULL, dao_contract_account TEXT NOT NULL, state TEXT NOT NULL, PRIMARY KEY (history_index, dao_contract_account));"
+    )?;
+    for (rank, validator) in state.ranked_validators().into_iter().enumerate() {
+        for (appchain_id, stake) in &validator.stake_in_appchains {
+            writeln!(
+                writer,
+                "INSERT INTO validator_stakes VALUES ({}, {}, {}, {}, {});",
+                rank,
+                sql_text(validator.validator_id.as_str()),
+                sql_text(&validator.total_stake.0.to_string()),
+                sql_text(appchain_id),
+                sql_text(&stake.0.to_string()),
+            )?;
+        }
+    }
+    for account_id in state.council_members() {
+        writeln!(
+            writer,
+            "INSERT INTO council_members VALUES ({});",
+            sql_text(account_id.as_str())
+        )?;
+    }
+    for change_history in state.change_histories() {
+        for (dao_contract_account, target_state) in sorted_target_states(change_history) {
+            writeln!(
+                writer,
+                "INSERT INTO change_history_states VALUES ({}, {}, {}, {}, {}, {}, {});",
+                change_history.index.0,
+                sql_text(action_name_of(&change_history.action)),
+                sql_text(change_history.action.account_id().as_str()),
+                change_history.timestamp.0,
+                sql_text(&change_history.running_hash),
+                sql_text(&dao_contract_account),
+                sql_text(&target_state),
+            )?;
+        }
+    }
+    writeln!(writer, "COMMIT;")
+}
+
+//
+fn action_name_of(action: &CouncilChangeAction) -> &'static str {
+    match action {
+        CouncilChangeAction::MemberAdded(_) => "MemberAdded",
+        CouncilChangeAction::MemberRemoved(_) => "MemberRemoved",
+    }
+}
+
+// The states of a change history ordered by the account of DAO contract, the state is in JSON.
+fn sorted_target_states(change_history: &CouncilChangeHistory) -> Vec<(String, String)> {
+    let mut target_states: Vec<(String, String)> = change_history
+        .target_states
+        .iter()
+        .map(|(account_id, state)| {
+            (
+                account_id.to_string(),
+                serde_json::to_string(state).unwrap(),
+            )
+        })
+        .collect();
+    target_states.sort();
+    target_states
+}
+
+// The fields containing comma, quote or line break are quoted, and the quotes are doubled.
+fn write_csv_row<W: Write>(writer: &mut W, fields: &[&str]) -> io::Result<()> {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match field.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.to_string(),
+        })
+        .collect();
+    writeln!(writer, "{}", fields.join(","))
+}
+
+//
+fn sql_text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -0,0 +1,81 @@
+//! Off-chain indexer of council keeper.
+//!
+//! The NEP-297 events emitted by council keeper are decoded from the logs of
+//! transaction outcomes (in the format returned by the `tx` method of NEAR RPC),
+//! and applied to an `IndexerState` which rebuilds the ranking of validators,
+//! the council members and the states of change histories.
+//! The rebuilt state can be exported to CSV files or to a SQL script for SQLite.
+mod export;
+mod outcome;
+mod state;
+
+pub use export::{
+    write_change_histories_csv, write_council_members_csv, write_sqlite_script,
+    write_validators_csv,
+};
+pub use outcome::{
+    decode_events_of_outcome, parse_transaction_outcomes, ExecutionOutcome, ExecutionOutcomeWithId,
+    ExecutionStatus, IndexedEvent, TransactionOutcome,
+};
+pub use state::{IndexedValidator, IndexerState};
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum IndexerError {
+    // the JSON of transaction outcomes can not be parsed
+    InvalidOutcomeJson(String),
+    // the log has prefix `EVENT_JSON:`, but the event can not be parsed
+    InvalidEventJson {
+        receipt_id: String,
+        message: String,
+    },
+    // the major version of the event standard is not supported
+    UnsupportedEventVersion {
+        receipt_id: String,
+        version: String,
+    },
+    // the running hash of a change history is not chained with the previous one
+    MismatchedRunningHash {
+        change_history_index: u64,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for IndexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexerError::InvalidOutcomeJson(message) => {
+                write!(f, "Invalid JSON of transaction outcomes: {}", message)
+            }
+            IndexerError::InvalidEventJson {
+                receipt_id,
+                message,
+            } => write!(
+                f,
+                "Invalid event in logs of receipt '{}': {}",
+                receipt_id, message
+            ),
+            IndexerError::UnsupportedEventVersion {
+                receipt_id,
+                version,
+            } => write!(
+                f,
+                "Unsupported version '{}' of event in logs of receipt '{}'.",
+                version, receipt_id
+            ),
+            IndexerError::MismatchedRunningHash {
+                change_history_index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Mismatched running hash of change history '{}'. Expected: {}, actual: {}.",
+                change_history_index, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndexerError {}
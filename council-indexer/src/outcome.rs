@@ -0,0 +1,134 @@
+use crate::IndexerError;
+use council_keeper::events::{EVENT_STANDARD, EVENT_STANDARD_VERSION};
+use council_keeper::types::CouncilKeeperEvent;
+use near_sdk::serde::Deserialize;
+use near_sdk::serde_json::{self, Value};
+use near_sdk::AccountId;
+
+/// The prefix of the logs which carry NEP-297 events.
+const EVENT_LOG_PREFIX: &str = "EVENT_JSON:";
+
+/// The outcome of a transaction, in the format returned by the `tx` method of NEAR RPC.
+/// Only the fields used by the indexer are decoded.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransactionOutcome {
+    pub transaction_outcome: ExecutionOutcomeWithId,
+    // the outcomes of the receipts in the order of execution
+    pub receipts_outcome: Vec<ExecutionOutcomeWithId>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExecutionOutcomeWithId {
+    pub id: String,
+    pub block_hash: String,
+    pub outcome: ExecutionOutcome,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExecutionOutcome {
+    pub logs: Vec<String>,
+    pub executor_id: AccountId,
+    pub status: ExecutionStatus,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ExecutionStatus {
+    Unknown,
+    Failure(Value),
+    SuccessValue(String),
+    SuccessReceiptId(String),
+}
+
+impl ExecutionStatus {
+    /// Whether the receipt is executed successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            ExecutionStatus::SuccessValue(_) | ExecutionStatus::SuccessReceiptId(_)
+        )
+    }
+}
+
+/// An event emitted by council keeper, with the receipt which emitted it.
+#[derive(Clone)]
+pub struct IndexedEvent {
+    pub receipt_id: String,
+    pub block_hash: String,
+    pub event: CouncilKeeperEvent,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog {
+    version: String,
+    #[serde(flatten)]
+    event: CouncilKeeperEvent,
+}
+
+/// Parse a JSON array of transaction outcomes, which should be in the order of execution.
+pub fn parse_transaction_outcomes(json: &str) -> Result<Vec<TransactionOutcome>, IndexerError> {
+    serde_json::from_str(json).map_err(|err| IndexerError::InvalidOutcomeJson(err.to_string()))
+}
+
+/// Decode the events emitted by the council keeper contract in the outcome of a transaction.
+///
+/// The logs of failed receipts are skipped, as the state changes of them are reverted.
+/// The events of other standards, and the other logs of the contract, are ignored.
+pub fn decode_events_of_outcome(
+    outcome: &TransactionOutcome,
+    council_keeper_account: &AccountId,
+) -> Result<Vec<IndexedEvent>, IndexerError> {
+    let mut events = Vec::new();
+    for receipt_outcome in &outcome.receipts_outcome {
+        if !receipt_outcome
+            .outcome
+            .executor_id
+            .eq(council_keeper_account)
+            || !receipt_outcome.outcome.status.is_success()
+        {
+            continue;
+        }
+        for log in &receipt_outcome.outcome.logs {
+            let json = match log.strip_prefix(EVENT_LOG_PREFIX) {
+                Some(json) => json,
+                None => continue,
+            };
+            let value: Value =
+                serde_json::from_str(json).map_err(|err| IndexerError::InvalidEventJson {
+                    receipt_id: receipt_outcome.id.clone(),
+                    message: err.to_string(),
+                })?;
+            // the events of other standards may be emitted by the contract in future
+            if value.get("standard").and_then(|standard| standard.as_str()) != Some(EVENT_STANDARD)
+            {
+                continue;
+            }
+            let event_log: EventLog =
+                serde_json::from_value(value).map_err(|err| IndexerError::InvalidEventJson {
+                    receipt_id: receipt_outcome.id.clone(),
+                    message: err.to_string(),
+                })?;
+            if major_version_of(&event_log.version) != major_version_of(EVENT_STANDARD_VERSION) {
+                return Err(IndexerError::UnsupportedEventVersion {
+                    receipt_id: receipt_outcome.id.clone(),
+                    version: event_log.version,
+                });
+            }
+            events.push(IndexedEvent {
+                receipt_id: receipt_outcome.id.clone(),
+                block_hash: receipt_outcome.block_hash.clone(),
+                event: event_log.event,
+            });
+        }
+    }
+    Ok(events)
+}
+
+//
+fn major_version_of(version: &str) -> &str {
+    version.split('.').next().unwrap_or(version)
+}
@@ -0,0 +1,350 @@
+use crate::{IndexedEvent, IndexerError};
+use council_keeper::types::{
+    self, ChangeHistoryRolledBackEventData, CouncilChangeAction, CouncilChangeHistory,
+    CouncilChangeHistoryState, CouncilKeeperEvent, CouncilMemberEventData, CouncilResetRecord,
+    DaoTarget, ParameterChangedEventData,
+};
+use near_sdk::json_types::U128;
+use near_sdk::serde_json;
+use near_sdk::AccountId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The stake and rank of a validator rebuilt from events.
+#[derive(Clone, Debug)]
+pub struct IndexedValidator {
+    pub validator_id: AccountId,
+    // key: appchain id
+    pub stake_in_appchains: BTreeMap<String, U128>,
+    pub total_stake: U128,
+    // the rank in the latest `rank_changed` event of the validator
+    pub reported_rank: Option<u32>,
+}
+
+/// The state of council keeper rebuilt from events.
+///
+/// The events need to be applied from the deployment of the contract in the order of execution.
+/// The states of change histories are taken from the events, and the running hash of
+/// each new change history is verified against the previous one.
+pub struct IndexerState {
+    validators: HashMap<AccountId, IndexedValidator>,
+    council_members: BTreeSet<AccountId>,
+    change_histories: BTreeMap<u64, CouncilChangeHistory>,
+    // the running hash of the last pruned or discarded change history
+    pruned_running_hash: String,
+    council_reset_records: Vec<CouncilResetRecord>,
+    dao_targets: BTreeSet<AccountId>,
+    excluding_validators: BTreeSet<AccountId>,
+    // key: name of parameter, value: the latest value in JSON
+    parameters: BTreeMap<String, String>,
+    applied_events: u64,
+}
+
+impl IndexerState {
+    /// The DAO contract passed to the initialization of council keeper
+    /// is the only DAO target before any `parameter_changed` event.
+    pub fn new(initial_dao_targets: Vec<AccountId>) -> Self {
+        Self {
+            validators: HashMap::new(),
+            council_members: BTreeSet::new(),
+            change_histories: BTreeMap::new(),
+            pruned_running_hash: String::new(),
+            council_reset_records: Vec::new(),
+            dao_targets: initial_dao_targets.into_iter().collect(),
+            excluding_validators: BTreeSet::new(),
+            parameters: BTreeMap::new(),
+            applied_events: 0,
+        }
+    }
+    /// Apply the events decoded from a transaction outcome, in the order of them.
+    pub fn apply_events(&mut self, events: &[IndexedEvent]) -> Result<(), IndexerError> {
+        for indexed_event in events {
+            self.apply_event(&indexed_event.event)?;
+        }
+        Ok(())
+    }
+    /// Apply an event to the state. An error is returned if the running hash of
+    /// a new change history doesn't match the previous one.
+    pub fn apply_event(&mut self, event: &CouncilKeeperEvent) -> Result<(), IndexerError> {
+        match event {
+            CouncilKeeperEvent::StakeSynced(data) => {
+                for stake in data {
+                    let validator = self.validator_entry(&stake.validator_id);
+                    validator
+                        .stake_in_appchains
+                        .insert(stake.appchain_id.clone(), stake.stake);
+                    validator.total_stake = stake.total_stake;
+                }
+            }
+            CouncilKeeperEvent::RankChanged(data) => {
+                for rank in data {
+                    let validator = self.validator_entry(&rank.validator_id);
+                    validator.total_stake = rank.total_stake;
+                    validator.reported_rank = Some(rank.rank);
+                }
+            }
+            CouncilKeeperEvent::MemberAdded(data) => {
+                for member in data {
+                    self.council_members.insert(member.account_id.clone());
+                    self.append_change_history(
+                        CouncilChangeAction::MemberAdded(member.account_id.clone()),
+                        member,
+                    )?;
+                }
+            }
+            CouncilKeeperEvent::MemberRemoved(data) => {
+                for member in data {
+                    self.council_members.remove(&member.account_id);
+                    self.append_change_history(
+                        CouncilChangeAction::MemberRemoved(member.account_id.clone()),
+                        member,
+                    )?;
+                }
+            }
+            CouncilKeeperEvent::ProposalSubmitting(data)
+            | CouncilKeeperEvent::ProposalSubmitted(data)
+            | CouncilKeeperEvent::ProposalApproved(data) => {
+                for proposal in data {
+                    self.set_target_state(
+                        proposal.change_history_index.0,
+                        &proposal.dao_contract_account,
+                        proposal.state.clone(),
+                    );
+                }
+            }
+            CouncilKeeperEvent::ProposalFailed(data) => {
+                for proposal in data {
+                    if let Some(state) = &proposal.state {
+                        self.set_target_state(
+                            proposal.change_history_index.0,
+                            &proposal.dao_contract_account,
+                            state.clone(),
+                        );
+                    }
+                }
+            }
+            CouncilKeeperEvent::ProposalSubmissionTimedOut(data) => {
+                for submission in data {
+                    self.set_target_state(
+                        submission.change_history_index.0,
+                        &submission.dao_contract_account,
+                        CouncilChangeHistoryState::WaitingForApplying,
+                    );
+                }
+            }
+            CouncilKeeperEvent::ExclusionToggled(data) => {
+                for exclusion in data {
+                    match exclusion.is_excluded {
+                        true => self
+                            .excluding_validators
+                            .insert(exclusion.validator_id.clone()),
+                        false => self.excluding_validators.remove(&exclusion.validator_id),
+                    };
+                }
+            }
+            CouncilKeeperEvent::ParameterChanged(data) => {
+                for parameter in data {
+                    self.apply_parameter_change(parameter);
+                }
+            }
+            CouncilKeeperEvent::CouncilReset(data) => {
+                // the previous members are dropped by the following `member_removed` event
+                for record in data {
+                    self.discard_change_histories_before(record.discarded_end_index.0 + 1);
+                    self.council_reset_records.push(record.clone());
+                }
+            }
+            CouncilKeeperEvent::ChangeHistoriesRolledBack(data) => {
+                for rolled_back in data {
+                    self.roll_back_change_history(rolled_back);
+                }
+            }
+            CouncilKeeperEvent::ChangeHistoriesPruned(data) => {
+                for pruned in data {
+                    self.discard_change_histories_before(pruned.before_index.0);
+                    self.pruned_running_hash = pruned.running_hash.clone();
+                }
+            }
+        }
+        self.applied_events += 1;
+        Ok(())
+    }
+    /// The validators ordered by total stake. The validators with the same total stake
+    /// are ordered by the reported rank, and the ones without reported rank are not included.
+    pub fn ranked_validators(&self) -> Vec<&IndexedValidator> {
+        let mut validators: Vec<&IndexedValidator> = self
+            .validators
+            .values()
+            .filter(|validator| validator.reported_rank.is_some())
+            .collect();
+        validators.sort_by(|a, b| {
+            b.total_stake
+                .0
+                .cmp(&a.total_stake.0)
+                .then(a.reported_rank.cmp(&b.reported_rank))
+                .then(a.validator_id.cmp(&b.validator_id))
+        });
+        validators
+    }
+    /// The stake and reported rank of a validator.
+    pub fn get_validator(&self, validator_id: &AccountId) -> Option<&IndexedValidator> {
+        self.validators.get(validator_id)
+    }
+    /// The council members ordered by account id.
+    pub fn council_members(&self) -> Vec<AccountId> {
+        self.council_members.iter().cloned().collect()
+    }
+    /// The change histories ordered by index.
+    pub fn change_histories(&self) -> Vec<&CouncilChangeHistory> {
+        self.change_histories.values().collect()
+    }
+    /// The change history of the index.
+    pub fn get_change_history(&self, index: u64) -> Option<&CouncilChangeHistory> {
+        self.change_histories.get(&index)
+    }
+    /// The running hash of the last pruned or discarded change history.
+    pub fn pruned_running_hash(&self) -> &String {
+        &self.pruned_running_hash
+    }
+    /// The records of council resets, in the order of them.
+    pub fn council_reset_records(&self) -> &[CouncilResetRecord] {
+        &self.council_reset_records
+    }
+    /// The DAO contracts which the council changes are applied to.
+    pub fn dao_targets(&self) -> Vec<AccountId> {
+        self.dao_targets.iter().cloned().collect()
+    }
+    /// The validators which excluded themselves from the council.
+    pub fn excluding_validators(&self) -> Vec<AccountId> {
+        self.excluding_validators.iter().cloned().collect()
+    }
+    /// The latest value of a parameter in JSON, if it has been changed.
+    pub fn get_parameter(&self, parameter: &str) -> Option<&String> {
+        self.parameters.get(parameter)
+    }
+    /// The number of events applied to the state.
+    pub fn applied_events(&self) -> u64 {
+        self.applied_events
+    }
+    //
+    fn validator_entry(&mut self, validator_id: &AccountId) -> &mut IndexedValidator {
+        self.validators
+            .entry(validator_id.clone())
+            .or_insert_with(|| IndexedValidator {
+                validator_id: validator_id.clone(),
+                stake_in_appchains: BTreeMap::new(),
+                total_stake: U128(0),
+                reported_rank: None,
+            })
+    }
+    // The change history of the reset of council has no index, and is not recorded.
+    fn append_change_history(
+        &mut self,
+        action: CouncilChangeAction,
+        member: &CouncilMemberEventData,
+    ) -> Result<(), IndexerError> {
+        let (index, running_hash) = match (member.change_history_index, &member.running_hash) {
+            (Some(index), Some(running_hash)) => (index, running_hash.clone()),
+            _ => return Ok(()),
+        };
+        let change_history = CouncilChangeHistory {
+            index,
+            action,
+            target_states: member.target_states.clone(),
+            timestamp: member.timestamp,
+            running_hash,
+        };
+        let previous_running_hash = self
+            .change_histories
+            .values()
+            .next_back()
+            .map_or(&self.pruned_running_hash, |previous| &previous.running_hash);
+        let expected_running_hash = types::to_hex(
+            &change_history.running_hash_with(&types::from_hex(previous_running_hash)),
+        );
+        if !expected_running_hash.eq(&change_history.running_hash) {
+            return Err(IndexerError::MismatchedRunningHash {
+                change_history_index: index.0,
+                expected: expected_running_hash,
+                actual: change_history.running_hash,
+            });
+        }
+        // the pending change history of the same account is cancelled out by the new one
+        if let Some(cancelled_index) = member.cancelled_change_history_index {
+            if let Some(cancelled_history) = self.change_histories.get_mut(&cancelled_index.0) {
+                cancelled_history
+                    .set_state_of_all_targets(CouncilChangeHistoryState::NoNeedToApply);
+            }
+        }
+        self.change_histories.insert(index.0, change_history);
+        Ok(())
+    }
+    // Remove the change histories before the index, and keep the running hash of
+    // the last removed one for verifying the following change histories.
+    fn discard_change_histories_before(&mut self, before_index: u64) {
+        let kept_histories = self.change_histories.split_off(&before_index);
+        if let Some(last_history) = self.change_histories.values().next_back() {
+            self.pruned_running_hash = last_history.running_hash.clone();
+        }
+        self.change_histories = kept_histories;
+    }
+    // Same as `revert_change_history` of the contract.
+    fn roll_back_change_history(&mut self, rolled_back: &ChangeHistoryRolledBackEventData) {
+        if let Some(change_history) = self
+            .change_histories
+            .remove(&rolled_back.change_history_index.0)
+        {
+            match change_history.action {
+                CouncilChangeAction::MemberAdded(account_id) => {
+                    self.council_members.remove(&account_id);
+                }
+                CouncilChangeAction::MemberRemoved(account_id) => {
+                    self.council_members.insert(account_id);
+                }
+            }
+        }
+        if let Some(restored_index) = rolled_back.restored_change_history_index {
+            if let Some(restored_history) = self.change_histories.get_mut(&restored_index.0) {
+                restored_history
+                    .set_state_of_all_targets(CouncilChangeHistoryState::WaitingForApplying);
+            }
+        }
+    }
+    //
+    fn set_target_state(
+        &mut self,
+        index: u64,
+        dao_contract_account: &AccountId,
+        state: CouncilChangeHistoryState,
+    ) {
+        if let Some(change_history) = self.change_histories.get_mut(&index) {
+            change_history
+                .target_states
+                .insert(dao_contract_account.clone(), state);
+        }
+    }
+    //
+    fn apply_parameter_change(&mut self, parameter: &ParameterChangedEventData) {
+        if parameter.parameter.eq("dao_target") {
+            let previous: Option<DaoTarget> =
+                serde_json::from_str(&parameter.previous_value).unwrap_or(None);
+            let current: Option<DaoTarget> = serde_json::from_str(&parameter.value).unwrap_or(None);
+            match (previous, current) {
+                (_, Some(dao_target)) => {
+                    self.dao_targets.insert(dao_target.dao_contract_account);
+                }
+                (Some(dao_target), None) => {
+                    // the states of change histories for the DAO contract are dropped
+                    for change_history in self.change_histories.values_mut() {
+                        change_history
+                            .target_states
+                            .remove(&dao_target.dao_contract_account);
+                    }
+                    self.dao_targets.remove(&dao_target.dao_contract_account);
+                }
+                (None, None) => (),
+            }
+        }
+        self.parameters
+            .insert(parameter.parameter.clone(), parameter.value.clone());
+    }
+}
@@ -0,0 +1,500 @@
+use council_indexer::{
+    decode_events_of_outcome, parse_transaction_outcomes, write_change_histories_csv,
+    write_council_members_csv, write_sqlite_script, write_validators_csv, IndexerError,
+    IndexerState,
+};
+use council_keeper::types::{
+    CouncilChangeHistoryState, DaoCallOfChangeHistory, MultiTxsOperationProcessingResult,
+    ValidatorStakeRecord,
+};
+use council_keeper::{CouncilKeeper, ResolverForSelfCallback};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::mock::VmAction;
+use near_sdk::serde_json::{self, json, Value};
+use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+use near_sdk::{testing_env, AccountId, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+const COUNCIL_KEEPER_ACCOUNT: &str = "octopus-council.registry.test_oct.testnet";
+const DAO_CONTRACT_ACCOUNT: &str = "octopus-dao.sputnikv2.testnet";
+const APPCHAIN_ANCHOR_ACCOUNT: &str = "appchain1.registry.test_oct.testnet";
+const FIXTURE_PATH: &str = "tests/fixtures/transaction_outcomes.json";
+/// Set this environment variable to regenerate the fixture after changing the contract.
+const UPDATE_FIXTURE_ENV: &str = "UPDATE_FIXTURES";
+const START_TIMESTAMP: u64 = 1672531200000000000;
+const NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+
+fn account(account_id: &str) -> AccountId {
+    account_id.parse().unwrap()
+}
+
+/// The receipts of a transaction executed by the contract in unit testing environment.
+struct ScenarioTransaction {
+    signer_id: String,
+    // (executor id, logs, whether the receipt is successful)
+    receipts: Vec<(String, Vec<String>, bool)>,
+}
+
+/// Run the council keeper contract in unit testing environment, and record the logs
+/// of each call in the format of transaction outcomes returned by NEAR RPC.
+struct Scenario {
+    contract: CouncilKeeper,
+    timestamp: u64,
+    transactions: Vec<ScenarioTransaction>,
+}
+
+impl Scenario {
+    fn new(max_number_of_council_members: u32) -> Self {
+        set_context(COUNCIL_KEEPER_ACCOUNT, START_TIMESTAMP, 0, Vec::new());
+        Self {
+            contract: CouncilKeeper::new(
+                max_number_of_council_members,
+                account(DAO_CONTRACT_ACCOUNT),
+            ),
+            timestamp: START_TIMESTAMP,
+            transactions: Vec::new(),
+        }
+    }
+    // Call the contract in a new transaction, return the result and the created receipts.
+    fn call<R>(
+        &mut self,
+        signer_id: &str,
+        deposit: u128,
+        call: impl FnOnce(&mut CouncilKeeper) -> R,
+    ) -> (R, Vec<VmAction>) {
+        self.timestamp += 60_000_000_000;
+        self.transactions.push(ScenarioTransaction {
+            signer_id: signer_id.to_string(),
+            receipts: Vec::new(),
+        });
+        self.call_in_receipt(signer_id, deposit, Vec::new(), call)
+    }
+    // Call the contract in a receipt of the latest transaction, such as a callback.
+    fn call_in_receipt<R>(
+        &mut self,
+        predecessor_id: &str,
+        deposit: u128,
+        promise_results: Vec<PromiseResult>,
+        call: impl FnOnce(&mut CouncilKeeper) -> R,
+    ) -> (R, Vec<VmAction>) {
+        set_context(predecessor_id, self.timestamp, deposit, promise_results);
+        let result = call(&mut self.contract);
+        self.transactions.last_mut().unwrap().receipts.push((
+            COUNCIL_KEEPER_ACCOUNT.to_string(),
+            get_logs(),
+            true,
+        ));
+        let actions = get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .collect();
+        (result, actions)
+    }
+    //
+    fn sync_stakes(&mut self, stakes: &[(&str, u128)]) {
+        let stake_records = stakes
+            .iter()
+            .map(|(validator_id, stake)| ValidatorStakeRecord {
+                validator_id: account(validator_id),
+                total_stake: U128(stake * NEAR),
+            })
+            .collect();
+        self.call(APPCHAIN_ANCHOR_ACCOUNT, 0, |contract| {
+            contract.sync_validator_stakes_of_anchor(stake_records)
+        });
+        for _ in 0..10 {
+            let (result, _) = self.call(COUNCIL_KEEPER_ACCOUNT, 0, |contract| {
+                contract.update_council_change_histories()
+            });
+            if matches!(result, MultiTxsOperationProcessingResult::Ok) {
+                return;
+            }
+        }
+        panic!("The change histories are not updated.");
+    }
+    // Apply the change histories, and resolve the `add_proposal` calls by the given results.
+    // The proposals added successfully are voted with the given results of `act_proposal`.
+    fn apply_change_histories(
+        &mut self,
+        add_proposal_results: Vec<PromiseResult>,
+        act_proposal_results: Vec<PromiseResult>,
+    ) {
+        let (_, actions) = self.call(COUNCIL_KEEPER_ACCOUNT, 0, |contract| {
+            contract.apply_change_histories_to_dao_contract(U64(0))
+        });
+        let dao_calls = dao_calls_of(&actions, "resolve_add_proposals");
+        if add_proposal_results.is_empty() {
+            return;
+        }
+        let (_, actions) = self.call_in_receipt(
+            COUNCIL_KEEPER_ACCOUNT,
+            0,
+            add_proposal_results,
+            |contract| contract.resolve_add_proposals(dao_calls),
+        );
+        if act_proposal_results.is_empty() {
+            return;
+        }
+        let dao_calls = dao_calls_of(&actions, "resolve_act_proposals");
+        self.call_in_receipt(
+            COUNCIL_KEEPER_ACCOUNT,
+            0,
+            act_proposal_results,
+            |contract| contract.resolve_act_proposals(dao_calls),
+        );
+    }
+    //
+    fn outcomes_json(&self) -> String {
+        let outcomes: Vec<Value> = self
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(tx_index, transaction)| {
+                let receipts_outcome: Vec<Value> = transaction
+                    .receipts
+                    .iter()
+                    .enumerate()
+                    .map(|(receipt_index, (executor_id, logs, is_success))| {
+                        execution_outcome(
+                            format!("Receipt{:03}{:02}", tx_index, receipt_index),
+                            format!("Block{:03}{:02}", tx_index, receipt_index),
+                            executor_id,
+                            logs,
+                            match is_success {
+                                true => json!({ "SuccessValue": "" }),
+                                false => json!({ "Failure": { "ActionError": { "index": 0 } } }),
+                            },
+                        )
+                    })
+                    .collect();
+                json!({
+                    "status": { "SuccessValue": "" },
+                    "transaction": {
+                        "signer_id": transaction.signer_id,
+                        "receiver_id": COUNCIL_KEEPER_ACCOUNT,
+                    },
+                    "transaction_outcome": execution_outcome(
+                        format!("Transaction{:03}", tx_index),
+                        format!("Block{:03}", tx_index),
+                        &transaction.signer_id,
+                        &[],
+                        json!({ "SuccessReceiptId": format!("Receipt{:03}00", tx_index) }),
+                    ),
+                    "receipts_outcome": receipts_outcome,
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&outcomes).unwrap() + "\n"
+    }
+}
+
+fn set_context(
+    predecessor_id: &str,
+    timestamp: u64,
+    deposit: u128,
+    promise_results: Vec<PromiseResult>,
+) {
+    let context = VMContextBuilder::new()
+        .current_account_id(account(COUNCIL_KEEPER_ACCOUNT))
+        .signer_account_id(account(predecessor_id))
+        .predecessor_account_id(account(predecessor_id))
+        .block_timestamp(timestamp)
+        .attached_deposit(deposit)
+        .account_balance(100 * NEAR)
+        .build();
+    testing_env!(
+        context,
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        promise_results
+    );
+}
+
+fn dao_calls_of(actions: &[VmAction], function_name: &str) -> Vec<DaoCallOfChangeHistory> {
+    #[derive(near_sdk::serde::Deserialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct Args {
+        dao_calls: Vec<DaoCallOfChangeHistory>,
+    }
+    actions
+        .iter()
+        .find_map(|action| match action {
+            VmAction::FunctionCall {
+                function_name: name,
+                args,
+                ..
+            } if name.eq(function_name) => {
+                Some(serde_json::from_slice::<Args>(args).unwrap().dao_calls)
+            }
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn execution_outcome(
+    id: String,
+    block_hash: String,
+    executor_id: &str,
+    logs: &[String],
+    status: Value,
+) -> Value {
+    json!({
+        "id": id,
+        "block_hash": block_hash,
+        "outcome": {
+            "logs": logs,
+            "receipt_ids": [],
+            "executor_id": executor_id,
+            "status": status,
+        }
+    })
+}
+
+fn proposal_id_result(proposal_id: u64) -> PromiseResult {
+    PromiseResult::Successful(serde_json::to_vec(&proposal_id).unwrap())
+}
+
+/// Run the contract through the life cycle of change histories, including the timeout,
+/// rollback and pruning of them and the reset of council.
+fn run_scenario() -> Scenario {
+    let mut scenario = Scenario::new(2);
+    scenario.sync_stakes(&[
+        ("alice-octopus.testnet", 300),
+        ("bob-octopus.testnet", 200),
+        ("charlie-octopus.testnet", 100),
+    ]);
+    // sync the proposal bond from the policy of DAO contract
+    scenario.call(COUNCIL_KEEPER_ACCOUNT, 0, |contract| {
+        contract.sync_proposal_bond_of_dao_contract(account(DAO_CONTRACT_ACCOUNT))
+    });
+    scenario.call_in_receipt(
+        COUNCIL_KEEPER_ACCOUNT,
+        0,
+        vec![PromiseResult::Successful(
+            serde_json::to_vec(&json!({ "proposal_bond": U128(NEAR) })).unwrap(),
+        )],
+        |contract| contract.resolve_get_policy(account(DAO_CONTRACT_ACCOUNT)),
+    );
+    scenario.call(COUNCIL_KEEPER_ACCOUNT, 10 * NEAR, |contract| {
+        contract.fund_keeper()
+    });
+    // the proposal of history 0 is approved, and the one of history 1 fails to be added
+    scenario.apply_change_histories(
+        vec![proposal_id_result(10), PromiseResult::Failed],
+        vec![PromiseResult::Successful(Vec::new())],
+    );
+    // alice is replaced by charlie, and then returns to the council,
+    // the pending changes cancel each other out
+    scenario.call("alice-octopus.testnet", 0, |contract| {
+        contract.exclude_validator_from_council()
+    });
+    scenario.call("alice-octopus.testnet", 0, |contract| {
+        contract.recover_excluding_validator()
+    });
+    scenario.call(COUNCIL_KEEPER_ACCOUNT, 0, |contract| {
+        contract.set_max_number_of_council_members(3)
+    });
+    // the callback of submitting proposals is lost, they are submitted again after timeout
+    scenario.apply_change_histories(Vec::new(), Vec::new());
+    scenario.timestamp += 2 * 3600 * 1_000_000_000;
+    scenario.apply_change_histories(
+        vec![proposal_id_result(11), proposal_id_result(12)],
+        vec![PromiseResult::Successful(Vec::new()), PromiseResult::Failed],
+    );
+    // the changes caused by dave are rolled back
+    scenario.sync_stakes(&[("dave-octopus.testnet", 500)]);
+    scenario.call(COUNCIL_KEEPER_ACCOUNT, 0, |contract| {
+        contract.rollback_change_histories_after(U64(6))
+    });
+    scenario.call(COUNCIL_KEEPER_ACCOUNT, 0, |contract| {
+        contract.prune_change_histories(U64(2))
+    });
+    scenario.call(COUNCIL_KEEPER_ACCOUNT, 0, |contract| {
+        contract.clear_council_members_and_regenerate_change_histories(
+            "Reset council after the rollback.".to_string(),
+        )
+    });
+    // the events of failed receipts and of other standards are skipped by the indexer
+    let logs = scenario.transactions[0].receipts[0].1.clone();
+    scenario.transactions.push(ScenarioTransaction {
+        signer_id: APPCHAIN_ANCHOR_ACCOUNT.to_string(),
+        receipts: vec![
+            (COUNCIL_KEEPER_ACCOUNT.to_string(), logs, false),
+            (
+                COUNCIL_KEEPER_ACCOUNT.to_string(),
+                vec![
+                    "EVENT_JSON:{\"standard\":\"nep171\",\"version\":\"1.0.0\",\"event\":\"nft_mint\",\"data\":[]}".to_string(),
+                    "A plain log of council keeper.".to_string(),
+                ],
+                true,
+            ),
+        ],
+    });
+    scenario
+}
+
+fn rebuild_state_from_json(json: &str) -> Result<IndexerState, IndexerError> {
+    let outcomes = parse_transaction_outcomes(json)?;
+    let mut state = IndexerState::new(vec![account(DAO_CONTRACT_ACCOUNT)]);
+    for outcome in &outcomes {
+        let events = decode_events_of_outcome(outcome, &account(COUNCIL_KEEPER_ACCOUNT))?;
+        state.apply_events(&events)?;
+    }
+    Ok(state)
+}
+
+fn rebuild_state_from_fixture() -> IndexerState {
+    rebuild_state_from_json(include_str!("fixtures/transaction_outcomes.json")).unwrap()
+}
+
+fn state_of_history(state: &IndexerState, index: u64) -> CouncilChangeHistoryState {
+    state
+        .get_change_history(index)
+        .unwrap()
+        .target_states
+        .get(&account(DAO_CONTRACT_ACCOUNT))
+        .unwrap()
+        .clone()
+}
+
+#[test]
+fn test_fixture_is_generated_by_contract() {
+    let scenario = run_scenario();
+    let json = scenario.outcomes_json();
+    if std::env::var(UPDATE_FIXTURE_ENV).is_ok() {
+        std::fs::write(FIXTURE_PATH, &json).unwrap();
+    }
+    assert!(
+        json.eq(&std::fs::read_to_string(FIXTURE_PATH).unwrap()),
+        "The fixture is outdated. Please run the test with '{}=1' to regenerate it.",
+        UPDATE_FIXTURE_ENV
+    );
+    // the state rebuilt from events is the same as the state of the contract
+    let state = rebuild_state_from_json(&json).unwrap();
+    let mut council_members = scenario.contract.get_council_members();
+    council_members.sort();
+    assert_eq!(state.council_members(), council_members);
+    assert_eq!(
+        serde_json::to_value(state.change_histories()).unwrap(),
+        serde_json::to_value(scenario.contract.get_council_change_histories(U64(0), None)).unwrap()
+    );
+    assert_eq!(
+        state.pruned_running_hash(),
+        &scenario
+            .contract
+            .get_change_history_chain()
+            .pruned_running_hash
+    );
+    assert_eq!(
+        serde_json::to_value(state.council_reset_records()).unwrap(),
+        serde_json::to_value(scenario.contract.get_council_reset_records()).unwrap()
+    );
+}
+
+#[test]
+fn test_rebuild_state_from_transaction_outcomes() {
+    let state = rebuild_state_from_fixture();
+    // the events in the failed receipt and the events of other standards are skipped
+    assert_eq!(state.applied_events(), 33);
+    //
+    let ranked_validators: Vec<String> = state
+        .ranked_validators()
+        .iter()
+        .map(|validator| validator.validator_id.to_string())
+        .collect();
+    assert_eq!(
+        ranked_validators,
+        vec![
+            "dave-octopus.testnet",
+            "alice-octopus.testnet",
+            "bob-octopus.testnet",
+            "charlie-octopus.testnet"
+        ]
+    );
+    assert_eq!(
+        state.council_members(),
+        vec![
+            account("alice-octopus.testnet"),
+            account("bob-octopus.testnet"),
+            account("dave-octopus.testnet")
+        ]
+    );
+    assert!(state.excluding_validators().is_empty());
+    assert_eq!(
+        state.get_parameter("max_number_of_council_members"),
+        Some(&"3".to_string())
+    );
+    // the change histories before the reset are pruned or discarded,
+    // the regenerated ones are chained after the last discarded one,
+    // and the indexes of the rolled back ones are not reused
+    assert_eq!(state.council_reset_records().len(), 1);
+    assert_eq!(state.change_histories().len(), 3);
+    assert!(state.get_change_history(7).is_none());
+    assert!(state.get_change_history(8).is_none());
+    for index in 9..12 {
+        assert!(matches!(
+            state_of_history(&state, index),
+            CouncilChangeHistoryState::WaitingForApplying
+        ));
+    }
+}
+
+#[test]
+fn test_mismatched_running_hash_is_rejected() {
+    let json = include_str!("fixtures/transaction_outcomes.json");
+    let state = rebuild_state_from_fixture();
+    let running_hash = &state.change_histories()[0].running_hash;
+    let tampered_json = json.replace(running_hash, &"0".repeat(64));
+    assert!(matches!(
+        rebuild_state_from_json(&tampered_json),
+        Err(IndexerError::MismatchedRunningHash { .. })
+    ));
+}
+
+#[test]
+fn test_export_rebuilt_state() {
+    let state = rebuild_state_from_fixture();
+    //
+    let mut validators_csv = Vec::new();
+    write_validators_csv(&state, &mut validators_csv).unwrap();
+    let validators_csv = String::from_utf8(validators_csv).unwrap();
+    assert_eq!(
+        validators_csv.lines().collect::<Vec<&str>>(),
+        vec![
+            "rank,validator_id,total_stake,appchain_id,stake_in_appchain",
+            "0,dave-octopus.testnet,500000000000000000000000000,appchain1,500000000000000000000000000",
+            "1,alice-octopus.testnet,300000000000000000000000000,appchain1,300000000000000000000000000",
+            "2,bob-octopus.testnet,200000000000000000000000000,appchain1,200000000000000000000000000",
+            "3,charlie-octopus.testnet,100000000000000000000000000,appchain1,100000000000000000000000000",
+        ]
+    );
+    //
+    let mut council_members_csv = Vec::new();
+    write_council_members_csv(&state, &mut council_members_csv).unwrap();
+    assert_eq!(
+        String::from_utf8(council_members_csv).unwrap(),
+        "account_id\nalice-octopus.testnet\nbob-octopus.testnet\ndave-octopus.testnet\n"
+    );
+    //
+    let mut change_histories_csv = Vec::new();
+    write_change_histories_csv(&state, &mut change_histories_csv).unwrap();
+    let change_histories_csv = String::from_utf8(change_histories_csv).unwrap();
+    let lines: Vec<&str> = change_histories_csv.lines().collect();
+    assert_eq!(lines.len(), state.change_histories().len() + 1);
+    //
+    let mut sqlite_script = Vec::new();
+    write_sqlite_script(&state, &mut sqlite_script).unwrap();
+    let sqlite_script = String::from_utf8(sqlite_script).unwrap();
+    assert!(sqlite_script.starts_with("BEGIN TRANSACTION;\n"));
+    assert!(sqlite_script.ends_with("COMMIT;\n"));
+    assert_eq!(
+        sqlite_script
+            .matches("INSERT INTO validator_stakes")
+            .count(),
+        4
+    );
+    assert_eq!(
+        sqlite_script.matches("INSERT INTO council_members").count(),
+        3
+    );
+}
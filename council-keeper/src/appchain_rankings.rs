@@ -0,0 +1,552 @@
+use crate::*;
+use council_core::MergeCursor;
+use rank_rebuild::{
+    collected_unranked_validators, rank_rebuilding_buffer, APPCHAIN_RANK_BUFFER_BASE,
+    BULK_REBUILD_THRESHOLD_PERCENT, MIN_WAITING_VALIDATORS_FOR_BULK_REBUILD,
+};
+use ranked_lookup_array::{RankValueHolder, RankedLookupArray};
+use types::{AppchainStakeSummary, AppchainValidatorStake};
+
+/// The validators of an appchain ordered by their stake in the appchain.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct AppchainRanking {
+    validators: RankedLookupArray<AccountId>,
+    // the validators whose stake in the appchain is changed but not re-ranked yet
+    waiting_validators: UnorderedSet<AccountId>,
+    // the total stake of all validators in the appchain
+    total_stake: U128,
+}
+
+// The stake in an appchain as the rank value of validators, the ranks are kept
+// in `appchain_validator_ranks`.
+struct AppchainRankValueHolder<'a> {
+    appchain_id: &'a String,
+    validator_stakes: &'a LookupMap<AccountId, InternalValidatorStake>,
+    appchain_validator_ranks: &'a mut LookupMap<(String, AccountId), u32>,
+}
+
+impl RankValueHolder<AccountId> for AppchainRankValueHolder<'_> {
+    //
+    fn get_rank_value_of(&self, member: &AccountId) -> u128 {
+        stake_in_appchain_of(self.validator_stakes, member, self.appchain_id)
+    }
+    //
+    fn update_rank_of(&mut self, member: &AccountId, new_rank: u32) {
+        self.appchain_validator_ranks
+            .insert(&(self.appchain_id.clone(), member.clone()), &new_rank);
+    }
+}
+
+fn stake_in_appchain_of(
+    validator_stakes: &LookupMap<AccountId, InternalValidatorStake>,
+    validator_id: &AccountId,
+    appchain_id: &String,
+) -> u128 {
+    validator_stakes
+        .get(validator_id)
+        .and_then(|validator_stake| validator_stake.stake_in_appchains.get(appchain_id))
+        .map_or(0, |stake| stake.0)
+}
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Get the validators of an appchain ordered by their stake in the appchain,
+    /// with pagination. The result is empty if `start_index` is out of bound.
+    ///
+    /// The validators waiting to update rank in the appchain keep their previous position,
+    /// until they are re-ranked by `update_council_change_histories`.
+    pub fn get_appchain_validator_stakes(
+        &self,
+        appchain_id: String,
+        start_index: u32,
+        quantity: Option<u32>,
+    ) -> Vec<AppchainValidatorStake> {
+        let ranking = match self.appchain_rankings.get(&appchain_id) {
+            Some(ranking) => ranking,
+            None => return Vec::new(),
+        };
+        ranking
+            .validators
+            .get_slice_of(start_index, quantity)
+            .into_iter()
+            .zip(start_index..)
+            .filter_map(|(validator_id, rank_in_appchain)| {
+                self.validator_stakes
+                    .get(&validator_id)
+                    .map(|validator_stake| AppchainValidatorStake {
+                        stake: validator_stake
+                            .stake_in_appchains
+                            .get(&appchain_id)
+                            .unwrap_or(U128(0)),
+                        rank_in_appchain,
+                        total_stake: validator_stake.total_stake,
+                        is_council_member: self.latest_members.contains(&validator_id),
+                        validator_id,
+                    })
+            })
+            .collect()
+    }
+    /// Get the total stake, the share of network stake and the council seats
+    /// of living appchains, with pagination.
+    pub fn get_appchain_stake_summaries(
+        &self,
+        start_index: u32,
+        quantity: Option<u32>,
+    ) -> Vec<AppchainStakeSummary> {
+        let appchain_ids = self.living_appchain_ids.as_vector();
+        let network_stake: u128 = appchain_ids
+            .iter()
+            .filter_map(|appchain_id| self.appchain_rankings.get(&appchain_id))
+            .map(|ranking| ranking.total_stake.0)
+            .sum();
+        let council_members: Vec<InternalValidatorStake> = self
+            .latest_members
+            .iter()
+            .filter_map(|account_id| self.get_validator_stake(&account_id))
+            .collect();
+        let end_index = match quantity {
+            Some(quantity) => appchain_ids
+                .len()
+                .min(u64::from(start_index) + u64::from(quantity)),
+            None => appchain_ids.len(),
+        };
+        (u64::from(start_index)..end_index)
+            .filter_map(|index| appchain_ids.get(index))
+            .map(|appchain_id| {
+                let (number_of_validators, total_stake) =
+                    match self.appchain_rankings.get(&appchain_id) {
+                        Some(ranking) => (ranking.validators.len(), ranking.total_stake.0),
+                        None => (0, 0),
+                    };
+                AppchainStakeSummary {
+                    number_of_validators,
+                    total_stake: U128::from(total_stake),
+                    share_of_network_stake_bps: council_core::share_in_bps(
+                        total_stake,
+                        network_stake,
+                    ),
+                    council_seats: council_members
+                        .iter()
+                        .filter(|member| {
+                            member
+                                .stake_in_appchains
+                                .get(&appchain_id)
+                                .is_some_and(|stake| stake.0 > 0)
+                        })
+                        .count() as u32,
+                    appchain_id,
+                }
+            })
+            .collect()
+    }
+}
+
+impl CouncilKeeper {
+    // Update the total stake of the appchain by the change from `previous_stake`,
+    // and queue the validator to update its rank in the appchain.
+    pub(crate) fn queue_appchain_rank_update_of(
+        &mut self,
+        validator_id: &AccountId,
+        appchain_id: &String,
+        previous_stake: u128,
+    ) {
+        let mut ranking = self
+            .appchain_rankings
+            .get(appchain_id)
+            .unwrap_or_else(|| AppchainRanking::new(appchain_id));
+        let stake = stake_in_appchain_of(&self.validator_stakes, validator_id, appchain_id);
+        ranking.total_stake = U128::from(council_core::updated_total_stake(
+            ranking.total_stake.0,
+            previous_stake,
+            stake,
+        ));
+        ranking.waiting_validators.insert(validator_id);
+        self.appchain_rankings.insert(appchain_id, &ranking);
+        self.appchains_waiting_to_update_rank.insert(appchain_id);
+    }
+    // Update the ranks of validators waiting in the rankings of appchains, until all of
+    // them are updated or the gas is running out. The ranking of an appchain is rebuilt
+    // in bulk if a large share of its validators is waiting.
+    pub(crate) fn update_appchain_ranks(&mut self) -> MultiTxsOperationProcessingResult {
+        while env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING) {
+            if self.appchain_rank_rebuilding.is_some() {
+                self.continue_rebuilding_appchain_ranks();
+                continue;
+            }
+            let appchain_count = self.appchains_waiting_to_update_rank.len();
+            if appchain_count == 0 {
+                return MultiTxsOperationProcessingResult::Ok;
+            }
+            let appchain_id = self
+                .appchains_waiting_to_update_rank
+                .as_vector()
+                .get(appchain_count - 1)
+                .unwrap();
+            let mut ranking = self.appchain_rankings.get(&appchain_id).unwrap();
+            let waiting_count = ranking.waiting_validators.len();
+            if waiting_count == 0 {
+                self.appchains_waiting_to_update_rank.remove(&appchain_id);
+            } else if waiting_count >= MIN_WAITING_VALIDATORS_FOR_BULK_REBUILD
+                && waiting_count * 100
+                    >= u64::from(ranking.validators.len()) * BULK_REBUILD_THRESHOLD_PERCENT
+            {
+                log!(
+                    "Start rebuilding the ranking of appchain '{}', with {} validators waiting to update rank.",
+                    appchain_id,
+                    waiting_count
+                );
+                self.appchain_rank_rebuilding = Some((
+                    appchain_id,
+                    RankRebuildingState::Collecting {
+                        ranked_count: ranking.validators.len(),
+                        unranked_count: 0,
+                        next_ranked_index: 0,
+                    },
+                ));
+            } else {
+                let validator_id = ranking
+                    .waiting_validators
+                    .as_vector()
+                    .get(waiting_count - 1)
+                    .unwrap();
+                ranking.waiting_validators.remove(&validator_id);
+                self.update_appchain_rank_of(&mut ranking, &appchain_id, &validator_id);
+                self.appchain_rankings.insert(&appchain_id, &ranking);
+            }
+        }
+        MultiTxsOperationProcessingResult::NeedMoreGas
+    }
+    // Move the validator by its latest stake in the ranking of the appchain.
+    fn update_appchain_rank_of(
+        &mut self,
+        ranking: &mut AppchainRanking,
+        appchain_id: &String,
+        validator_id: &AccountId,
+    ) {
+        let current_rank = self.ranked_appchain_rank_of(ranking, appchain_id, validator_id);
+        let mut rank_value_holder = AppchainRankValueHolder {
+            appchain_id,
+            validator_stakes: &self.validator_stakes,
+            appchain_validator_ranks: &mut self.appchain_validator_ranks,
+        };
+        match current_rank {
+            Some(rank) => ranking
+                .validators
+                .insert(rank, validator_id, &mut rank_value_holder),
+            None => ranking
+                .validators
+                .append(validator_id, &mut rank_value_holder),
+        };
+    }
+    // The rank of the validator if it is in the ranking of the appchain.
+    fn ranked_appchain_rank_of(
+        &self,
+        ranking: &AppchainRanking,
+        appchain_id: &str,
+        validator_id: &AccountId,
+    ) -> Option<u32> {
+        self.appchain_validator_ranks
+            .get(&(appchain_id.to_string(), validator_id.clone()))
+            .filter(|rank| ranking.validators.get(*rank).as_ref() == Some(validator_id))
+    }
+    // Continue rebuilding the ranking of the appchain by one step, in the same way as
+    // rebuilding the ranking of all validators, with the buffers of appchain rankings.
+    fn continue_rebuilding_appchain_ranks(&mut self) {
+        let (appchain_id, state) = match self.appchain_rank_rebuilding.take() {
+            Some(rebuilding) => rebuilding,
+            None => return,
+        };
+        let mut ranking = self.appchain_rankings.get(&appchain_id).unwrap();
+        let next_state = match state {
+            RankRebuildingState::Collecting {
+                ranked_count,
+                unranked_count,
+                next_ranked_index,
+            } => self.collect_appchain_validator_for_rebuilding(
+                &mut ranking,
+                &appchain_id,
+                ranked_count,
+                unranked_count,
+                next_ranked_index,
+            ),
+            RankRebuildingState::Sorting {
+                count,
+                width,
+                source,
+                run_start,
+                left,
+                right,
+                out,
+            } => rank_rebuild::merge_validator_for_rebuilding(
+                APPCHAIN_RANK_BUFFER_BASE,
+                MergeCursor {
+                    count,
+                    width,
+                    source,
+                    run_start,
+                    left,
+                    right,
+                    out,
+                },
+            ),
+            RankRebuildingState::Writing {
+                count,
+                source,
+                next_index,
+            } => {
+                if next_index >= count {
+                    log!(
+                        "The ranking of {} validators in appchain '{}' has been rebuilt.",
+                        ranking.validators.len(),
+                        appchain_id
+                    );
+                    return;
+                }
+                let (validator_id, _) = rank_rebuilding_buffer(APPCHAIN_RANK_BUFFER_BASE + source)
+                    .remove(&next_index)
+                    .unwrap();
+                rank_rebuilding_buffer(APPCHAIN_RANK_BUFFER_BASE + 1 - source).remove(&next_index);
+                collected_unranked_validators(APPCHAIN_RANK_BUFFER_BASE).remove(&validator_id);
+                ranking.validators.rewrite(
+                    next_index,
+                    &validator_id,
+                    &mut AppchainRankValueHolder {
+                        appchain_id: &appchain_id,
+                        validator_stakes: &self.validator_stakes,
+                        appchain_validator_ranks: &mut self.appchain_validator_ranks,
+                    },
+                );
+                self.appchain_rankings.insert(&appchain_id, &ranking);
+                RankRebuildingState::Writing {
+                    count,
+                    source,
+                    next_index: next_index + 1,
+                }
+            }
+        };
+        self.appchain_rank_rebuilding = Some((appchain_id, next_state));
+    }
+    // The validators waiting in the appchain are drained before copying each ranked validator,
+    // the same as `collect_validator_for_rebuilding`.
+    fn collect_appchain_validator_for_rebuilding(
+        &mut self,
+        ranking: &mut AppchainRanking,
+        appchain_id: &String,
+        ranked_count: u32,
+        unranked_count: u32,
+        next_ranked_index: u32,
+    ) -> RankRebuildingState {
+        let mut buffer = rank_rebuilding_buffer(APPCHAIN_RANK_BUFFER_BASE);
+        let waiting_count = ranking.waiting_validators.len();
+        if waiting_count > 0 {
+            let validator_id = ranking
+                .waiting_validators
+                .as_vector()
+                .get(waiting_count - 1)
+                .unwrap();
+            ranking.waiting_validators.remove(&validator_id);
+            self.appchain_rankings.insert(appchain_id, ranking);
+            let stake = stake_in_appchain_of(&self.validator_stakes, &validator_id, appchain_id);
+            let current_rank = self.ranked_appchain_rank_of(ranking, appchain_id, &validator_id);
+            let mut collected_validators = collected_unranked_validators(APPCHAIN_RANK_BUFFER_BASE);
+            let (buffer_index, unranked_count) = match current_rank {
+                Some(rank) => (rank, unranked_count),
+                None => match collected_validators.get(&validator_id) {
+                    Some(buffer_index) => (buffer_index, unranked_count),
+                    None => {
+                        let buffer_index = ranked_count + unranked_count;
+                        collected_validators.insert(&validator_id, &buffer_index);
+                        (buffer_index, unranked_count + 1)
+                    }
+                },
+            };
+            buffer.insert(&buffer_index, &(validator_id, U128::from(stake)));
+            return RankRebuildingState::Collecting {
+                ranked_count,
+                unranked_count,
+                next_ranked_index,
+            };
+        }
+        if next_ranked_index < ranked_count {
+            let validator_id = ranking.validators.get(next_ranked_index).unwrap();
+            let stake = stake_in_appchain_of(&self.validator_stakes, &validator_id, appchain_id);
+            buffer.insert(&next_ranked_index, &(validator_id, U128::from(stake)));
+            return RankRebuildingState::Collecting {
+                ranked_count,
+                unranked_count,
+                next_ranked_index: next_ranked_index + 1,
+            };
+        }
+        let cursor = MergeCursor::new(ranked_count + unranked_count);
+        RankRebuildingState::Sorting {
+            count: cursor.count,
+            width: cursor.width,
+            source: cursor.source,
+            run_start: cursor.run_start,
+            left: cursor.left,
+            right: cursor.right,
+            out: cursor.out,
+        }
+    }
+}
+
+impl AppchainRanking {
+    //
+    fn new(appchain_id: &str) -> Self {
+        Self {
+            validators: RankedLookupArray::new(StorageKey::AppchainRankedValidators(
+                appchain_id.to_string(),
+            )),
+            waiting_validators: UnorderedSet::new(
+                StorageKey::AppchainValidatorsWaitingToUpdateRank(appchain_id.to_string()),
+            ),
+            total_stake: U128(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn validators_of(contract: &CouncilKeeper, appchain_id: &str) -> Vec<(AccountId, U128)> {
+        contract
+            .get_appchain_validator_stakes(appchain_id.to_string(), 0, None)
+            .into_iter()
+            .map(|validator_stake| (validator_stake.validator_id, validator_stake.stake))
+            .collect()
+    }
+
+    #[test]
+    fn test_rank_validators_in_appchains() {
+        let mut contract = new_contract(3);
+        sync_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+            ],
+        );
+        sync_stakes(
+            &mut contract,
+            "appchain2",
+            &[("bob.testnet", 250), ("dave.testnet", 50)],
+        );
+        update_change_histories(&mut contract);
+        assert_eq!(
+            validators_of(&contract, "appchain1"),
+            vec![
+                (account("alice.testnet"), U128(300 * NEAR)),
+                (account("bob.testnet"), U128(200 * NEAR)),
+                (account("charlie.testnet"), U128(100 * NEAR)),
+            ]
+        );
+        let validator_stakes =
+            contract.get_appchain_validator_stakes("appchain2".to_string(), 0, None);
+        assert_eq!(validator_stakes.len(), 2);
+        assert_eq!(validator_stakes[0].validator_id, account("bob.testnet"));
+        assert_eq!(validator_stakes[0].total_stake, U128(450 * NEAR));
+        assert!(validator_stakes[0].is_council_member);
+        assert_eq!(validator_stakes[1].rank_in_appchain, 1);
+        assert!(!validator_stakes[1].is_council_member);
+        // pagination
+        let validator_stakes =
+            contract.get_appchain_validator_stakes("appchain1".to_string(), 1, Some(1));
+        assert_eq!(validator_stakes.len(), 1);
+        assert_eq!(validator_stakes[0].validator_id, account("bob.testnet"));
+        assert_eq!(validator_stakes[0].rank_in_appchain, 1);
+        assert!(contract
+            .get_appchain_validator_stakes("appchain1".to_string(), 5, None)
+            .is_empty());
+        assert!(contract
+            .get_appchain_validator_stakes("appchain3".to_string(), 0, None)
+            .is_empty());
+        //
+        let summaries = contract.get_appchain_stake_summaries(0, None);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].appchain_id, "appchain1");
+        assert_eq!(summaries[0].number_of_validators, 3);
+        assert_eq!(summaries[0].total_stake, U128(600 * NEAR));
+        assert_eq!(summaries[0].share_of_network_stake_bps, 6666);
+        assert_eq!(summaries[0].council_seats, 3);
+        assert_eq!(summaries[1].appchain_id, "appchain2");
+        assert_eq!(summaries[1].total_stake, U128(300 * NEAR));
+        assert_eq!(summaries[1].share_of_network_stake_bps, 3333);
+        assert_eq!(summaries[1].council_seats, 1);
+        assert_eq!(contract.get_appchain_stake_summaries(1, Some(5)).len(), 1);
+        assert!(contract.get_appchain_stake_summaries(2, None).is_empty());
+        // the validator waiting to update rank keeps its position until it is re-ranked
+        sync_stakes(&mut contract, "appchain1", &[("charlie.testnet", 500)]);
+        assert_eq!(
+            validators_of(&contract, "appchain1")[2],
+            (account("charlie.testnet"), U128(500 * NEAR))
+        );
+        assert_eq!(
+            contract.get_appchain_stake_summaries(0, Some(1))[0].total_stake,
+            U128(1000 * NEAR)
+        );
+        update_change_histories(&mut contract);
+        assert_eq!(
+            validators_of(&contract, "appchain1")
+                .into_iter()
+                .map(|(validator_id, _)| validator_id)
+                .collect::<Vec<AccountId>>(),
+            accounts(&["charlie.testnet", "alice.testnet", "bob.testnet"])
+        );
+    }
+
+    #[test]
+    fn test_rebuild_appchain_ranking_in_bulk() {
+        let mut contract = new_contract(3);
+        let stakes: Vec<(String, u128)> = (0..30)
+            .map(|index| {
+                (
+                    format!("validator{}.testnet", index),
+                    100 + (index * 7) % 30,
+                )
+            })
+            .collect();
+        let stakes: Vec<(&str, u128)> = stakes
+            .iter()
+            .map(|(validator_id, stake)| (validator_id.as_str(), *stake))
+            .collect();
+        sync_stakes(&mut contract, "appchain1", &stakes);
+        contract.appchain_rank_rebuilding = Some((
+            "appchain1".to_string(),
+            RankRebuildingState::Collecting {
+                ranked_count: 0,
+                unranked_count: 0,
+                next_ranked_index: 0,
+            },
+        ));
+        for _ in 0..5 {
+            contract.continue_rebuilding_appchain_ranks();
+        }
+        // the first collected validator is synced again before the collecting is finished
+        let (validator_id, _) = rank_rebuilding_buffer(APPCHAIN_RANK_BUFFER_BASE)
+            .get(&0)
+            .unwrap();
+        sync_stakes(&mut contract, "appchain1", &[(validator_id.as_str(), 1000)]);
+        update_change_histories(&mut contract);
+        assert!(contract.appchain_rank_rebuilding.is_none());
+        let validator_stakes =
+            contract.get_appchain_validator_stakes("appchain1".to_string(), 0, None);
+        assert_eq!(validator_stakes.len(), 30);
+        assert_eq!(validator_stakes[0].validator_id, validator_id);
+        assert_eq!(validator_stakes[0].stake, U128(1000 * NEAR));
+        for (index, validator_stake) in validator_stakes.iter().enumerate().skip(1) {
+            assert_eq!(validator_stake.rank_in_appchain, index as u32);
+            assert!(validator_stake.stake <= validator_stakes[index - 1].stake);
+        }
+        assert!(collected_unranked_validators(APPCHAIN_RANK_BUFFER_BASE)
+            .get(&validator_id)
+            .is_none());
+        assert_eq!(
+            contract.get_appchain_stake_summaries(0, None)[0].number_of_validators,
+            30
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::*;
+use types::{InternalStateAuditReport, InternalStateViolation, InternalStateViolationRecord};
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Check the consistency of internal state, starting from the index of ranked validators.
+    /// The audit is gas-bounded, call it again with `next_index` in the report to continue.
+    ///
+    /// The validators waiting to update rank are skipped, as their stake is synced
+    /// but their position in the ranking is not updated yet.
+    pub fn audit_internal_state(&self, start_index: u32) -> InternalStateAuditReport {
+        let mut violations = Vec::new();
+        let (next_index, council_members_checked) =
+            self.audit_internal_state_from(start_index, &mut violations);
+        InternalStateAuditReport {
+            start_index,
+            next_index,
+            council_members_checked,
+            violations: violations
+                .into_iter()
+                .map(|violation| InternalStateViolationRecord {
+                    violation,
+                    repaired: false,
+                })
+                .collect(),
+        }
+    }
+    /// Same as `audit_internal_state`, and repair the violations which are repairable.
+    ///
+    /// The validators with wrong rank are queued to update rank, which needs
+    /// `update_council_change_histories` to be called after the repairing.
+    /// The missing entries of ranked validators or validator stakes can not be repaired,
+    /// as the validator or its stake is unknown to this contract.
+    pub fn repair_internal_state(&mut self, start_index: u32) -> InternalStateAuditReport {
+        self.assert_owner();
+        assert!(
+            !self.is_migrating_validator_stakes,
+            "Validator stakes are being migrated. Please call 'migrate_validator_stakes' first."
+        );
+        assert!(
+            self.rank_rebuilding.is_none(),
+            "The ranking of validators is being rebuilt. Please call 'update_council_change_histories' first."
+        );
+        let mut violations = Vec::new();
+        let (next_index, council_members_checked) =
+            self.audit_internal_state_from(start_index, &mut violations);
+        let mut records = Vec::new();
+        let mut is_council_repaired = false;
+        for violation in violations {
+            let repaired = match &violation {
+                InternalStateViolation::MissingRankedValidator { .. }
+                | InternalStateViolation::MissingValidatorStake { .. } => false,
+                InternalStateViolation::UnorderedRank { validator_id, .. } => {
+                    self.validators_waiting_to_update_rank.insert(validator_id);
+                    true
+                }
+                InternalStateViolation::MismatchedOverallRank {
+                    index,
+                    validator_id,
+                    ..
+                } => {
+                    self.validator_stakes.update_rank_of(validator_id, *index);
+                    self.ranking_version += 1;
+                    true
+                }
+                InternalStateViolation::MismatchedTotalStake {
+                    validator_id,
+                    sum_of_stake_in_appchains,
+                    ..
+                } => {
+                    let mut validator_stake = self.validator_stakes.get(validator_id).unwrap();
+                    validator_stake.total_stake = *sum_of_stake_in_appchains;
+                    self.validator_stakes.insert(validator_id, &validator_stake);
+                    self.validators_waiting_to_update_rank.insert(validator_id);
+                    self.ranking_version += 1;
+                    true
+                }
+                InternalStateViolation::UnexpectedCouncilMember { .. }
+                | InternalStateViolation::MissingCouncilMember { .. } => {
+                    if !is_council_repaired {
+                        self.check_and_generate_change_histories();
+                        is_council_repaired = true;
+                    }
+                    true
+                }
+            };
+            log!(
+                "Internal state violation found: '{}', repaired: {}",
+                near_sdk::serde_json::to_string(&violation).unwrap(),
+                repaired
+            );
+            records.push(InternalStateViolationRecord {
+                violation,
+                repaired,
+            });
+        }
+        InternalStateAuditReport {
+            start_index,
+            next_index,
+            council_members_checked,
+            violations: records,
+        }
+    }
+}
+
+impl CouncilKeeper {
+    // Check the ranked validators from `start_index` until the gas is running out,
+    // and check `latest_members` if all of the ranked validators are checked.
+    // Return the index to continue from, and whether `latest_members` is checked.
+    fn audit_internal_state_from(
+        &self,
+        start_index: u32,
+        violations: &mut Vec<InternalStateViolation>,
+    ) -> (Option<u32>, bool) {
+        let mut previous_total_stake = match start_index > 0 {
+            true => self
+                .ranked_validators
+                .get(start_index - 1)
+                .filter(|validator_id| {
+                    !self
+                        .validators_waiting_to_update_rank
+                        .contains(validator_id)
+                })
+                .and_then(|validator_id| self.get_validator_stake(&validator_id))
+                .map(|validator_stake| validator_stake.total_stake.0),
+            false => None,
+        };
+        let mut index = start_index;
+        while index < self.ranked_validators.len()
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+        {
+            previous_total_stake =
+                self.audit_ranked_validator_at(index, previous_total_stake, violations);
+            index += 1;
+        }
+        if index < self.ranked_validators.len() {
+            return (Some(index), false);
+        }
+        // the selection rule only holds when the ranking is up to date
+        if !self.validators_waiting_to_update_rank.is_empty()
+            || self.rank_rebuilding.is_some()
+            || self.council_reset.is_some()
+        {
+            return (None, false);
+        }
+        let council_members: HashSet<AccountId> =
+            self.select_council_members().into_iter().collect();
+        for account_id in self.latest_members.iter() {
+            if !council_members.contains(&account_id) {
+                violations.push(InternalStateViolation::UnexpectedCouncilMember { account_id });
+            }
+        }
+        for account_id in council_members {
+            if !self.latest_members.contains(&account_id) {
+                violations.push(InternalStateViolation::MissingCouncilMember { account_id });
+            }
+        }
+        (None, true)
+    }
+    // Check the validator at the index of ranked validators,
+    // and return the total stake of it for checking the next one.
+    // The validator waiting to update rank is skipped, and the total stake
+    // of the one before it is returned.
+    fn audit_ranked_validator_at(
+        &self,
+        index: u32,
+        previous_total_stake: Option<u128>,
+        violations: &mut Vec<InternalStateViolation>,
+    ) -> Option<u128> {
+        let validator_id = match self.ranked_validators.get(index) {
+            Some(validator_id) => validator_id,
+            None => {
+                violations.push(InternalStateViolation::MissingRankedValidator { index });
+                return None;
+            }
+        };
+        if self
+            .validators_waiting_to_update_rank
+            .contains(&validator_id)
+        {
+            return previous_total_stake;
+        }
+        let validator_stake = match self.get_validator_stake(&validator_id) {
+            Some(validator_stake) => validator_stake,
+            None => {
+                violations.push(InternalStateViolation::MissingValidatorStake {
+                    index,
+                    validator_id,
+                });
+                return None;
+            }
+        };
+        if validator_stake.overall_rank != index {
+            violations.push(InternalStateViolation::MismatchedOverallRank {
+                index,
+                validator_id: validator_id.clone(),
+                overall_rank: validator_stake.overall_rank,
+            });
+        }
+        let sum_of_stake_in_appchains: u128 = validator_stake
+            .stake_in_appchains
+            .to_hash_map()
+            .values()
+            .map(|stake| stake.0)
+            .sum();
+        if validator_stake.total_stake.0 != sum_of_stake_in_appchains {
+            violations.push(InternalStateViolation::MismatchedTotalStake {
+                validator_id: validator_id.clone(),
+                total_stake: validator_stake.total_stake,
+                sum_of_stake_in_appchains: U128::from(sum_of_stake_in_appchains),
+            });
+        }
+        if let Some(previous_total_stake) = previous_total_stake {
+            if validator_stake.total_stake.0 > previous_total_stake {
+                violations.push(InternalStateViolation::UnorderedRank {
+                    index,
+                    validator_id,
+                    total_stake: validator_stake.total_stake,
+                    previous_total_stake: U128::from(previous_total_stake),
+                });
+            }
+        }
+        Some(validator_stake.total_stake.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn setup_contract() -> CouncilKeeper {
+        let mut contract = new_contract(2);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+            ],
+        );
+        contract
+    }
+
+    #[test]
+    fn test_audit_skips_validators_waiting_to_update_rank() {
+        let mut contract = setup_contract();
+        let report = contract.audit_internal_state(0);
+        assert!(report.violations.is_empty());
+        assert!(report.next_index.is_none());
+        assert!(report.council_members_checked);
+        // the stake of charlie is synced but the ranking is not updated yet
+        sync_stakes(&mut contract, "appchain1", &[("charlie.testnet", 400)]);
+        let report = contract.audit_internal_state(0);
+        assert!(report.violations.is_empty());
+        assert!(!report.council_members_checked);
+        let report = contract.audit_internal_state(2);
+        assert!(report.violations.is_empty());
+    }
+
+    #[test]
+    fn test_repair_internal_state() {
+        let mut contract = setup_contract();
+        let mut validator_stake = contract
+            .validator_stakes
+            .get(&account("alice.testnet"))
+            .unwrap();
+        validator_stake.overall_rank = 5;
+        contract
+            .validator_stakes
+            .insert(&account("alice.testnet"), &validator_stake);
+        let mut validator_stake = contract
+            .validator_stakes
+            .get(&account("bob.testnet"))
+            .unwrap();
+        validator_stake.total_stake = U128(250 * NEAR);
+        contract
+            .validator_stakes
+            .insert(&account("bob.testnet"), &validator_stake);
+        //
+        let report = contract.audit_internal_state(0);
+        assert_eq!(report.violations.len(), 2);
+        assert!(matches!(
+            &report.violations[0].violation,
+            InternalStateViolation::MismatchedOverallRank {
+                index: 0,
+                overall_rank: 5,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &report.violations[1].violation,
+            InternalStateViolation::MismatchedTotalStake { validator_id, sum_of_stake_in_appchains, .. }
+                if validator_id.eq(&account("bob.testnet"))
+                    && sum_of_stake_in_appchains.0 == 200 * NEAR
+        ));
+        //
+        let ranking_version = contract.ranking_version;
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        let report = contract.repair_internal_state(0);
+        assert!(report.violations.iter().all(|record| record.repaired));
+        assert_eq!(contract.ranking_version, ranking_version + 2);
+        assert!(contract
+            .validators_waiting_to_update_rank
+            .contains(&account("bob.testnet")));
+        update_change_histories(&mut contract);
+        let report = contract.audit_internal_state(0);
+        assert!(report.violations.is_empty());
+        assert!(report.council_members_checked);
+        assert_eq!(
+            contract
+                .get_validator_stake_of(account("bob.testnet"))
+                .total_stake,
+            U128(200 * NEAR)
+        );
+    }
+
+    #[test]
+    fn test_repair_council_members() {
+        let mut contract = setup_contract();
+        contract.latest_members.remove(&account("bob.testnet"));
+        let report = contract.audit_internal_state(0);
+        assert_eq!(report.violations.len(), 1);
+        assert!(matches!(
+            &report.violations[0].violation,
+            InternalStateViolation::MissingCouncilMember { account_id }
+                if account_id.eq(&account("bob.testnet"))
+        ));
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        let report = contract.repair_internal_state(0);
+        assert!(report.violations[0].repaired);
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+        assert!(contract.audit_internal_state(0).violations.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::*;
+use types::{CouncilMemberRecord, CouncilSimulation};
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Simulate the council selected by the hypothetical parameters, without changing the state.
+    ///
+    /// The parameters which are not given are the same as the current ones.
+    /// The `extra_excluding_accounts` are excluded in addition to the current excluding
+    /// validator accounts, and the validators whose total stake is less than
+    /// `min_total_stake` can not be selected.
+    pub fn simulate_council(
+        &self,
+        max_number_of_council_members: Option<u32>,
+        extra_excluding_accounts: Option<Vec<AccountId>>,
+        min_total_stake: Option<U128>,
+    ) -> CouncilSimulation {
+        let extra_excluding_accounts: HashSet<AccountId> = extra_excluding_accounts
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        // the validators are ranked by total stake, so the walk stops at the first one
+        // whose total stake is less than the min stake
+        let members: Vec<CouncilMemberRecord> = council_core::select_council_members(
+            (0..self.ranked_validators.len())
+                .filter_map(|index| self.ranked_validators.get(index))
+                .filter_map(|account_id| {
+                    self.get_validator_stake(&account_id)
+                        .map(|validator_stake| CouncilMemberRecord {
+                            account_id,
+                            total_stake: validator_stake.total_stake,
+                            overall_rank: validator_stake.overall_rank,
+                        })
+                })
+                .take_while(|member| {
+                    min_total_stake
+                        .is_none_or(|min_total_stake| member.total_stake.0 >= min_total_stake.0)
+                }),
+            max_number_of_council_members.unwrap_or(self.max_number_of_council_members),
+            |member| {
+                self.excluding_validator_accounts
+                    .contains(&member.account_id)
+                    || extra_excluding_accounts.contains(&member.account_id)
+            },
+        );
+        let council_diff = self.diff_with_latest_members(
+            &members
+                .iter()
+                .map(|member| member.account_id.clone())
+                .collect::<Vec<AccountId>>(),
+        );
+        CouncilSimulation {
+            members,
+            members_to_add: council_diff.added,
+            members_to_remove: council_diff.removed,
+            is_ranking_up_to_date: self.validators_waiting_to_update_rank.is_empty()
+                && self.rank_rebuilding.is_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn member_ids_of(simulation: &CouncilSimulation) -> Vec<AccountId> {
+        simulation
+            .members
+            .iter()
+            .map(|member| member.account_id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_simulate_council() {
+        let mut contract = new_contract(2);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+            ],
+        );
+        // the current parameters
+        let simulation = contract.simulate_council(None, None, None);
+        assert_eq!(
+            member_ids_of(&simulation),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+        assert_eq!(simulation.members[1].total_stake, U128(200 * NEAR));
+        assert_eq!(simulation.members[1].overall_rank, 1);
+        assert!(simulation.members_to_add.is_empty());
+        assert!(simulation.members_to_remove.is_empty());
+        assert!(simulation.is_ranking_up_to_date);
+        // more seats
+        let simulation = contract.simulate_council(Some(3), None, None);
+        assert_eq!(
+            member_ids_of(&simulation),
+            accounts(&["alice.testnet", "bob.testnet", "charlie.testnet"])
+        );
+        assert_eq!(simulation.members_to_add, accounts(&["charlie.testnet"]));
+        assert!(simulation.members_to_remove.is_empty());
+        // the excluded validator is replaced by the next one
+        let simulation = contract.simulate_council(None, Some(accounts(&["alice.testnet"])), None);
+        assert_eq!(
+            member_ids_of(&simulation),
+            accounts(&["bob.testnet", "charlie.testnet"])
+        );
+        assert_eq!(simulation.members_to_add, accounts(&["charlie.testnet"]));
+        assert_eq!(simulation.members_to_remove, accounts(&["alice.testnet"]));
+        // the validators with less stake than the min stake can not be selected,
+        // even if there are seats left
+        let simulation = contract.simulate_council(Some(3), None, Some(U128(200 * NEAR)));
+        assert_eq!(
+            member_ids_of(&simulation),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+        assert!(simulation.members_to_add.is_empty());
+        let simulation = contract.simulate_council(
+            None,
+            Some(accounts(&["bob.testnet"])),
+            Some(U128(200 * NEAR + 1)),
+        );
+        assert_eq!(member_ids_of(&simulation), accounts(&["alice.testnet"]));
+        assert!(simulation.members_to_add.is_empty());
+        assert_eq!(simulation.members_to_remove, accounts(&["bob.testnet"]));
+        // the state is not changed by the simulation
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+        assert_eq!(contract.change_histories.end_index, 1);
+    }
+
+    #[test]
+    fn test_simulate_council_with_validators_waiting_to_update_rank() {
+        let mut contract = new_contract(2);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[("alice.testnet", 300), ("bob.testnet", 200)],
+        );
+        sync_stakes(&mut contract, "appchain1", &[("charlie.testnet", 500)]);
+        // the simulation is based on the ranks before the stake changed
+        let simulation = contract.simulate_council(None, None, None);
+        assert!(!simulation.is_ranking_up_to_date);
+        assert_eq!(
+            member_ids_of(&simulation),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+        update_change_histories(&mut contract);
+        let simulation = contract.simulate_council(None, None, None);
+        assert!(simulation.is_ranking_up_to_date);
+        assert_eq!(
+            member_ids_of(&simulation),
+            accounts(&["charlie.testnet", "alice.testnet"])
+        );
+        assert!(simulation.members_to_add.is_empty());
+        assert!(simulation.members_to_remove.is_empty());
+    }
+}
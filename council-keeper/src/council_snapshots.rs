@@ -0,0 +1,138 @@
+use crate::*;
+use types::{CouncilMemberRecord, CouncilSnapshot};
+
+impl CouncilKeeper {
+    // Append a snapshot of council members if the members are changed since the latest
+    // snapshot. The stake and rank of the members are recorded at the time of the change.
+    pub(crate) fn take_council_snapshot_if_changed(&mut self) {
+        let index_range = self.council_snapshots.index_range();
+        if let Some(latest_snapshot) = self.council_snapshots.get(&index_range.end_index.0) {
+            if latest_snapshot.members.len() == self.latest_members.len() as usize
+                && latest_snapshot
+                    .members
+                    .iter()
+                    .all(|member| self.latest_members.contains(&member.account_id))
+            {
+                return;
+            }
+        }
+        let mut members: Vec<CouncilMemberRecord> = self
+            .latest_members
+            .iter()
+            .map(|account_id| {
+                let validator_stake = self.get_validator_stake(&account_id);
+                CouncilMemberRecord {
+                    account_id,
+                    total_stake: validator_stake
+                        .as_ref()
+                        .map_or(U128(0), |stake| stake.total_stake),
+                    overall_rank: validator_stake.map_or(u32::MAX, |stake| stake.overall_rank),
+                }
+            })
+            .collect();
+        members.sort_by_key(|member| member.overall_rank);
+        self.council_snapshots.append(&mut CouncilSnapshot {
+            index: U64::from(0),
+            timestamp: U64::from(env::block_timestamp()),
+            members,
+        });
+    }
+    // Find the latest snapshot taken at or before the timestamp by binary search.
+    pub(crate) fn get_council_snapshot_at(&self, timestamp: u64) -> Option<CouncilSnapshot> {
+        let index_range = self.council_snapshots.index_range();
+        let mut low = index_range.start_index.0;
+        let mut high = index_range.end_index.0 + 1;
+        let mut result = None;
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.council_snapshots.get(&middle) {
+                Some(snapshot) if snapshot.timestamp.0 <= timestamp => {
+                    result = Some(snapshot);
+                    low = middle + 1;
+                }
+                _ => high = middle,
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn members_of(records: &[CouncilMemberRecord]) -> Vec<(AccountId, u128, u32)> {
+        records
+            .iter()
+            .map(|record| {
+                (
+                    record.account_id.clone(),
+                    record.total_stake.0 / NEAR,
+                    record.overall_rank,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_council_snapshots_are_taken_when_members_change() {
+        let mut contract = new_contract(2);
+        // the empty council is recorded by the initialization
+        assert_eq!(contract.council_snapshots.index_range().end_index, U64(0));
+        assert!(contract
+            .council_snapshots
+            .get(&0)
+            .unwrap()
+            .members
+            .is_empty());
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[("alice.testnet", 300), ("bob.testnet", 200)],
+        );
+        assert_eq!(contract.council_snapshots.index_range().end_index, U64(1));
+        // the change of stake doesn't change the members
+        advance_time(NANOS_PER_DAY);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("bob.testnet", 250)]);
+        assert_eq!(contract.council_snapshots.index_range().end_index, U64(1));
+        //
+        advance_time(NANOS_PER_DAY);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("dave.testnet", 500)]);
+        assert_eq!(contract.council_snapshots.index_range().end_index, U64(2));
+        //
+        assert!(contract
+            .get_council_members_at(U64(START_TIMESTAMP - 1))
+            .is_empty());
+        let expected_members = vec![
+            (account("alice.testnet"), 300, 0),
+            (account("bob.testnet"), 200, 1),
+        ];
+        assert_eq!(
+            members_of(&contract.get_council_members_at(U64(START_TIMESTAMP))),
+            expected_members
+        );
+        assert_eq!(
+            members_of(&contract.get_council_members_at(U64(START_TIMESTAMP + NANOS_PER_DAY))),
+            expected_members
+        );
+        assert_eq!(
+            members_of(&contract.get_council_members_at(U64(START_TIMESTAMP + 2 * NANOS_PER_DAY))),
+            vec![
+                (account("dave.testnet"), 500, 0),
+                (account("alice.testnet"), 300, 1),
+            ]
+        );
+        // the snapshot is taken again when the members are restored by rollback
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.rollback_change_histories_after(U64(1)).is_ok());
+        assert_eq!(contract.council_snapshots.index_range().end_index, U64(3));
+        assert_eq!(
+            members_of(&contract.get_council_members_at(U64(env::block_timestamp())))
+                .into_iter()
+                .map(|(account_id, _, _)| account_id)
+                .collect::<Vec<AccountId>>(),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use crate::*;
+use types::{CouncilChangeAction, DaoAdapterKind};
+
+/// The default name of the role in DAO contract which the council members belong to.
+pub const DEFAULT_COUNCIL_ROLE_NAME: &str = "council";
+/// Constants for gas.
+const T_GAS_FOR_ADD_PROPOSAL: u64 = 5;
+const T_GAS_FOR_ACT_PROPOSAL: u64 = 7;
+const T_GAS_FOR_DIRECT_CALL: u64 = 10;
+
+/// A function call which will be sent to DAO contract.
+pub struct DaoFunctionCall {
+    pub method_name: String,
+    pub args: Vec<u8>,
+    pub gas: Gas,
+}
+
+/// The way of applying council changes to a DAO contract.
+pub trait DaoAdapter {
+    /// Whether the council changes are applied by proposals,
+    /// which need proposal bonds and need to be approved after being added.
+    fn is_proposal_based(&self) -> bool;
+    /// The function call for applying a council change to DAO contract.
+    fn apply_change_call(&self, action: &CouncilChangeAction, role: &str) -> DaoFunctionCall;
+    /// The state of the change history after the function call for applying is successful,
+    /// `None` if the result of the function call can not be parsed.
+    fn state_after_applying(&self, result: &[u8]) -> Option<CouncilChangeHistoryState>;
+    /// The function call for approving the proposal of a council change.
+    fn approve_change_call(
+        &self,
+        action: &CouncilChangeAction,
+        role: &str,
+        proposal_id: u64,
+    ) -> DaoFunctionCall;
+}
+
+impl DaoAdapterKind {
+    /// The adapter of this kind, for generating the function calls to DAO contract.
+    pub fn adapter(&self) -> Box<dyn DaoAdapter> {
+        match self {
+            DaoAdapterKind::SputnikV2 => Box::new(SputnikV2Adapter {}),
+            DaoAdapterKind::SputnikV3 => Box::new(SputnikV3Adapter {}),
+            DaoAdapterKind::Direct {
+                add_member_method,
+                remove_member_method,
+            } => Box::new(DirectAdapter {
+                add_member_method: add_member_method.clone(),
+                remove_member_method: remove_member_method.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+enum ProposalKind {
+    /// Add member to given role in the policy. This is short cut to updating the whole policy.
+    AddMemberToRole { member_id: AccountId, role: String },
+    /// Remove member to given role in the policy. This is short cut to updating the whole policy.
+    RemoveMemberFromRole { member_id: AccountId, role: String },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+struct ProposalInput {
+    /// Description of this proposal.
+    pub description: String,
+    /// Kind of proposal with relevant information.
+    pub kind: ProposalKind,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+struct AddProposalInput {
+    pub proposal: ProposalInput,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+enum Action {
+    /// Vote to approve given proposal or bounty.
+    VoteApprove,
+}
+
+fn proposal_kind_of(action: &CouncilChangeAction, role: &str) -> ProposalKind {
+    match action {
+        CouncilChangeAction::MemberAdded(account_id) => ProposalKind::AddMemberToRole {
+            member_id: account_id.clone(),
+            role: role.to_string(),
+        },
+        CouncilChangeAction::MemberRemoved(account_id) => ProposalKind::RemoveMemberFromRole {
+            member_id: account_id.clone(),
+            role: role.to_string(),
+        },
+    }
+}
+
+fn description_of(action: &CouncilChangeAction) -> String {
+    match action {
+        CouncilChangeAction::MemberAdded(account_id) => format!(
+            "Add '{}' to council based on the rule in contract '{}'.",
+            account_id,
+            env::current_account_id()
+        ),
+        CouncilChangeAction::MemberRemoved(account_id) => format!(
+            "Remove '{}' from council based on the rule in contract '{}'.",
+            account_id,
+            env::current_account_id()
+        ),
+    }
+}
+
+fn add_proposal_call(action: &CouncilChangeAction, role: &str) -> DaoFunctionCall {
+    let args = AddProposalInput {
+        proposal: ProposalInput {
+            description: description_of(action),
+            kind: proposal_kind_of(action, role),
+        },
+    };
+    DaoFunctionCall {
+        method_name: "add_proposal".to_string(),
+        args: near_sdk::serde_json::to_vec(&args)
+            .expect("Failed to serialize the cross contract args using JSON."),
+        gas: Gas::ONE_TERA.mul(T_GAS_FOR_ADD_PROPOSAL),
+    }
+}
+
+fn proposal_added_state_of(result: &[u8]) -> Option<CouncilChangeHistoryState> {
+    near_sdk::serde_json::from_slice::<u64>(result)
+        .ok()
+        .map(CouncilChangeHistoryState::ProposalAdded)
+}
+
+fn approving_memo() -> Option<String> {
+    Some(format!(
+        "Automatically vote approve by '{}'.",
+        env::current_account_id()
+    ))
+}
+
+/// Adapter for sputnik DAO v2 contract.
+pub struct SputnikV2Adapter {}
+
+impl DaoAdapter for SputnikV2Adapter {
+    //
+    fn is_proposal_based(&self) -> bool {
+        true
+    }
+    //
+    fn apply_change_call(&self, action: &CouncilChangeAction, role: &str) -> DaoFunctionCall {
+        add_proposal_call(action, role)
+    }
+    //
+    fn state_after_applying(&self, result: &[u8]) -> Option<CouncilChangeHistoryState> {
+        proposal_added_state_of(result)
+    }
+    //
+    fn approve_change_call(
+        &self,
+        _action: &CouncilChangeAction,
+        _role: &str,
+        proposal_id: u64,
+    ) -> DaoFunctionCall {
+        #[derive(Serialize, Deserialize, Clone)]
+        #[serde(crate = "near_sdk::serde")]
+        struct Input {
+            pub id: u64,
+            pub action: Action,
+            pub memo: Option<String>,
+        }
+        let args = Input {
+            id: proposal_id,
+            action: Action::VoteApprove,
+            memo: approving_memo(),
+        };
+        DaoFunctionCall {
+            method_name: "act_proposal".to_string(),
+            args: near_sdk::serde_json::to_vec(&args)
+                .expect("Failed to serialize the cross contract args using JSON."),
+            gas: Gas::ONE_TERA.mul(T_GAS_FOR_ACT_PROPOSAL),
+        }
+    }
+}
+
+/// Adapter for sputnik DAO v3 contract, which requires the kind of proposal
+/// in `act_proposal` to make sure the voted proposal is the expected one.
+pub struct SputnikV3Adapter {}
+
+impl DaoAdapter for SputnikV3Adapter {
+    //
+    fn is_proposal_based(&self) -> bool {
+        true
+    }
+    //
+    fn apply_change_call(&self, action: &CouncilChangeAction, role: &str) -> DaoFunctionCall {
+        add_proposal_call(action, role)
+    }
+    //
+    fn state_after_applying(&self, result: &[u8]) -> Option<CouncilChangeHistoryState> {
+        proposal_added_state_of(result)
+    }
+    //
+    fn approve_change_call(
+        &self,
+        action: &CouncilChangeAction,
+        role: &str,
+        proposal_id: u64,
+    ) -> DaoFunctionCall {
+        #[derive(Serialize, Deserialize, Clone)]
+        #[serde(crate = "near_sdk::serde")]
+        struct Input {
+            pub id: u64,
+            pub action: Action,
+            pub proposal: ProposalKind,
+            pub memo: Option<String>,
+        }
+        let args = Input {
+            id: proposal_id,
+            action: Action::VoteApprove,
+            proposal: proposal_kind_of(action, role),
+            memo: approving_memo(),
+        };
+        DaoFunctionCall {
+            method_name: "act_proposal".to_string(),
+            args: near_sdk::serde_json::to_vec(&args)
+                .expect("Failed to serialize the cross contract args using JSON."),
+            gas: Gas::ONE_TERA.mul(T_GAS_FOR_ACT_PROPOSAL),
+        }
+    }
+}
+
+/// Adapter for a contract which has custom functions for setting members directly.
+/// The functions are called with args `{"member_id": "...", "role": "..."}`.
+pub struct DirectAdapter {
+    pub add_member_method: String,
+    pub remove_member_method: String,
+}
+
+impl DaoAdapter for DirectAdapter {
+    //
+    fn is_proposal_based(&self) -> bool {
+        false
+    }
+    //
+    fn apply_change_call(&self, action: &CouncilChangeAction, role: &str) -> DaoFunctionCall {
+        #[derive(Serialize, Deserialize, Clone)]
+        #[serde(crate = "near_sdk::serde")]
+        struct Input {
+            pub member_id: AccountId,
+            pub role: String,
+        }
+        let method_name = match action {
+            CouncilChangeAction::MemberAdded(_) => self.add_member_method.clone(),
+            CouncilChangeAction::MemberRemoved(_) => self.remove_member_method.clone(),
+        };
+        let args = Input {
+            member_id: action.account_id().clone(),
+            role: role.to_string(),
+        };
+        DaoFunctionCall {
+            method_name,
+            args: near_sdk::serde_json::to_vec(&args)
+                .expect("Failed to serialize the cross contract args using JSON."),
+            gas: Gas::ONE_TERA.mul(T_GAS_FOR_DIRECT_CALL),
+        }
+    }
+    //
+    fn state_after_applying(&self, _result: &[u8]) -> Option<CouncilChangeHistoryState> {
+        Some(CouncilChangeHistoryState::AppliedDirectly)
+    }
+    //
+    fn approve_change_call(
+        &self,
+        _action: &CouncilChangeAction,
+        _role: &str,
+        _proposal_id: u64,
+    ) -> DaoFunctionCall {
+        panic!("Council changes applied directly need no approval.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json::{json, Value};
+    use test_utils::*;
+
+    fn args_of(function_call: &DaoFunctionCall) -> Value {
+        near_sdk::serde_json::from_slice(&function_call.args).unwrap()
+    }
+
+    #[test]
+    fn test_sputnik_adapters() {
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        let action = CouncilChangeAction::MemberAdded(account("alice.testnet"));
+        for adapter_kind in [DaoAdapterKind::SputnikV2, DaoAdapterKind::SputnikV3] {
+            let adapter = adapter_kind.adapter();
+            assert!(adapter.is_proposal_based());
+            let function_call = adapter.apply_change_call(&action, "council");
+            assert_eq!(function_call.method_name, "add_proposal");
+            assert_eq!(
+                args_of(&function_call)["proposal"]["kind"],
+                json!({ "AddMemberToRole": { "member_id": "alice.testnet", "role": "council" } })
+            );
+            assert_eq!(
+                adapter.state_after_applying(b"7"),
+                Some(CouncilChangeHistoryState::ProposalAdded(7))
+            );
+            assert_eq!(adapter.state_after_applying(b"\"7\""), None);
+        }
+        // the kind of proposal is only passed to `act_proposal` of sputnik DAO v3
+        let action = CouncilChangeAction::MemberRemoved(account("alice.testnet"));
+        let function_call = DaoAdapterKind::SputnikV2
+            .adapter()
+            .approve_change_call(&action, "council", 7);
+        assert_eq!(function_call.method_name, "act_proposal");
+        let args = args_of(&function_call);
+        assert_eq!(args["id"], json!(7));
+        assert_eq!(args["action"], json!("VoteApprove"));
+        assert!(args.get("proposal").is_none());
+        let function_call = DaoAdapterKind::SputnikV3
+            .adapter()
+            .approve_change_call(&action, "council", 7);
+        assert_eq!(
+            args_of(&function_call)["proposal"],
+            json!({ "RemoveMemberFromRole": { "member_id": "alice.testnet", "role": "council" } })
+        );
+    }
+
+    #[test]
+    fn test_direct_adapter() {
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        let adapter = DaoAdapterKind::Direct {
+            add_member_method: "add_member".to_string(),
+            remove_member_method: "remove_member".to_string(),
+        }
+        .adapter();
+        assert!(!adapter.is_proposal_based());
+        for (action, method_name) in [
+            (
+                CouncilChangeAction::MemberAdded(account("alice.testnet")),
+                "add_member",
+            ),
+            (
+                CouncilChangeAction::MemberRemoved(account("alice.testnet")),
+                "remove_member",
+            ),
+        ] {
+            let function_call = adapter.apply_change_call(&action, "grants");
+            assert_eq!(function_call.method_name, method_name);
+            assert_eq!(
+                args_of(&function_call),
+                json!({ "member_id": "alice.testnet", "role": "grants" })
+            );
+        }
+        assert_eq!(
+            adapter.state_after_applying(&[]),
+            Some(CouncilChangeHistoryState::AppliedDirectly)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Council changes applied directly need no approval.")]
+    fn test_direct_adapter_needs_no_approval() {
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        DaoAdapterKind::Direct {
+            add_member_method: "add_member".to_string(),
+            remove_member_method: "remove_member".to_string(),
+        }
+        .adapter()
+        .approve_change_call(
+            &CouncilChangeAction::MemberAdded(account("alice.testnet")),
+            "grants",
+            0,
+        );
+    }
+}
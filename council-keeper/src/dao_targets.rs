@@ -0,0 +1,178 @@
+use crate::*;
+use dao_adapter::DEFAULT_COUNCIL_ROLE_NAME;
+use types::CouncilKeeperEvent;
+
+/// The progress of removing a DAO target, which may take multiple transactions.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct DaoTargetRemovalState {
+    //
+    pub dao_contract_account: AccountId,
+    // the index of the next change history to drop the state for the DAO contract from
+    pub next_index: u64,
+    // the end index of change histories when the removal is started
+    pub end_index: u64,
+}
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Add a DAO contract as a target of council changes, or update the settings of it.
+    ///
+    /// The change histories generated before a DAO contract is added will not be applied
+    /// to it, the council members of the DAO contract should be initialized by its policy.
+    ///
+    /// The adapter of an existing DAO target can not be changed while any proposal of it
+    /// is pending, as the pending proposals can only be resolved by the previous adapter.
+    /// The DAO target needs to be removed and added again in that case.
+    pub fn set_dao_target(
+        &mut self,
+        dao_contract_account: AccountId,
+        role_name: String,
+        dao_adapter: DaoAdapterKind,
+    ) {
+        self.assert_owner();
+        assert!(!role_name.is_empty(), "Invalid role name.");
+        assert!(
+            !matches!(&self.dao_target_removal, Some(removal) if removal.dao_contract_account.eq(&dao_contract_account)),
+            "The DAO contract '{}' is being removed. Please call 'remove_dao_target' first.",
+            dao_contract_account
+        );
+        let previous_dao_target = self.dao_targets.get(&dao_contract_account);
+        if let Some(previous_dao_target) = &previous_dao_target {
+            assert!(
+                previous_dao_target.dao_adapter == dao_adapter
+                    || !self.has_pending_proposals_in(&dao_contract_account),
+                "The DAO contract '{}' has pending proposals. Please remove it and add it again to change the adapter.",
+                dao_contract_account
+            );
+        }
+        let mut dao_target = previous_dao_target
+            .clone()
+            .unwrap_or(DaoTarget::new(&dao_contract_account));
+        dao_target.role_name = role_name;
+        dao_target.dao_adapter = dao_adapter;
+        self.dao_targets.insert(&dao_contract_account, &dao_target);
+        CouncilKeeperEvent::emit_parameter_changed(
+            "dao_target",
+            &previous_dao_target,
+            &Some(dao_target),
+        );
+    }
+    /// Stop applying council changes to a DAO contract, and drop the states of existing
+    /// change histories for it. The proposal bonds of its pending proposals are released,
+    /// as they are no longer tracked by this contract.
+    ///
+    /// This function needs to be called repeatedly until it returns `Ok`.
+    pub fn remove_dao_target(
+        &mut self,
+        dao_contract_account: AccountId,
+    ) -> MultiTxsOperationProcessingResult {
+        self.assert_owner();
+        self.assert_change_histories_migrated();
+        let mut removal = match self.dao_target_removal.take() {
+            Some(removal) => {
+                assert!(
+                    removal.dao_contract_account.eq(&dao_contract_account),
+                    "The DAO contract '{}' is being removed. Please call 'remove_dao_target' for it first.",
+                    removal.dao_contract_account
+                );
+                removal
+            }
+            None => {
+                let previous_dao_target = self.dao_targets.remove(&dao_contract_account);
+                assert!(
+                    previous_dao_target.is_some(),
+                    "The DAO contract '{}' is not a target of council changes.",
+                    dao_contract_account
+                );
+                CouncilKeeperEvent::emit_parameter_changed(
+                    "dao_target",
+                    &previous_dao_target,
+                    &None,
+                );
+                let index_range = self.change_histories.index_range();
+                DaoTargetRemovalState {
+                    dao_contract_account,
+                    next_index: index_range.start_index.0,
+                    end_index: index_range.end_index.0,
+                }
+            }
+        };
+        // the change histories generated after the removal don't have the DAO contract
+        while removal.next_index <= removal.end_index
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+        {
+            let index = removal.next_index;
+            if let Some(mut change_history) = self.change_histories.get(&index) {
+                if change_history
+                    .target_states
+                    .remove(&removal.dao_contract_account)
+                    .is_some()
+                {
+                    self.change_histories.insert(&index, &change_history);
+                }
+            }
+            self.release_proposal_bond_of(&index, &removal.dao_contract_account);
+            removal.next_index += 1;
+        }
+        if removal.next_index <= removal.end_index {
+            self.dao_target_removal = Some(removal);
+            MultiTxsOperationProcessingResult::NeedMoreGas
+        } else {
+            log!(
+                "The states of change histories for DAO contract '{}' are dropped.",
+                removal.dao_contract_account
+            );
+            MultiTxsOperationProcessingResult::Ok
+        }
+    }
+}
+
+impl CouncilKeeper {
+    // The initial states of a new change history for all DAO contracts.
+    pub(crate) fn new_target_states(&self) -> HashMap<AccountId, CouncilChangeHistoryState> {
+        self.dao_targets
+            .keys()
+            .map(|account_id| (account_id, CouncilChangeHistoryState::WaitingForApplying))
+            .collect()
+    }
+    // Whether any kept change history is submitting or has added a proposal to the DAO contract.
+    fn has_pending_proposals_in(&self, dao_contract_account: &AccountId) -> bool {
+        let index_range = self.change_histories.index_range();
+        for index in index_range.start_index.0..index_range.end_index.0 + 1 {
+            assert!(
+                env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_SCANNING_CHANGE_HISTORIES),
+                "Too many change histories to check. Please prune the applied ones first."
+            );
+            if let Some(change_history) = self.change_histories.get(&index) {
+                if matches!(
+                    change_history.target_states.get(dao_contract_account),
+                    Some(CouncilChangeHistoryState::ProposalAdded(_))
+                        | Some(CouncilChangeHistoryState::ProposalSubmitting(_))
+                ) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl DaoTarget {
+    /// A DAO target with the default role name and the sputnik DAO v2 adapter.
+    pub fn new(dao_contract_account: &AccountId) -> Self {
+        Self {
+            dao_contract_account: dao_contract_account.clone(),
+            role_name: DEFAULT_COUNCIL_ROLE_NAME.to_string(),
+            dao_adapter: DaoAdapterKind::SputnikV2,
+            proposal_bond: None,
+        }
+    }
+    /// The proposal bond is only needed by the DAO adapters which apply changes by proposals.
+    /// Return `None` if it is needed but has not been synced from the DAO contract.
+    pub fn get_proposal_bond_to_attach(&self) -> Option<Balance> {
+        match self.dao_adapter.adapter().is_proposal_based() {
+            true => self.proposal_bond.map(|proposal_bond| proposal_bond.0),
+            false => Some(0),
+        }
+    }
+}
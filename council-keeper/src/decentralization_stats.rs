@@ -0,0 +1,358 @@
+use crate::*;
+use council_core::GiniSums;
+use std::collections::BTreeMap;
+use types::{CouncilAppchainDistribution, DecentralizationStats};
+
+/// The progress of calculating the decentralization stats, which may take multiple
+/// transactions. The ranked validators are walked twice, the first walk sums their
+/// total stakes, and the second walk counts the top validators for the Nakamoto coefficient.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy)]
+pub enum StatsCalculationState {
+    /// Summing the total stakes of ranked validators before `next_index`.
+    Summing {
+        started_at: u64,
+        next_index: u32,
+        count: u64,
+        total_stake: u128,
+        weighted_total_stake: u128,
+    },
+    /// Adding up the total stakes of top validators before `next_index`,
+    /// until they exceed one third of the total stake.
+    Counting {
+        started_at: u64,
+        next_index: u32,
+        count: u64,
+        total_stake: u128,
+        weighted_total_stake: u128,
+        cumulative_stake: u128,
+    },
+}
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Calculate the decentralization stats by the current ranking of validators,
+    /// the result is cached in state and shown by `get_decentralization_stats`.
+    ///
+    /// The calculation continues from where the previous call stopped, until it returns `Ok`.
+    /// It is refused while the ranking is not up to date, and the unfinished calculation
+    /// is discarded if the stake of validators is changed since it is started.
+    pub fn calculate_decentralization_stats(&mut self) -> MultiTxsOperationProcessingResult {
+        self.assert_owner();
+        assert!(
+            self.rank_rebuilding.is_none(),
+            "The ranking of validators is being rebuilt. Please call 'update_council_change_histories' first."
+        );
+        assert!(
+            self.validators_waiting_to_update_rank.is_empty(),
+            "There are validators waiting to update rank. Please call 'update_council_change_histories' first."
+        );
+        let mut state = match self.stats_calculation.take() {
+            Some((ranking_version, state)) if ranking_version == self.ranking_version => state,
+            previous_calculation => {
+                if previous_calculation.is_some() {
+                    log!("The ranking of validators is changed, the unfinished calculation of decentralization stats is discarded.");
+                }
+                StatsCalculationState::Summing {
+                    started_at: env::block_timestamp(),
+                    next_index: 0,
+                    count: 0,
+                    total_stake: 0,
+                    weighted_total_stake: 0,
+                }
+            }
+        };
+        let ranked_count = self.ranked_validators.len();
+        while env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING) {
+            state = match state {
+                StatsCalculationState::Summing {
+                    started_at,
+                    next_index,
+                    count,
+                    total_stake,
+                    weighted_total_stake,
+                } => {
+                    let mut gini_sums = GiniSums {
+                        count,
+                        total: total_stake,
+                        weighted_total: weighted_total_stake,
+                    };
+                    match next_index < ranked_count {
+                        true => {
+                            gini_sums.add(self.total_stake_of_rank(next_index).unwrap_or(0));
+                            StatsCalculationState::Summing {
+                                started_at,
+                                next_index: next_index + 1,
+                                count: gini_sums.count,
+                                total_stake: gini_sums.total,
+                                weighted_total_stake: gini_sums.weighted_total,
+                            }
+                        }
+                        false => StatsCalculationState::Counting {
+                            started_at,
+                            next_index: 0,
+                            count,
+                            total_stake,
+                            weighted_total_stake,
+                            cumulative_stake: 0,
+                        },
+                    }
+                }
+                StatsCalculationState::Counting {
+                    started_at,
+                    next_index,
+                    count,
+                    total_stake,
+                    weighted_total_stake,
+                    cumulative_stake,
+                } => {
+                    if council_core::exceeds_nakamoto_threshold(cumulative_stake, total_stake)
+                        || u64::from(next_index) >= count
+                    {
+                        self.finish_stats_calculation(
+                            started_at,
+                            GiniSums {
+                                count,
+                                total: total_stake,
+                                weighted_total: weighted_total_stake,
+                            },
+                            next_index,
+                        );
+                        return MultiTxsOperationProcessingResult::Ok;
+                    }
+                    StatsCalculationState::Counting {
+                        started_at,
+                        next_index: next_index + 1,
+                        count,
+                        total_stake,
+                        weighted_total_stake,
+                        cumulative_stake: cumulative_stake
+                            .saturating_add(self.total_stake_of_rank(next_index).unwrap_or(0)),
+                    }
+                }
+            };
+        }
+        self.stats_calculation = Some((self.ranking_version, state));
+        MultiTxsOperationProcessingResult::NeedMoreGas
+    }
+    /// Get the decentralization stats of the latest finished calculation.
+    pub fn get_decentralization_stats(&self) -> Option<DecentralizationStats> {
+        self.decentralization_stats.clone()
+    }
+}
+
+impl CouncilKeeper {
+    //
+    fn total_stake_of_rank(&self, rank: u32) -> Option<u128> {
+        self.ranked_validators
+            .get(rank)
+            .and_then(|validator_id| self.get_validator_stake(&validator_id))
+            .map(|validator_stake| validator_stake.total_stake.0)
+    }
+    // The stake of council members is summed when the calculation is finished,
+    // as the number of them is small.
+    fn finish_stats_calculation(
+        &mut self,
+        started_at: u64,
+        gini_sums: GiniSums,
+        nakamoto_coefficient: u32,
+    ) {
+        let mut council_stake: u128 = 0;
+        // key: appchain id, value: (council seats, council stake)
+        let mut distribution: BTreeMap<String, (u32, u128)> = BTreeMap::new();
+        for account_id in self.latest_members.iter() {
+            if let Some(validator_stake) = self.get_validator_stake(&account_id) {
+                council_stake = council_stake.saturating_add(validator_stake.total_stake.0);
+                for (appchain_id, stake) in validator_stake.stake_in_appchains.to_hash_map() {
+                    if stake.0 > 0 {
+                        let entry = distribution.entry(appchain_id).or_insert((0, 0));
+                        entry.0 += 1;
+                        entry.1 = entry.1.saturating_add(stake.0);
+                    }
+                }
+            }
+        }
+        let stats = DecentralizationStats {
+            number_of_validators: gini_sums.count as u32,
+            total_stake: U128::from(gini_sums.total),
+            nakamoto_coefficient,
+            gini_coefficient_bps: gini_sums.coefficient_in_bps(),
+            council_stake: U128::from(council_stake),
+            council_stake_share_bps: council_core::share_in_bps(council_stake, gini_sums.total),
+            council_appchain_distribution: distribution
+                .into_iter()
+                .map(
+                    |(appchain_id, (council_seats, stake))| CouncilAppchainDistribution {
+                        appchain_id,
+                        council_seats,
+                        council_stake: U128::from(stake),
+                        share_of_council_stake_bps: council_core::share_in_bps(
+                            stake,
+                            council_stake,
+                        ),
+                    },
+                )
+                .collect(),
+            started_at: U64::from(started_at),
+            calculated_at: U64::from(env::block_timestamp()),
+        };
+        log!(
+            "The decentralization stats of {} validators are calculated. Nakamoto coefficient: {}, Gini coefficient: {} bps.",
+            stats.number_of_validators,
+            stats.nakamoto_coefficient,
+            stats.gini_coefficient_bps
+        );
+        self.decentralization_stats = Some(stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+    use test_utils::*;
+
+    // enough for processing a few validators, but not all of them
+    const GAS_LEFT_FOR_A_FEW_STEPS: Gas = Gas(300_000_000_000);
+
+    // Call `calculate_decentralization_stats` after using the gas up to the given amount
+    // below the cap, so that only a few validators are processed in the call.
+    fn calculate_with_gas_left(
+        contract: &mut CouncilKeeper,
+        gas_left: Gas,
+    ) -> MultiTxsOperationProcessingResult {
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        let data = [0u8; 1000];
+        while env::used_gas() + gas_left < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING) {
+            env::sha256(&data);
+        }
+        contract.calculate_decentralization_stats()
+    }
+
+    // Call `calculate_decentralization_stats` until it returns `Ok`,
+    // and return the number of calls.
+    fn calculate_in_multi_txs(contract: &mut CouncilKeeper) -> u32 {
+        for transactions in 1..100 {
+            match calculate_with_gas_left(contract, GAS_LEFT_FOR_A_FEW_STEPS) {
+                MultiTxsOperationProcessingResult::Ok => return transactions,
+                MultiTxsOperationProcessingResult::NeedMoreGas => (),
+                MultiTxsOperationProcessingResult::Error(message) => panic!("{}", message),
+            }
+        }
+        panic!("The decentralization stats are not calculated.");
+    }
+
+    fn setup_contract() -> CouncilKeeper {
+        let mut contract = new_contract(2);
+        sync_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+                ("dave.testnet", 100),
+            ],
+        );
+        sync_and_update_stakes(&mut contract, "appchain2", &[("eve.testnet", 300)]);
+        contract
+    }
+
+    fn stats_json_of(contract: &CouncilKeeper) -> String {
+        near_sdk::serde_json::to_string(&contract.get_decentralization_stats().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_calculate_decentralization_stats_in_multi_txs() {
+        let mut contract = setup_contract();
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.calculate_decentralization_stats().is_ok());
+        let stats = contract.get_decentralization_stats().unwrap();
+        assert_eq!(stats.number_of_validators, 5);
+        assert_eq!(stats.total_stake, U128(1000 * NEAR));
+        assert_eq!(stats.nakamoto_coefficient, 2);
+        assert_eq!(stats.council_stake, U128(600 * NEAR));
+        assert_eq!(stats.council_stake_share_bps, 6000);
+        assert_eq!(
+            stats
+                .council_appchain_distribution
+                .iter()
+                .map(|distribution| (
+                    distribution.appchain_id.as_str(),
+                    distribution.council_seats,
+                    distribution.share_of_council_stake_bps
+                ))
+                .collect::<Vec<(&str, u32, u32)>>(),
+            vec![("appchain1", 1, 5000), ("appchain2", 1, 5000)]
+        );
+        let stats_json = stats_json_of(&contract);
+        // the calculation continues from where the previous call stopped,
+        // and the previous stats are kept until it is finished
+        assert!(
+            calculate_with_gas_left(&mut contract, GAS_LEFT_FOR_A_FEW_STEPS).is_need_more_gas()
+        );
+        assert!(matches!(
+            contract.stats_calculation,
+            Some((_, StatsCalculationState::Summing { next_index, .. })) if next_index > 0
+        ));
+        assert_eq!(stats_json_of(&contract), stats_json);
+        assert!(calculate_in_multi_txs(&mut contract) > 1);
+        assert!(contract.stats_calculation.is_none());
+        assert_eq!(stats_json_of(&contract), stats_json);
+    }
+
+    #[test]
+    fn test_unfinished_calculation_is_discarded_when_ranking_changed() {
+        let mut contract = setup_contract();
+        assert!(
+            calculate_with_gas_left(&mut contract, GAS_LEFT_FOR_A_FEW_STEPS).is_need_more_gas()
+        );
+        advance_time(NANOS_PER_DAY);
+        sync_and_update_stakes(&mut contract, "appchain2", &[("frank.testnet", 1000)]);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.calculate_decentralization_stats().is_ok());
+        assert!(get_logs().iter().any(|log| log.contains("is discarded")));
+        let stats = contract.get_decentralization_stats().unwrap();
+        assert_eq!(stats.number_of_validators, 6);
+        assert_eq!(stats.total_stake, U128(2000 * NEAR));
+        assert_eq!(stats.nakamoto_coefficient, 1);
+        // the calculation is restarted
+        assert_eq!(stats.started_at, U64(START_TIMESTAMP + NANOS_PER_DAY));
+    }
+
+    #[test]
+    fn test_nakamoto_coefficient() {
+        let mut contract = new_contract(3);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 300),
+                ("charlie.testnet", 300),
+            ],
+        );
+        // the top validators need to hold more than one third of the total stake
+        calculate_in_multi_txs(&mut contract);
+        let stats = contract.get_decentralization_stats().unwrap();
+        assert_eq!(stats.nakamoto_coefficient, 2);
+        assert_eq!(stats.gini_coefficient_bps, 0);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("charlie.testnet", 301)]);
+        calculate_in_multi_txs(&mut contract);
+        assert_eq!(
+            contract
+                .get_decentralization_stats()
+                .unwrap()
+                .nakamoto_coefficient,
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "There are validators waiting to update rank.")]
+    fn test_calculate_decentralization_stats_with_validators_waiting_to_update_rank() {
+        let mut contract = setup_contract();
+        sync_stakes(&mut contract, "appchain1", &[("alice.testnet", 500)]);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.calculate_decentralization_stats();
+    }
+}
@@ -0,0 +1,42 @@
+use crate::*;
+use types::{CouncilKeeperEvent, ParameterChangedEventData};
+
+/// The standard name of the events emitted by this contract.
+pub const EVENT_STANDARD: &str = "council_keeper";
+/// The version of the event standard, which should be bumped if any payload is changed.
+pub const EVENT_STANDARD_VERSION: &str = "1.1.0";
+/// The max number of entries in the data of an event emitted by a multi-txs operation,
+/// to keep the logs of a receipt in the limit of total log length.
+pub const MAX_ENTRIES_IN_EVENT_DATA: usize = 64;
+
+impl CouncilKeeperEvent {
+    /// Emit the event as a log with prefix `EVENT_JSON:`, following NEP-297.
+    pub fn emit(&self) {
+        #[derive(Serialize)]
+        #[serde(crate = "near_sdk::serde")]
+        struct EventLog<'a> {
+            standard: &'static str,
+            version: &'static str,
+            #[serde(flatten)]
+            event: &'a CouncilKeeperEvent,
+        }
+        log!(
+            "EVENT_JSON:{}",
+            near_sdk::serde_json::to_string(&EventLog {
+                standard: EVENT_STANDARD,
+                version: EVENT_STANDARD_VERSION,
+                event: self,
+            })
+            .unwrap()
+        );
+    }
+    /// Emit an event of a changed parameter, the values are serialized to JSON.
+    pub fn emit_parameter_changed<T: Serialize>(parameter: &str, previous_value: &T, value: &T) {
+        CouncilKeeperEvent::ParameterChanged(vec![ParameterChangedEventData {
+            parameter: parameter.to_string(),
+            previous_value: near_sdk::serde_json::to_string(previous_value).unwrap(),
+            value: near_sdk::serde_json::to_string(value).unwrap(),
+        }])
+        .emit();
+    }
+}
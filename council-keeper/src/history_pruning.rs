@@ -0,0 +1,166 @@
+use crate::*;
+use types::{ChangeHistoriesPrunedEventData, CouncilKeeperEvent};
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Remove the change histories before the given index, which are applied to
+    /// all of the DAO contracts. The running hash of the last removed change history
+    /// is kept, to verify the change histories exported off-chain.
+    ///
+    /// This function needs to be called repeatedly until it returns `Ok`.
+    pub fn prune_change_histories(
+        &mut self,
+        before_index: U64,
+    ) -> MultiTxsOperationProcessingResult {
+        self.assert_owner_or_dao();
+        self.assert_change_histories_migrated();
+        assert!(
+            self.council_reset.is_none(),
+            "Council is being reset. Please call 'clear_council_members_and_regenerate_change_histories' first."
+        );
+        let end_index = match before_index.0 > self.change_histories.end_index + 1 {
+            true => self.change_histories.end_index + 1,
+            false => before_index.0,
+        };
+        let start_index = self.change_histories.start_index;
+        while self.change_histories.start_index < end_index
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+        {
+            let index = self.change_histories.start_index;
+            if let Some(change_history) = self.change_histories.get(&index) {
+                if !change_history.is_applied() {
+                    self.emit_change_histories_pruned_event(start_index);
+                    return MultiTxsOperationProcessingResult::Error(format!(
+                        "Change history '{}' is not applied yet.",
+                        index
+                    ));
+                }
+                self.pruned_change_history_hash = change_history.running_hash;
+                self.previous_pending_history_indexes.remove(&index);
+            }
+            self.change_histories.remove_before(&(index + 1));
+        }
+        self.emit_change_histories_pruned_event(start_index);
+        if self.change_histories.start_index < end_index {
+            MultiTxsOperationProcessingResult::NeedMoreGas
+        } else {
+            log!(
+                "Change histories before '{}' are pruned, the running hash of the last pruned one is '{}'.",
+                self.change_histories.start_index,
+                self.pruned_change_history_hash
+            );
+            MultiTxsOperationProcessingResult::Ok
+        }
+    }
+}
+
+impl CouncilKeeper {
+    // Emit the event if any change history is pruned since `start_index`.
+    fn emit_change_histories_pruned_event(&self, start_index: u64) {
+        if self.change_histories.start_index > start_index {
+            CouncilKeeperEvent::ChangeHistoriesPruned(vec![ChangeHistoriesPrunedEventData {
+                operator: env::predecessor_account_id(),
+                before_index: U64::from(self.change_histories.start_index),
+                running_hash: self.pruned_change_history_hash.clone(),
+            }])
+            .emit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    // Apply the change histories waiting for applying by the direct adapter.
+    fn apply_change_histories_directly(contract: &mut CouncilKeeper) {
+        let (_, dao_calls) = apply_change_histories(contract);
+        let results = dao_calls
+            .iter()
+            .map(|_| PromiseResult::Successful(Vec::new()))
+            .collect();
+        resolve_add_proposals(contract, &dao_calls, results);
+    }
+
+    fn assert_running_hash_chained(contract: &CouncilKeeper, index: u64, previous_hash: &str) {
+        let change_history = contract.change_histories.get(&index).unwrap();
+        assert_eq!(
+            change_history.running_hash,
+            types::to_hex(&change_history.running_hash_with(&types::from_hex(previous_hash)))
+        );
+    }
+
+    #[test]
+    fn test_prune_applied_change_histories() {
+        let mut contract = new_contract(2);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.set_dao_target(
+            account(DAO_CONTRACT_ACCOUNT),
+            "council".to_string(),
+            DaoAdapterKind::Direct {
+                add_member_method: "add_member".to_string(),
+                remove_member_method: "remove_member".to_string(),
+            },
+        );
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+            ],
+        );
+        apply_change_histories_directly(&mut contract);
+        set_context("alice.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        // the change histories are chained by running hash
+        assert_running_hash_chained(&contract, 0, "");
+        for index in 1..4 {
+            let previous_hash = contract
+                .change_histories
+                .get(&(index - 1))
+                .unwrap()
+                .running_hash;
+            assert_running_hash_chained(&contract, index, &previous_hash);
+        }
+        let chain = contract.get_change_history_chain();
+        assert_eq!(
+            chain.latest_running_hash,
+            contract.change_histories.get(&3).unwrap().running_hash
+        );
+        assert_eq!(chain.pruned_running_hash, "");
+        // the pruning stops at the first change history which is not applied
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        let result = contract.prune_change_histories(U64(4));
+        assert!(matches!(
+            result,
+            MultiTxsOperationProcessingResult::Error(message)
+                if message.eq("Change history '2' is not applied yet.")
+        ));
+        let chain = contract.get_change_history_chain();
+        assert_eq!(chain.index_range.start_index, U64(2));
+        assert!(contract.change_histories.get(&1).is_none());
+        assert_running_hash_chained(&contract, 2, &chain.pruned_running_hash);
+        // the DAO contract can prune the change histories as well,
+        // and the index is clamped to the end of change histories
+        apply_change_histories_directly(&mut contract);
+        set_context(DAO_CONTRACT_ACCOUNT, Vec::new());
+        assert!(contract.prune_change_histories(U64(100)).is_ok());
+        let chain = contract.get_change_history_chain();
+        assert_eq!(chain.index_range.start_index, U64(4));
+        assert_eq!(chain.pruned_running_hash, chain.latest_running_hash);
+        // the new change histories are chained after the pruned ones
+        sync_and_update_stakes(&mut contract, "appchain1", &[("dave.testnet", 500)]);
+        assert_running_hash_chained(&contract, 4, &chain.pruned_running_hash);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or the primary DAO contract can call this function.")]
+    fn test_prune_change_histories_by_others() {
+        let mut contract = new_contract(2);
+        set_context("alice.testnet", Vec::new());
+        contract.prune_change_histories(U64(1));
+    }
+}
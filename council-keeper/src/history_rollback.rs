@@ -0,0 +1,287 @@
+use crate::*;
+use events::MAX_ENTRIES_IN_EVENT_DATA;
+use types::{ChangeHistoryRolledBackEventData, CouncilChangeAction, CouncilKeeperEvent};
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Roll back the change histories after the given index, and restore `latest_members`
+    /// to what it was when the change history at the index was generated.
+    ///
+    /// The rollback is refused if any of the change histories after the index has been
+    /// submitted to a DAO contract. This function needs to be called repeatedly
+    /// until it returns `Ok`.
+    pub fn rollback_change_histories_after(
+        &mut self,
+        index: U64,
+    ) -> MultiTxsOperationProcessingResult {
+        self.assert_owner_or_dao();
+        self.assert_change_histories_migrated();
+        assert!(
+            self.council_reset.is_none(),
+            "Council is being reset. Please call 'clear_council_members_and_regenerate_change_histories' first."
+        );
+        let index_range = self.change_histories.index_range();
+        assert!(
+            index.0 >= index_range.start_index.0 && index.0 <= index_range.end_index.0,
+            "Invalid history data index."
+        );
+        for history_index in index.0 + 1..index_range.end_index.0 + 1 {
+            assert!(
+                env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_SCANNING_CHANGE_HISTORIES),
+                "Too many change histories to check. Please roll back to a later index first."
+            );
+            if let Some(change_history) = self.change_histories.get(&history_index) {
+                if !change_history.target_states.values().all(|state| {
+                    matches!(
+                        state,
+                        CouncilChangeHistoryState::WaitingForApplying
+                            | CouncilChangeHistoryState::NoNeedToApply
+                    )
+                }) {
+                    return MultiTxsOperationProcessingResult::Error(format!(
+                        "Change history '{}' has been submitted to DAO contract, it can not be rolled back.",
+                        history_index
+                    ));
+                }
+            }
+        }
+        // the change histories are rolled back from the latest one
+        let mut rolled_back_histories = Vec::new();
+        while self.change_histories.end_index > index.0
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+            && rolled_back_histories.len() < MAX_ENTRIES_IN_EVENT_DATA
+        {
+            let end_index = self.change_histories.end_index;
+            if let Some(change_history) = self.change_histories.get(&end_index) {
+                let restored_index = self.revert_change_history(&change_history);
+                rolled_back_histories.push(ChangeHistoryRolledBackEventData {
+                    operator: env::predecessor_account_id(),
+                    change_history_index: change_history.index,
+                    restored_change_history_index: restored_index.map(U64::from),
+                });
+            }
+            self.change_histories.reset_to(&(end_index - 1));
+        }
+        if !rolled_back_histories.is_empty() {
+            CouncilKeeperEvent::ChangeHistoriesRolledBack(rolled_back_histories).emit();
+        }
+        if self.change_histories.end_index > index.0 {
+            MultiTxsOperationProcessingResult::NeedMoreGas
+        } else {
+            // the new change histories are chained after the latest kept one,
+            // the indexes of the rolled back ones are skipped
+            self.latest_change_history_hash =
+                types::from_hex(&self.latest_running_hash_at_or_before(index.0));
+            self.take_council_snapshot_if_changed();
+            log!(
+                "Change histories after '{}' are rolled back by '{}'.",
+                index.0,
+                env::predecessor_account_id()
+            );
+            MultiTxsOperationProcessingResult::Ok
+        }
+    }
+}
+
+impl CouncilKeeper {
+    // The running hash of the latest change history at or before the index. There may be
+    // gaps of indexes which are left by the previous rollbacks.
+    fn latest_running_hash_at_or_before(&self, index: u64) -> String {
+        let mut index = index;
+        loop {
+            if let Some(change_history) = self.change_histories.get(&index) {
+                return change_history.running_hash;
+            }
+            if index <= self.change_histories.start_index {
+                return self.pruned_change_history_hash.clone();
+            }
+            index -= 1;
+        }
+    }
+    // Undo the effect of a change history on `latest_members` and the terms of the member,
+    // and restore the pending change history of the account. Return the index of the
+    // previous change history if it is waiting for applying again.
+    fn revert_change_history(&mut self, change_history: &CouncilChangeHistory) -> Option<u64> {
+        let account_id = change_history.action.account_id();
+        match &change_history.action {
+            CouncilChangeAction::MemberAdded(account_id) => {
+                self.latest_members.remove(account_id);
+                self.undo_start_council_term_of(account_id);
+            }
+            CouncilChangeAction::MemberRemoved(account_id) => {
+                self.latest_members.insert(account_id);
+                self.undo_end_council_term_of(account_id);
+            }
+        }
+        match self
+            .previous_pending_history_indexes
+            .remove(&change_history.index.0)
+        {
+            Some((previous_index, is_cancelled)) => {
+                // the previous one was waiting for applying before being cancelled
+                if is_cancelled {
+                    if let Some(mut previous_history) = self.change_histories.get(&previous_index) {
+                        previous_history.set_state_of_all_targets(
+                            CouncilChangeHistoryState::WaitingForApplying,
+                        );
+                        self.change_histories
+                            .insert(&previous_index, &previous_history);
+                    }
+                }
+                self.pending_change_history_indexes
+                    .insert(account_id, &previous_index);
+                match is_cancelled {
+                    true => Some(previous_index),
+                    false => None,
+                }
+            }
+            None => {
+                if self.pending_change_history_indexes.get(account_id)
+                    == Some(change_history.index.0)
+                {
+                    self.pending_change_history_indexes.remove(account_id);
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+    use test_utils::*;
+
+    fn setup_contract() -> CouncilKeeper {
+        let mut contract = new_contract(2);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+            ],
+        );
+        contract
+    }
+
+    #[test]
+    fn test_rollback_change_histories() {
+        let mut contract = setup_contract();
+        advance_time(NANOS_PER_DAY);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("dave.testnet", 500)]);
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "dave.testnet"])
+        );
+        let running_hash = contract.change_histories.get(&1).unwrap().running_hash;
+        //
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.rollback_change_histories_after(U64(1)).is_ok());
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains("\"event\":\"change_histories_rolled_back\"")));
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+        assert_eq!(contract.change_histories.end_index, 1);
+        assert_eq!(
+            contract.get_change_history_chain().latest_running_hash,
+            running_hash
+        );
+        assert_eq!(
+            contract
+                .pending_change_history_indexes
+                .get(&account("bob.testnet")),
+            Some(1)
+        );
+        assert!(contract
+            .pending_change_history_indexes
+            .get(&account("dave.testnet"))
+            .is_none());
+        // the terms are restored
+        assert!(contract
+            .get_council_member_tenure(account("dave.testnet"))
+            .is_none());
+        let tenure = contract
+            .get_council_member_tenure(account("bob.testnet"))
+            .unwrap();
+        assert!(tenure.is_serving);
+        assert_eq!(tenure.terms, 1);
+        // the indexes of the rolled back change histories are not reused
+        update_change_histories(&mut contract);
+        assert!(contract.change_histories.get(&2).is_none());
+        assert!(contract.change_histories.get(&3).is_none());
+        let change_history = contract.change_histories.get(&4).unwrap();
+        assert!(matches!(
+            &change_history.action,
+            CouncilChangeAction::MemberAdded(account_id) if account_id.eq(&account("dave.testnet"))
+        ));
+        assert_eq!(
+            change_history.running_hash,
+            types::to_hex(&change_history.running_hash_with(&types::from_hex(&running_hash)))
+        );
+        // the latest running hash is found across the gap of indexes
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.rollback_change_histories_after(U64(4)).is_ok());
+        assert_eq!(contract.latest_running_hash_at_or_before(3), running_hash);
+    }
+
+    #[test]
+    fn test_rollback_restores_cancelled_change_history() {
+        let mut contract = setup_contract();
+        set_context("alice.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::NoNeedToApply)
+        );
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.rollback_change_histories_after(U64(2)).is_ok());
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains("\"restored_change_history_index\":\"0\"")));
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::WaitingForApplying)
+        );
+        assert_eq!(
+            contract
+                .pending_change_history_indexes
+                .get(&account("alice.testnet")),
+            Some(0)
+        );
+        assert!(contract.latest_members.contains(&account("alice.testnet")));
+    }
+
+    #[test]
+    fn test_rollback_submitted_change_histories_is_refused() {
+        let mut contract = setup_contract();
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 0);
+        apply_change_histories(&mut contract);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(matches!(
+            contract.rollback_change_histories_after(U64(0)),
+            MultiTxsOperationProcessingResult::Error(message)
+                if message.contains("Change history '1' has been submitted")
+        ));
+        assert_eq!(contract.change_histories.end_index, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or the primary DAO contract can call this function.")]
+    fn test_rollback_by_other_dao_target() {
+        let mut contract = setup_contract();
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.set_dao_target(
+            account("grants-dao.testnet"),
+            "council".to_string(),
+            DaoAdapterKind::SputnikV2,
+        );
+        set_context("grants-dao.testnet", Vec::new());
+        contract.rollback_change_histories_after(U64(0));
+    }
+}
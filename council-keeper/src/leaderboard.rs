@@ -0,0 +1,259 @@
+use crate::*;
+use types::{ExclusionReason, LeaderboardEntry};
+
+/// The cut-off of the council selected by the current ranking.
+struct CouncilCutOff {
+    // the rank and total stake of the last selected validator
+    last_seat: Option<(u32, u128)>,
+    // the total stake of the first eligible validator which is not selected
+    runner_up_stake: Option<u128>,
+}
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Get the ranked validators with their eligibility for the council, with pagination.
+    ///
+    /// The stake needed to enter or to drop out is based on the current ranking, which
+    /// doesn't include the validators waiting to update rank. The result is empty
+    /// if `start_index` is out of bound.
+    pub fn get_validator_leaderboard(
+        &self,
+        start_index: u32,
+        quantity: Option<u32>,
+    ) -> Vec<LeaderboardEntry> {
+        let validator_ids = self.ranked_validators.get_slice_of(start_index, quantity);
+        if validator_ids.is_empty() {
+            return Vec::new();
+        }
+        let cut_off = self.get_council_cut_off();
+        validator_ids
+            .into_iter()
+            .zip(start_index..)
+            .filter_map(|(validator_id, rank)| {
+                self.get_validator_stake(&validator_id)
+                    .map(|validator_stake| {
+                        self.to_leaderboard_entry(validator_stake, rank, &cut_off)
+                    })
+            })
+            .collect()
+    }
+    //
+    fn to_leaderboard_entry(
+        &self,
+        validator_stake: InternalValidatorStake,
+        rank: u32,
+        cut_off: &CouncilCutOff,
+    ) -> LeaderboardEntry {
+        let total_stake = validator_stake.total_stake.0;
+        let exclusion_reason = match self
+            .excluding_validator_accounts
+            .contains(&validator_stake.validator_id)
+        {
+            true => Some(ExclusionReason::SelfExcluded),
+            false => None,
+        };
+        let is_selected = exclusion_reason.is_none()
+            && cut_off
+                .last_seat
+                .is_some_and(|(last_seat_rank, _)| rank <= last_seat_rank);
+        // the validator needs to have more stake than the other one to move ahead of it,
+        // as the validators with the same stake keep the order in which they were ranked
+        let stake_to_enter = match (is_selected, &exclusion_reason, cut_off.last_seat) {
+            (false, None, Some((_, last_seat_stake))) => {
+                Some(U128::from(last_seat_stake.saturating_sub(total_stake) + 1))
+            }
+            _ => None,
+        };
+        let stake_to_drop_out = match (is_selected, cut_off.runner_up_stake) {
+            (true, Some(runner_up_stake)) => {
+                Some(U128::from(total_stake.saturating_sub(runner_up_stake) + 1))
+            }
+            _ => None,
+        };
+        let primary_appchain_id = validator_stake
+            .stake_in_appchains
+            .to_hash_map()
+            .into_iter()
+            .max_by(|a, b| a.1 .0.cmp(&b.1 .0).then(b.0.cmp(&a.0)))
+            .map(|(appchain_id, _)| appchain_id);
+        LeaderboardEntry {
+            is_council_member: self.latest_members.contains(&validator_stake.validator_id),
+            tenure: self.get_council_member_tenure_of(&validator_stake.validator_id),
+            validator_id: validator_stake.validator_id,
+            total_stake: validator_stake.total_stake,
+            overall_rank: rank,
+            is_excluded: exclusion_reason.is_some(),
+            exclusion_reason,
+            stake_to_enter,
+            stake_to_drop_out,
+            primary_appchain_id,
+        }
+    }
+    // Walk the ranked validators from the top, until the first eligible validator
+    // after the last seat of council is found.
+    fn get_council_cut_off(&self) -> CouncilCutOff {
+        let mut seats = 0;
+        let mut last_seat = None;
+        for rank in 0..self.ranked_validators.len() {
+            let validator_id = match self.ranked_validators.get(rank) {
+                Some(validator_id) => validator_id,
+                None => continue,
+            };
+            if self.excluding_validator_accounts.contains(&validator_id) {
+                continue;
+            }
+            let total_stake = self
+                .get_validator_stake(&validator_id)
+                .map_or(0, |validator_stake| validator_stake.total_stake.0);
+            if seats >= self.max_number_of_council_members {
+                return CouncilCutOff {
+                    last_seat,
+                    runner_up_stake: Some(total_stake),
+                };
+            }
+            seats += 1;
+            last_seat = Some((rank, total_stake));
+        }
+        CouncilCutOff {
+            last_seat,
+            runner_up_stake: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    // The stake to enter and the stake to drop out of the entries, in yoctoNEAR.
+    fn stakes_around_cut_off_of(entries: &[LeaderboardEntry]) -> Vec<(Option<u128>, Option<u128>)> {
+        entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.stake_to_enter.map(|stake| stake.0),
+                    entry.stake_to_drop_out.map(|stake| stake.0),
+                )
+            })
+            .collect()
+    }
+
+    fn setup_contract() -> CouncilKeeper {
+        let mut contract = new_contract(2);
+        sync_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 200),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+                ("dave.testnet", 50),
+            ],
+        );
+        sync_and_update_stakes(&mut contract, "appchain2", &[("alice.testnet", 250)]);
+        contract
+    }
+
+    #[test]
+    fn test_get_validator_leaderboard() {
+        let contract = setup_contract();
+        let entries = contract.get_validator_leaderboard(0, None);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.validator_id.clone(), entry.overall_rank))
+                .collect::<Vec<(AccountId, u32)>>(),
+            vec![
+                (account("alice.testnet"), 0),
+                (account("bob.testnet"), 1),
+                (account("charlie.testnet"), 2),
+                (account("dave.testnet"), 3),
+            ]
+        );
+        assert_eq!(entries[0].total_stake, U128(450 * NEAR));
+        assert_eq!(
+            entries[0].primary_appchain_id,
+            Some("appchain2".to_string())
+        );
+        assert!(entries[0].is_council_member);
+        assert!(entries[0].tenure.is_some());
+        assert!(!entries[2].is_council_member);
+        assert!(entries[2].tenure.is_none());
+        // the stakes are compared with the last seat and the runner-up
+        assert_eq!(
+            stakes_around_cut_off_of(&entries),
+            vec![
+                (None, Some(350 * NEAR + 1)),
+                (None, Some(100 * NEAR + 1)),
+                (Some(100 * NEAR + 1), None),
+                (Some(150 * NEAR + 1), None),
+            ]
+        );
+        // pagination
+        let entries = contract.get_validator_leaderboard(1, Some(2));
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.overall_rank)
+                .collect::<Vec<u32>>(),
+            vec![1, 2]
+        );
+        assert_eq!(entries[1].stake_to_enter, Some(U128(100 * NEAR + 1)));
+        assert_eq!(contract.get_validator_leaderboard(3, Some(5)).len(), 1);
+    }
+
+    #[test]
+    fn test_get_validator_leaderboard_out_of_range() {
+        let contract = setup_contract();
+        assert!(contract.get_validator_leaderboard(4, None).is_empty());
+        assert!(contract.get_validator_leaderboard(10, Some(5)).is_empty());
+        assert!(contract
+            .get_validator_leaderboard(u32::MAX, Some(u32::MAX))
+            .is_empty());
+        assert!(contract.get_validator_leaderboard(0, Some(0)).is_empty());
+    }
+
+    #[test]
+    fn test_stakes_around_cut_off_with_excluded_validators() {
+        let mut contract = setup_contract();
+        // the excluded validator can neither enter nor be the runner-up
+        set_context("charlie.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        let entries = contract.get_validator_leaderboard(0, None);
+        assert!(entries[2].is_excluded);
+        assert_eq!(
+            entries[2].exclusion_reason,
+            Some(ExclusionReason::SelfExcluded)
+        );
+        assert_eq!(
+            stakes_around_cut_off_of(&entries),
+            vec![
+                (None, Some(400 * NEAR + 1)),
+                (None, Some(150 * NEAR + 1)),
+                (None, None),
+                (Some(150 * NEAR + 1), None),
+            ]
+        );
+        // the validator with the same stake as the last seat is ranked after it,
+        // so it needs one more yoctoNEAR to enter
+        sync_and_update_stakes(&mut contract, "appchain2", &[("eve.testnet", 200)]);
+        let entries = contract.get_validator_leaderboard(1, Some(2));
+        assert_eq!(entries[0].validator_id, account("bob.testnet"));
+        assert_eq!(entries[1].validator_id, account("eve.testnet"));
+        assert_eq!(
+            stakes_around_cut_off_of(&entries),
+            vec![(None, Some(1)), (Some(1), None)]
+        );
+        // no one is waiting for a seat when the excluded validator is the only one left
+        set_context("dave.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        set_context("eve.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        let entries = contract.get_validator_leaderboard(0, Some(2));
+        assert_eq!(
+            stakes_around_cut_off_of(&entries),
+            vec![(None, None), (None, None)]
+        );
+    }
+}
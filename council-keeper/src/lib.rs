@@ -1,44 +1,85 @@
+mod appchain_rankings;
+mod audit;
+mod council_simulation;
+mod council_snapshots;
+mod dao_adapter;
+mod dao_targets;
+mod decentralization_stats;
+pub mod events;
+mod history_pruning;
+mod history_rollback;
+mod leaderboard;
 mod lookup_array;
+mod pending_changes;
+mod rank_rebuild;
 mod ranked_lookup_array;
+mod stake_history;
 mod storage_migration;
 mod sudo_functions;
+mod tenure;
+#[cfg(test)]
+mod test_utils;
+mod treasury;
 pub mod types;
 mod upgrade;
 mod views;
 
+use appchain_rankings::AppchainRanking;
+use dao_targets::DaoTargetRemovalState;
+use decentralization_stats::StatsCalculationState;
 use lookup_array::{IndexedAndClearable, LookupArray};
 use near_contract_standards::upgrade::Ownable;
 use near_sdk::{
     assert_self,
     borsh::{self, BorshDeserialize, BorshSerialize},
-    collections::{LookupMap, UnorderedMap, UnorderedSet},
+    collections::{LookupMap, UnorderedMap, UnorderedSet, Vector},
     env, ext_contract,
     json_types::{U128, U64},
     log, near_bindgen,
     serde::{Deserialize, Serialize},
-    AccountId, BorshStorageKey, Gas, PanicOnDefault, Promise, PromiseResult,
+    AccountId, Balance, BorshStorageKey, Gas, PanicOnDefault, Promise, PromiseResult,
 };
+use rank_rebuild::RankRebuildingState;
 use ranked_lookup_array::{RankValueHolder, RankedLookupArray};
-use std::{collections::HashMap, ops::Mul, str::FromStr};
+use stake_history::DEFAULT_STAKE_HISTORY_RETENTION;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Mul,
+    str::FromStr,
+};
+use sudo_functions::CouncilResetState;
 use types::{
-    CouncilChangeHistory, CouncilChangeHistoryState, IndexRange, MultiTxsOperationProcessingResult,
-    ValidatorStake, ValidatorStakeRecord,
+    CouncilChangeHistory, CouncilChangeHistoryState, CouncilKeeperEvent, CouncilMemberEventData,
+    CouncilResetRecord, CouncilSnapshot, CouncilTerm, DaoAdapterKind, DaoCallOfChangeHistory,
+    DaoCallStage, DaoProposalEventData, DaoProposalFailedEventData, DaoTarget,
+    DecentralizationStats, ExclusionToggledEventData, IndexRange,
+    MultiTxsOperationProcessingResult, ProposalSubmissionTimedOutEventData, RankChangedEventData,
+    StakeSyncedEventData, ValidatorStake, ValidatorStakeHistoryEntry, ValidatorStakeRecord,
 };
 
 const VERSION: &str = "v0.5.0";
 /// Constants for gas.
 const T_GAS_CAP_FOR_MULTI_TXS_PROCESSING: u64 = 150;
-const T_GAS_FOR_ADD_PROPOSAL: u64 = 5;
-const T_GAS_FOR_RESOLVE_ADD_PROPOSAL: u64 = 25;
-const T_GAS_FOR_ACT_PROPOSAL: u64 = 7;
-const T_GAS_FOR_RESOLVE_ACT_PROPOSAL: u64 = 5;
+const T_GAS_CAP_FOR_SCANNING_CHANGE_HISTORIES: u64 = 50;
+const T_GAS_FOR_RESOLVING_DAO_CALLS: u64 = 10;
+const T_GAS_FOR_RESOLVING_EACH_DAO_CALL: u64 = 4;
+const T_GAS_FOR_GET_POLICY: u64 = 5;
+const T_GAS_FOR_RESOLVE_GET_POLICY: u64 = 5;
+/// The time after which a proposal submitting without callback is regarded as lost.
+const PROPOSAL_SUBMITTING_TIMEOUT_IN_NANOS: u64 = 3600 * 1_000_000_000;
+/// The max number of function calls to DAO contracts which are sent in parallel.
+const MAX_DAO_CALLS_IN_BATCH: usize = 5;
+/// The max number of appchains whose stakes are stored inline in `InternalValidatorStake`.
+const MAX_INLINE_STAKE_IN_APPCHAINS: usize = 4;
 
 #[ext_contract(ext_self)]
-trait ResolverForSelfCallback {
-    /// Resolver for adding proposal to DAO contract
-    fn resolve_add_proposal(&mut self, change_history: &mut CouncilChangeHistory);
-    /// Resolver for acting proposal to DAO contract
-    fn resolve_act_proposal(&mut self, change_history: &mut CouncilChangeHistory);
+pub trait ResolverForSelfCallback {
+    /// Resolver for adding a batch of proposals to DAO contracts
+    fn resolve_add_proposals(&mut self, dao_calls: Vec<DaoCallOfChangeHistory>);
+    /// Resolver for acting a batch of proposals on DAO contracts
+    fn resolve_act_proposals(&mut self, dao_calls: Vec<DaoCallOfChangeHistory>);
+    /// Resolver for getting policy of DAO contract
+    fn resolve_get_policy(&mut self, dao_contract_account: AccountId);
 }
 
 /// Storage keys for collections of sub-struct in main contract
@@ -53,6 +94,34 @@ pub enum StorageKey {
     ValidatorsWaitingToUpdateRank,
     LivingAppchainIds,
     ExcludingValidatorAccounts,
+    ProposalBondsOfHistories,
+    PendingChangeHistoryIndexes,
+    DaoTargets,
+    TargetedCouncilChangeHistories,
+    CompactValidatorStakes,
+    RankRebuildingBuffer(u8),
+    CouncilResetRecords,
+    PreviousPendingHistoryIndexes,
+    CouncilSnapshots,
+    StakeHistoryIndexRanges,
+    StakeHistoryEntries,
+    MemberTenures,
+    CouncilTerms,
+    TenureAccounts,
+    AppchainRankings,
+    AppchainRankedValidators(String),
+    AppchainValidatorRanks,
+    AppchainsWaitingToUpdateRank,
+    AppchainValidatorsWaitingToUpdateRank(String),
+    RankRebuildingCollectedValidators(u8),
+}
+
+/// The stakes of a validator in appchains. Most validators only stake in a few appchains,
+/// so the stakes are stored inline, and spill to a collection only above a threshold.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum StakeInAppchains {
+    Inline(Vec<(String, U128)>),
+    Spilled(UnorderedMap<String, U128>),
 }
 
 #[derive(BorshSerialize, BorshDeserialize)]
@@ -60,7 +129,7 @@ pub struct InternalValidatorStake {
     //
     validator_id: AccountId,
     // key: appchain id, value: total stake in the appchain anchor
-    stake_in_appchains: UnorderedMap<String, U128>,
+    stake_in_appchains: StakeInAppchains,
     // total stake in all appchain anchors
     total_stake: U128,
     // the rank of the validator in all validators
@@ -75,8 +144,6 @@ pub struct CouncilKeeper {
     //
     appchain_registry_account: AccountId,
     //
-    dao_contract_account: AccountId,
-    //
     living_appchain_ids: UnorderedSet<String>,
     //
     validator_stakes: LookupMap<AccountId, InternalValidatorStake>,
@@ -90,8 +157,80 @@ pub struct CouncilKeeper {
     excluding_validator_accounts: UnorderedSet<AccountId>,
     //
     change_histories: LookupArray<CouncilChangeHistory>,
+    // the index of the next generated change history, the indexes of the change histories
+    // which are rolled back are not reused
+    next_change_history_index: u64,
     //
     validators_waiting_to_update_rank: UnorderedSet<AccountId>,
+    // the total amount of NEAR deposited by `fund_keeper`
+    funded_balance: Balance,
+    // the total amount of proposal bonds held by DAO contracts
+    locked_proposal_bonds: Balance,
+    // key: (index of change history, account id of DAO contract),
+    // value: the proposal bond attached to the proposal
+    proposal_bonds_of_histories: LookupMap<(u64, AccountId), Balance>,
+    // key: account id of council member, value: index of the latest change history of the member
+    pending_change_history_indexes: LookupMap<AccountId, u64>,
+    // key: account id of DAO contract, value: the settings of applying council changes to it
+    dao_targets: UnorderedMap<AccountId, DaoTarget>,
+    // the progress of removing a DAO target
+    dao_target_removal: Option<DaoTargetRemovalState>,
+    // the DAO contract given at initialization, which can manage the change histories
+    // together with the owner
+    primary_dao_contract_account: AccountId,
+    // whether the validator stakes in old layout are not fully migrated yet
+    is_migrating_validator_stakes: bool,
+    // whether the change histories in old layout are not fully migrated yet
+    is_migrating_change_histories: bool,
+    // the index of the next change history to migrate
+    next_migrating_change_history_index: u64,
+    // the combined index of the next validator to migrate the stake of
+    next_migrating_validator_stake_index: u32,
+    // the DAO contract to which the change histories in old layout were applied
+    dao_contract_account_of_old_histories: Option<AccountId>,
+    // the progress of rebuilding the ranking of validators in bulk
+    rank_rebuilding: Option<RankRebuildingState>,
+    // the progress of resetting council
+    council_reset: Option<CouncilResetState>,
+    // the records of finished council resets
+    council_reset_records: Vector<CouncilResetRecord>,
+    // the running hash of the latest change history
+    latest_change_history_hash: Vec<u8>,
+    // key: index of change history, value: the index of the pending change history
+    // of the same account when it was generated, and whether they cancelled each other out
+    previous_pending_history_indexes: LookupMap<u64, (u64, bool)>,
+    // the running hash of the last pruned change history in hex
+    pruned_change_history_hash: String,
+    // the snapshots of council members, appended when the council is changed
+    council_snapshots: LookupArray<CouncilSnapshot>,
+    // the number of stake history entries kept for each validator
+    stake_history_retention: u32,
+    // key: validator id, value: the index range `[first, next)` of kept stake history entries
+    stake_history_index_ranges: LookupMap<AccountId, (u64, u64)>,
+    // key: (validator id, index of entry)
+    stake_history_entries: LookupMap<(AccountId, u64), ValidatorStakeHistoryEntry>,
+    // key: account id, value: the number of terms and the total nanoseconds of ended terms
+    member_tenures: LookupMap<AccountId, (u32, u64)>,
+    // key: (account id, index of term)
+    council_terms: LookupMap<(AccountId, u32), CouncilTerm>,
+    // the accounts which have sat on the council
+    tenure_accounts: UnorderedSet<AccountId>,
+    // key: appchain id, value: the validators ordered by their stake in the appchain
+    appchain_rankings: LookupMap<String, AppchainRanking>,
+    // key: (appchain id, validator id), value: the rank of the validator in the appchain
+    appchain_validator_ranks: LookupMap<(String, AccountId), u32>,
+    // the appchains which have validators waiting to update rank in the appchain
+    appchains_waiting_to_update_rank: UnorderedSet<String>,
+    // the appchain whose ranking is being rebuilt in bulk, and the progress of it
+    appchain_rank_rebuilding: Option<(String, RankRebuildingState)>,
+    // increased when the stake of validators is synced or repaired, the ranking is only
+    // changed after that by updating the rank of the validators waiting to update rank
+    ranking_version: u64,
+    // the ranking version when the calculation of decentralization stats is started,
+    // and the progress of it
+    stats_calculation: Option<(u64, StatsCalculationState)>,
+    // the result of the latest finished calculation of decentralization stats
+    decentralization_stats: Option<DecentralizationStats>,
 }
 
 #[near_bindgen]
@@ -106,21 +245,63 @@ impl CouncilKeeper {
             "This contract must be deployed as a sub-account of octopus appchain registry.",
         );
         let (_first, second) = account_id.split_once(".").unwrap();
-        let result = Self {
+        let mut result = Self {
             owner: env::current_account_id(),
             appchain_registry_account: AccountId::from_str(second).unwrap(),
-            dao_contract_account,
             living_appchain_ids: UnorderedSet::new(StorageKey::LivingAppchainIds),
-            validator_stakes: LookupMap::new(StorageKey::ValidatorStakes),
+            validator_stakes: LookupMap::new(StorageKey::CompactValidatorStakes),
             ranked_validators: RankedLookupArray::new(StorageKey::OrderedValidators),
             max_number_of_council_members,
             latest_members: UnorderedSet::new(StorageKey::LatestMembers),
             excluding_validator_accounts: UnorderedSet::new(StorageKey::ExcludingValidatorAccounts),
-            change_histories: LookupArray::new(StorageKey::CouncilChangeHistories),
+            change_histories: LookupArray::new(StorageKey::TargetedCouncilChangeHistories),
+            next_change_history_index: 0,
             validators_waiting_to_update_rank: UnorderedSet::new(
                 StorageKey::ValidatorsWaitingToUpdateRank,
             ),
+            funded_balance: 0,
+            locked_proposal_bonds: 0,
+            proposal_bonds_of_histories: LookupMap::new(StorageKey::ProposalBondsOfHistories),
+            pending_change_history_indexes: LookupMap::new(StorageKey::PendingChangeHistoryIndexes),
+            dao_targets: UnorderedMap::new(StorageKey::DaoTargets),
+            dao_target_removal: None,
+            primary_dao_contract_account: dao_contract_account.clone(),
+            is_migrating_validator_stakes: false,
+            is_migrating_change_histories: false,
+            next_migrating_change_history_index: 0,
+            next_migrating_validator_stake_index: 0,
+            dao_contract_account_of_old_histories: None,
+            rank_rebuilding: None,
+            council_reset: None,
+            council_reset_records: Vector::new(StorageKey::CouncilResetRecords),
+            latest_change_history_hash: Vec::new(),
+            previous_pending_history_indexes: LookupMap::new(
+                StorageKey::PreviousPendingHistoryIndexes,
+            ),
+            pruned_change_history_hash: String::new(),
+            council_snapshots: LookupArray::new(StorageKey::CouncilSnapshots),
+            stake_history_retention: DEFAULT_STAKE_HISTORY_RETENTION,
+            stake_history_index_ranges: LookupMap::new(StorageKey::StakeHistoryIndexRanges),
+            stake_history_entries: LookupMap::new(StorageKey::StakeHistoryEntries),
+            member_tenures: LookupMap::new(StorageKey::MemberTenures),
+            council_terms: LookupMap::new(StorageKey::CouncilTerms),
+            tenure_accounts: UnorderedSet::new(StorageKey::TenureAccounts),
+            appchain_rankings: LookupMap::new(StorageKey::AppchainRankings),
+            appchain_validator_ranks: LookupMap::new(StorageKey::AppchainValidatorRanks),
+            appchains_waiting_to_update_rank: UnorderedSet::new(
+                StorageKey::AppchainsWaitingToUpdateRank,
+            ),
+            appchain_rank_rebuilding: None,
+            ranking_version: 0,
+            stats_calculation: None,
+            decentralization_stats: None,
         };
+        result.dao_targets.insert(
+            &dao_contract_account,
+            &DaoTarget::new(&dao_contract_account),
+        );
+        // the council is empty since the initialization
+        result.take_council_snapshot_if_changed();
         result
     }
     // Assert that the contract is called by an appchain anchor contract and
@@ -145,39 +326,88 @@ impl CouncilKeeper {
     ///
     pub fn sync_validator_stakes_of_anchor(&mut self, stake_records: Vec<ValidatorStakeRecord>) {
         let appchain_id = self.assert_and_update_living_appchain_ids();
+        let mut synced_stakes = Vec::new();
         for stake_record in stake_records {
+            self.migrate_validator_stake_of(&stake_record.validator_id);
             let mut validator_stake = self
                 .validator_stakes
                 .get(&stake_record.validator_id)
                 .unwrap_or(InternalValidatorStake::new(&stake_record.validator_id));
+            let previous_stake = validator_stake
+                .stake_in_appchains
+                .get(&appchain_id)
+                .map_or(0, |stake| stake.0);
             if validator_stake.update_stake_record(&appchain_id, &stake_record) {
                 self.validator_stakes
                     .insert(&stake_record.validator_id, &validator_stake);
-                log!(
-                    "Total stake of validator '{}' has changed, need to update rank.",
-                    stake_record.validator_id
+                self.queue_appchain_rank_update_of(
+                    &stake_record.validator_id,
+                    &appchain_id,
+                    previous_stake,
                 );
+                self.record_stake_history_of(&validator_stake);
                 self.validators_waiting_to_update_rank
                     .insert(&stake_record.validator_id);
+                synced_stakes.push(StakeSyncedEventData {
+                    validator_id: stake_record.validator_id,
+                    appchain_id: appchain_id.clone(),
+                    stake: stake_record.total_stake,
+                    total_stake: validator_stake.total_stake,
+                });
             }
         }
+        if !synced_stakes.is_empty() {
+            self.ranking_version += 1;
+            CouncilKeeperEvent::StakeSynced(synced_stakes).emit();
+        }
     }
     ///
     pub fn update_council_change_histories(&mut self) -> MultiTxsOperationProcessingResult {
+        assert!(
+            !self.is_migrating_validator_stakes,
+            "Validator stakes are being migrated. Please call 'migrate_validator_stakes' first."
+        );
+        self.assert_change_histories_migrated();
+        if self.rank_rebuilding.is_some() || self.should_rebuild_ranks_in_bulk() {
+            if self.rank_rebuilding.is_none() {
+                self.start_rebuilding_ranks();
+            }
+            self.continue_rebuilding_ranks();
+            return MultiTxsOperationProcessingResult::NeedMoreGas;
+        }
         let validator_ids = self.validators_waiting_to_update_rank.to_vec();
-        if validator_ids.len() > 0 {
+        if !validator_ids.is_empty() {
+            let mut changed_ranks = Vec::new();
             for validator_id in validator_ids {
                 let mut validator_stake = self.validator_stakes.get(&validator_id).unwrap();
+                let previous_rank = self.ranked_rank_of(&validator_stake);
                 self.update_validator_rank_of(&mut validator_stake);
                 self.validators_waiting_to_update_rank.remove(&validator_id);
-                if env::used_gas() > Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING) {
+                let validator_stake = self.validator_stakes.get(&validator_id).unwrap();
+                self.fill_rank_of_stake_history(&validator_id, validator_stake.overall_rank);
+                if previous_rank != Some(validator_stake.overall_rank) {
+                    changed_ranks.push(RankChangedEventData {
+                        validator_id,
+                        total_stake: validator_stake.total_stake,
+                        previous_rank,
+                        rank: validator_stake.overall_rank,
+                    });
+                }
+                if env::used_gas() > Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+                    || changed_ranks.len() >= events::MAX_ENTRIES_IN_EVENT_DATA
+                {
                     break;
                 }
             }
+            if !changed_ranks.is_empty() {
+                CouncilKeeperEvent::RankChanged(changed_ranks).emit();
+            }
             return MultiTxsOperationProcessingResult::NeedMoreGas;
         } else {
             self.check_and_generate_change_histories();
-            MultiTxsOperationProcessingResult::Ok
+            // the rankings of appchains don't affect the council,
+            // they are updated after the change histories are generated
+            self.update_appchain_ranks()
         }
     }
     // the function will return true if the rank of validator stake has been changed and updated,
@@ -205,207 +435,424 @@ impl CouncilKeeper {
     }
     //
     fn check_and_generate_change_histories(&mut self) {
-        let validator_accounts = self.ranked_validators.get_slice_of(0, None);
-        // generate a new array of council members
-        let mut council_members = Vec::new();
-        for account_id in validator_accounts {
-            if !self.excluding_validator_accounts.contains(&account_id) {
-                council_members.push(account_id);
-                if council_members.len() >= self.max_number_of_council_members as usize {
-                    break;
-                }
-            }
+        self.assert_change_histories_migrated();
+        // the ranking is incomplete while it is being rebuilt,
+        // the change histories will be generated after the rebuilding or resetting
+        if self.rank_rebuilding.is_some() || self.council_reset.is_some() {
+            return;
         }
+        let council_diff = self.diff_with_latest_members(&self.select_council_members());
         // update `latest_members` and generate change histories
-        for account_id in &council_members {
-            if !self.latest_members.contains(account_id) {
-                self.latest_members.insert(account_id);
-                let history = self.change_histories.append(&mut CouncilChangeHistory {
-                    index: U64::from(0),
-                    action: types::CouncilChangeAction::MemberAdded(account_id.clone()),
-                    state: CouncilChangeHistoryState::WaitingForApplying,
-                    timestamp: U64::from(env::block_timestamp()),
-                });
-                log!(
-                    "Council change history generated: '{}'",
-                    near_sdk::serde_json::to_string(&history).unwrap()
-                );
+        for account_id in council_diff.added {
+            self.latest_members.insert(&account_id);
+            self.generate_change_history(types::CouncilChangeAction::MemberAdded(account_id));
+        }
+        for account_id in council_diff.removed {
+            self.latest_members.remove(&account_id);
+            self.generate_change_history(types::CouncilChangeAction::MemberRemoved(account_id));
+        }
+        self.take_council_snapshot_if_changed();
+    }
+    // The difference between `latest_members` and the selected members, by looking up
+    // the selected members in the set. The set is only iterated for the removed members
+    // if some of the latest members are not selected.
+    pub(crate) fn diff_with_latest_members(
+        &self,
+        selected: &[AccountId],
+    ) -> council_core::CouncilDiff<AccountId> {
+        let added: Vec<AccountId> = selected
+            .iter()
+            .filter(|account_id| !self.latest_members.contains(account_id))
+            .cloned()
+            .collect();
+        let removed = match (selected.len() - added.len()) as u64 == self.latest_members.len() {
+            true => Vec::new(),
+            false => {
+                let selected_set: HashSet<&AccountId> = selected.iter().collect();
+                self.latest_members
+                    .iter()
+                    .filter(|account_id| !selected_set.contains(account_id))
+                    .collect()
+            }
+        };
+        council_core::CouncilDiff { added, removed }
+    }
+    // Walk the ranked validators from the top, until enough eligible validators are found.
+    // Only the validators ranked before the last selected one are read from storage.
+    fn select_council_members(&self) -> Vec<AccountId> {
+        council_core::select_council_members(
+            (0..self.ranked_validators.len()).filter_map(|index| self.ranked_validators.get(index)),
+            self.max_number_of_council_members,
+            |account_id| self.excluding_validator_accounts.contains(account_id),
+        )
+    }
+    //
+    fn generate_change_history(&mut self, action: types::CouncilChangeAction) {
+        let mut history = CouncilChangeHistory {
+            index: U64::from(self.next_change_history_index),
+            action,
+            target_states: self.new_target_states(),
+            timestamp: U64::from(env::block_timestamp()),
+            running_hash: String::new(),
+        };
+        self.next_change_history_index += 1;
+        self.latest_change_history_hash =
+            history.running_hash_with(&self.latest_change_history_hash);
+        history.running_hash = types::to_hex(&self.latest_change_history_hash);
+        self.change_histories.insert(&history.index.0, &history);
+        let cancelled_index = self.coalesce_change_history(&mut history);
+        let event_data = vec![CouncilMemberEventData {
+            account_id: history.action.account_id().clone(),
+            change_history_index: Some(history.index),
+            running_hash: Some(history.running_hash.clone()),
+            timestamp: history.timestamp,
+            target_states: history.target_states.clone(),
+            cancelled_change_history_index: cancelled_index.map(U64::from),
+        }];
+        match &history.action {
+            types::CouncilChangeAction::MemberAdded(account_id) => {
+                self.start_council_term_of(account_id, history.timestamp.0);
+                CouncilKeeperEvent::MemberAdded(event_data).emit();
+            }
+            types::CouncilChangeAction::MemberRemoved(account_id) => {
+                self.end_council_term_of(account_id, history.timestamp.0);
+                CouncilKeeperEvent::MemberRemoved(event_data).emit();
             }
         }
-        for account_id in &self.latest_members.to_vec() {
-            if !council_members.contains(account_id) {
-                self.latest_members.remove(account_id);
-                let history = self.change_histories.append(&mut CouncilChangeHistory {
-                    index: U64::from(0),
-                    action: types::CouncilChangeAction::MemberRemoved(account_id.clone()),
-                    state: CouncilChangeHistoryState::WaitingForApplying,
-                    timestamp: U64::from(env::block_timestamp()),
-                });
+    }
+    // If the latest change history of the same account is still waiting for applying,
+    // it is cancelled by the given one, so neither of them needs to be applied.
+    // Return the index of the cancelled pending change history.
+    fn coalesce_change_history(
+        &mut self,
+        change_history: &mut CouncilChangeHistory,
+    ) -> Option<u64> {
+        let account_id = change_history.action.account_id().clone();
+        if let Some(mut pending_history) = self
+            .pending_change_history_indexes
+            .get(&account_id)
+            .and_then(|index| self.change_histories.get(&index))
+        {
+            let is_cancelled = pending_history.index != change_history.index
+                && council_core::can_coalesce(
+                    pending_history.action.kind(),
+                    pending_history.is_waiting_for_applying(),
+                    change_history.action.kind(),
+                );
+            // record the previous pending change history for rolling back
+            self.previous_pending_history_indexes.insert(
+                &change_history.index.0,
+                &(pending_history.index.0, is_cancelled),
+            );
+            if is_cancelled {
+                pending_history.set_state_of_all_targets(CouncilChangeHistoryState::NoNeedToApply);
+                self.change_histories
+                    .insert(&pending_history.index.0, &pending_history);
+                change_history.set_state_of_all_targets(CouncilChangeHistoryState::NoNeedToApply);
+                self.change_histories
+                    .insert(&change_history.index.0, change_history);
+                self.pending_change_history_indexes.remove(&account_id);
                 log!(
-                    "Council change history generated: '{}'",
-                    near_sdk::serde_json::to_string(&history).unwrap()
+                    "Council change histories '{}' and '{}' cancel each other out, no need to apply them.",
+                    pending_history.index.0,
+                    change_history.index.0
                 );
+                return Some(pending_history.index.0);
             }
         }
+        self.pending_change_history_indexes
+            .insert(&account_id, &change_history.index.0);
+        None
     }
-    ///
-    pub fn set_dao_contract_account(&mut self, account_id: AccountId) {
-        self.assert_owner();
-        self.dao_contract_account = account_id;
-    }
-    ///
+    /// Submit a batch of proposals of the change histories which are waiting for applying,
+    /// and a batch of votes for the proposals which are added but not approved yet.
     pub fn apply_change_histories_to_dao_contract(
         &mut self,
         start_index: U64,
     ) -> MultiTxsOperationProcessingResult {
         assert!(
-            self.dao_contract_account.to_string().len() > 0,
-            "Invalid account id of DAO contract."
+            !self.dao_targets.is_empty(),
+            "There is no DAO contract to apply."
+        );
+        self.assert_change_histories_migrated();
+        assert!(
+            self.council_reset.is_none(),
+            "Council is being reset. Please call 'clear_council_members_and_regenerate_change_histories' first."
         );
+        let dao_targets = self.dao_targets.values_as_vector().to_vec();
         let index_range = self.change_histories.index_range();
-        let mut index = start_index.0;
-        while index <= index_range.end_index.0
-            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+        let mut submissions = Vec::<(CouncilChangeHistory, DaoTarget)>::new();
+        let mut votes = Vec::<(CouncilChangeHistory, DaoTarget, u64)>::new();
+        let mut proposal_bonds: Balance = 0;
+        let mut error_message: Option<String> = None;
+        let mut timed_out_submissions = Vec::new();
+        let mut submitting_proposals = Vec::new();
+        // the change histories before the start index of the range are pruned
+        let mut index = start_index.0.max(index_range.start_index.0);
+        'histories: while index <= index_range.end_index.0
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_SCANNING_CHANGE_HISTORIES)
         {
-            let mut change_history = self.change_histories.get(&index).unwrap();
-            match change_history.state {
-                CouncilChangeHistoryState::WaitingForApplying => {
-                    self.add_proposal_to_dao_contract(&mut change_history)
+            let mut change_history = match self.change_histories.get(&index) {
+                Some(change_history) => change_history,
+                None => {
+                    index += 1;
+                    continue;
                 }
-                CouncilChangeHistoryState::ProposalAdded(_) => {
-                    self.act_proposal_on_dao_contract(&mut change_history)
+            };
+            for dao_target in &dao_targets {
+                if let Some(CouncilChangeHistoryState::ProposalSubmitting(submitted_at)) =
+                    change_history
+                        .target_states
+                        .get(&dao_target.dao_contract_account)
+                        .cloned()
+                {
+                    if env::block_timestamp() > submitted_at + PROPOSAL_SUBMITTING_TIMEOUT_IN_NANOS
+                    {
+                        timed_out_submissions.push(ProposalSubmissionTimedOutEventData {
+                            dao_contract_account: dao_target.dao_contract_account.clone(),
+                            change_history_index: U64::from(index),
+                            submitted_at: U64::from(submitted_at),
+                        });
+                        change_history.target_states.insert(
+                            dao_target.dao_contract_account.clone(),
+                            CouncilChangeHistoryState::WaitingForApplying,
+                        );
+                        // the state is kept even if the batch is full before it is submitted
+                        self.change_histories.insert(&index, &change_history);
+                    }
+                }
+                match change_history
+                    .target_states
+                    .get(&dao_target.dao_contract_account)
+                {
+                    Some(CouncilChangeHistoryState::WaitingForApplying) => {
+                        if submissions.len() >= MAX_DAO_CALLS_IN_BATCH {
+                            break 'histories;
+                        }
+                        let proposal_bond = match dao_target.get_proposal_bond_to_attach() {
+                            Some(proposal_bond) => proposal_bond,
+                            None => {
+                                error_message = Some(format!(
+                                    "Proposal bond of DAO contract '{}' is not synced. Please call 'sync_proposal_bond_of_dao_contract' first.",
+                                    dao_target.dao_contract_account
+                                ));
+                                break 'histories;
+                            }
+                        };
+                        let available_balance = self.get_available_balance();
+                        if available_balance < proposal_bonds + proposal_bond {
+                            error_message = Some(format!(
+                                "Insufficient balance for proposal bond of DAO contract '{}'. Available: {}, required: {}. Please call 'fund_keeper' first.",
+                                dao_target.dao_contract_account,
+                                available_balance,
+                                proposal_bonds + proposal_bond
+                            ));
+                            break 'histories;
+                        }
+                        proposal_bonds += proposal_bond;
+                        // The state is set to `ProposalSubmitting` before the promise is created,
+                        // to avoid submitting the same change history again before the callback.
+                        change_history.target_states.insert(
+                            dao_target.dao_contract_account.clone(),
+                            CouncilChangeHistoryState::ProposalSubmitting(env::block_timestamp()),
+                        );
+                        self.change_histories.insert(&index, &change_history);
+                        submitting_proposals.push(DaoProposalEventData {
+                            dao_contract_account: dao_target.dao_contract_account.clone(),
+                            change_history_index: U64::from(index),
+                            proposal_id: None,
+                            state: CouncilChangeHistoryState::ProposalSubmitting(
+                                env::block_timestamp(),
+                            ),
+                        });
+                        submissions.push((change_history.clone(), dao_target.clone()));
+                    }
+                    Some(CouncilChangeHistoryState::ProposalAdded(proposal_id)) => {
+                        if votes.len() >= MAX_DAO_CALLS_IN_BATCH {
+                            break 'histories;
+                        }
+                        votes.push((change_history.clone(), dao_target.clone(), *proposal_id));
+                    }
+                    _ => (),
                 }
-                _ => (),
             }
             index += 1;
         }
-        if index > index_range.end_index.0 {
+        if !timed_out_submissions.is_empty() {
+            CouncilKeeperEvent::ProposalSubmissionTimedOut(timed_out_submissions).emit();
+        }
+        if !submitting_proposals.is_empty() {
+            CouncilKeeperEvent::ProposalSubmitting(submitting_proposals).emit();
+        }
+        self.add_proposals_to_dao_contracts(submissions);
+        self.act_proposals_on_dao_contracts(votes);
+        if let Some(message) = error_message {
+            MultiTxsOperationProcessingResult::Error(message)
+        } else if index > index_range.end_index.0 {
             MultiTxsOperationProcessingResult::Ok
         } else {
             MultiTxsOperationProcessingResult::NeedMoreGas
         }
     }
-    //
-    fn add_proposal_to_dao_contract(&mut self, change_history: &mut CouncilChangeHistory) {
-        #[derive(Serialize, Deserialize, Clone)]
-        #[serde(crate = "near_sdk::serde")]
-        enum ProposalKind {
-            /// Add member to given role in the policy. This is short cut to updating the whole policy.
-            AddMemberToRole { member_id: AccountId, role: String },
-            /// Remove member to given role in the policy. This is short cut to updating the whole policy.
-            RemoveMemberFromRole { member_id: AccountId, role: String },
-        }
-        #[derive(Serialize, Deserialize, Clone)]
-        #[serde(crate = "near_sdk::serde")]
-        struct ProposalInput {
-            /// Description of this proposal.
-            pub description: String,
-            /// Kind of proposal with relevant information.
-            pub kind: ProposalKind,
+    // Send `add_proposal` of the change histories to DAO contracts in parallel,
+    // and resolve all of the results in one callback.
+    fn add_proposals_to_dao_contracts(
+        &mut self,
+        submissions: Vec<(CouncilChangeHistory, DaoTarget)>,
+    ) {
+        if submissions.is_empty() {
+            return;
         }
-        #[derive(Serialize, Deserialize, Clone)]
-        #[serde(crate = "near_sdk::serde")]
-        struct Input {
-            pub proposal: ProposalInput,
-        }
-        let args = match &change_history.action {
-            types::CouncilChangeAction::MemberAdded(account_id) => Input {
-                proposal: ProposalInput {
-                    description: format!(
-                        "Add '{}' to council based on the rule in contract '{}'.",
-                        account_id,
-                        env::current_account_id()
-                    ),
-                    kind: ProposalKind::AddMemberToRole {
-                        member_id: account_id.clone(),
-                        role: "council".to_string(),
-                    },
-                },
-            },
-            types::CouncilChangeAction::MemberRemoved(account_id) => Input {
-                proposal: ProposalInput {
-                    description: format!(
-                        "Remove '{}' from council based on the rule in contract '{}'.",
-                        account_id,
-                        env::current_account_id()
-                    ),
-                    kind: ProposalKind::RemoveMemberFromRole {
-                        member_id: account_id.clone(),
-                        role: "council".to_string(),
-                    },
-                },
-            },
-        };
-        let args = near_sdk::serde_json::to_vec(&args)
-            .expect("Failed to serialize the cross contract args using JSON.");
-        Promise::new(self.dao_contract_account.clone())
-            .function_call(
-                "add_proposal".to_string(),
-                args,
-                0,
-                Gas::ONE_TERA.mul(T_GAS_FOR_ADD_PROPOSAL),
-            )
-            .then(
-                ext_self::ext(env::current_account_id())
-                    .with_attached_deposit(0)
-                    .with_static_gas(Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVE_ADD_PROPOSAL))
-                    .with_unused_gas_weight(0)
-                    .resolve_add_proposal(change_history),
+        let mut joint_promise: Option<Promise> = None;
+        let mut dao_calls = Vec::<DaoCallOfChangeHistory>::new();
+        // the callback needs to send `act_proposal` for all of the added proposals
+        let mut gas_for_voting = Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVING_DAO_CALLS);
+        for (change_history, dao_target) in submissions {
+            let adapter = dao_target.dao_adapter.adapter();
+            // the proposal bond is checked before submitting
+            let proposal_bond = dao_target.get_proposal_bond_to_attach().unwrap_or(0);
+            let function_call =
+                adapter.apply_change_call(&change_history.action, &dao_target.role_name);
+            let promise = Promise::new(dao_target.dao_contract_account.clone()).function_call(
+                function_call.method_name,
+                function_call.args,
+                proposal_bond,
+                function_call.gas,
             );
+            joint_promise = Some(match joint_promise {
+                Some(joint_promise) => joint_promise.and(promise),
+                None => promise,
+            });
+            if adapter.is_proposal_based() {
+                gas_for_voting += adapter
+                    .approve_change_call(&change_history.action, &dao_target.role_name, 0)
+                    .gas
+                    + Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVING_EACH_DAO_CALL);
+            }
+            dao_calls.push(DaoCallOfChangeHistory {
+                index: change_history.index,
+                expected_state: change_history
+                    .target_states
+                    .get(&dao_target.dao_contract_account)
+                    .unwrap()
+                    .clone(),
+                dao_contract_account: dao_target.dao_contract_account,
+                proposal_bond: U128::from(proposal_bond),
+            });
+        }
+        let gas_for_resolving = Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVING_DAO_CALLS)
+            + Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVING_EACH_DAO_CALL * dao_calls.len() as u64)
+            + gas_for_voting;
+        joint_promise.unwrap().then(
+            ext_self::ext(env::current_account_id())
+                .with_attached_deposit(0)
+                .with_static_gas(gas_for_resolving)
+                .with_unused_gas_weight(0)
+                .resolve_add_proposals(dao_calls),
+        );
     }
-    //
-    fn act_proposal_on_dao_contract(&mut self, change_history: &mut CouncilChangeHistory) {
-        #[derive(Serialize, Deserialize, Clone)]
-        #[serde(crate = "near_sdk::serde")]
-        enum Action {
-            /// Vote to approve given proposal or bounty.
-            VoteApprove,
+    // Send `act_proposal` of the change histories to DAO contracts in parallel,
+    // and resolve all of the results in one callback.
+    fn act_proposals_on_dao_contracts(
+        &mut self,
+        votes: Vec<(CouncilChangeHistory, DaoTarget, u64)>,
+    ) {
+        if votes.is_empty() {
+            return;
         }
-        #[derive(Serialize, Deserialize, Clone)]
-        #[serde(crate = "near_sdk::serde")]
-        struct Input {
-            pub id: u64,
-            pub action: Action,
-            pub memo: Option<String>,
-        }
-        let args = Input {
-            id: match change_history.state {
-                CouncilChangeHistoryState::ProposalAdded(proposal_id) => proposal_id,
-                _ => panic!(
-                    "Invalid state of change history: '{}'",
-                    near_sdk::serde_json::to_string(change_history).unwrap()
-                ),
-            },
-            action: Action::VoteApprove,
-            memo: Some(format!(
-                "Automatically vote approve by '{}'.",
-                env::current_account_id()
-            )),
-        };
-        let args = near_sdk::serde_json::to_vec(&args)
-            .expect("Failed to serialize the cross contract args using JSON.");
-        Promise::new(self.dao_contract_account.clone())
-            .function_call(
-                "act_proposal".to_string(),
-                args,
+        let mut joint_promise: Option<Promise> = None;
+        let mut dao_calls = Vec::<DaoCallOfChangeHistory>::new();
+        for (change_history, dao_target, proposal_id) in votes {
+            let function_call = dao_target.dao_adapter.adapter().approve_change_call(
+                &change_history.action,
+                &dao_target.role_name,
+                proposal_id,
+            );
+            let promise = Promise::new(dao_target.dao_contract_account.clone()).function_call(
+                function_call.method_name,
+                function_call.args,
                 0,
-                Gas::ONE_TERA.mul(T_GAS_FOR_ACT_PROPOSAL),
-            )
-            .then(
-                ext_self::ext(env::current_account_id())
-                    .with_attached_deposit(0)
-                    .with_static_gas(Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVE_ACT_PROPOSAL))
-                    .with_unused_gas_weight(0)
-                    .resolve_act_proposal(change_history),
+                function_call.gas,
             );
+            joint_promise = Some(match joint_promise {
+                Some(joint_promise) => joint_promise.and(promise),
+                None => promise,
+            });
+            dao_calls.push(DaoCallOfChangeHistory {
+                index: change_history.index,
+                dao_contract_account: dao_target.dao_contract_account,
+                expected_state: CouncilChangeHistoryState::ProposalAdded(proposal_id),
+                proposal_bond: U128(0),
+            });
+        }
+        let gas_for_resolving = Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVING_DAO_CALLS)
+            + Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVING_EACH_DAO_CALL * dao_calls.len() as u64);
+        joint_promise.unwrap().then(
+            ext_self::ext(env::current_account_id())
+                .with_attached_deposit(0)
+                .with_static_gas(gas_for_resolving)
+                .with_unused_gas_weight(0)
+                .resolve_act_proposals(dao_calls),
+        );
     }
-    ///
+    // Load the change history from storage, and check that the state of it for the
+    // DAO contract is still the expected one. As the change history may be modified,
+    // cleared or re-applied while a promise is pending, the callbacks must not
+    // change the state of a change history which is not in the expected state.
+    fn get_change_history_in_state(
+        &self,
+        index: &u64,
+        dao_contract_account: &AccountId,
+        expected_state: &CouncilChangeHistoryState,
+    ) -> Option<CouncilChangeHistory> {
+        match self.change_histories.get(index) {
+            Some(change_history) => match change_history.target_states.get(dao_contract_account) {
+                Some(state) if state.eq(expected_state) => Some(change_history),
+                state => {
+                    log!(
+                            "The state of change history '{}' for DAO contract '{}' is '{}' rather than '{}', ignore the result.",
+                            index,
+                            dao_contract_account,
+                            near_sdk::serde_json::to_string(&state).unwrap(),
+                            near_sdk::serde_json::to_string(expected_state).unwrap()
+                        );
+                    None
+                }
+            },
+            None => {
+                log!(
+                    "Change history '{}' no longer exists, ignore the result.",
+                    index
+                );
+                None
+            }
+        }
+    }
+    //
+    fn get_state_of_change_history(
+        &self,
+        index: &u64,
+        dao_contract_account: &AccountId,
+    ) -> Option<CouncilChangeHistoryState> {
+        self.change_histories.get(index).and_then(|change_history| {
+            change_history
+                .target_states
+                .get(dao_contract_account)
+                .cloned()
+        })
+    }
+    /// Change the number of council members, the change histories are generated by the new number.
     pub fn set_max_number_of_council_members(&mut self, max_number_of_council_members: u32) {
         self.assert_owner();
         assert!(
             self.max_number_of_council_members != max_number_of_council_members,
             "The value is not changed."
         );
+        CouncilKeeperEvent::emit_parameter_changed(
+            "max_number_of_council_members",
+            &self.max_number_of_council_members,
+            &max_number_of_council_members,
+        );
         self.max_number_of_council_members = max_number_of_council_members;
         //
         self.check_and_generate_change_histories();
@@ -415,7 +862,7 @@ impl CouncilKeeper {
     pub fn exclude_validator_from_council(&mut self) {
         let validator_id = env::predecessor_account_id();
         assert!(
-            self.validator_stakes.contains_key(&validator_id),
+            self.get_validator_stake(&validator_id).is_some(),
             "Only valid validator can call this function."
         );
         assert!(
@@ -425,6 +872,11 @@ impl CouncilKeeper {
         );
         //
         self.excluding_validator_accounts.insert(&validator_id);
+        CouncilKeeperEvent::ExclusionToggled(vec![ExclusionToggledEventData {
+            validator_id,
+            is_excluded: true,
+        }])
+        .emit();
         self.check_and_generate_change_histories();
     }
     /// Called by excluding validator account,
@@ -437,6 +889,11 @@ impl CouncilKeeper {
         );
         //
         self.excluding_validator_accounts.remove(&validator_id);
+        CouncilKeeperEvent::ExclusionToggled(vec![ExclusionToggledEventData {
+            validator_id,
+            is_excluded: false,
+        }])
+        .emit();
         self.check_and_generate_change_histories();
     }
 }
@@ -452,14 +909,89 @@ impl Ownable for CouncilKeeper {
     }
 }
 
+impl CouncilKeeper {
+    // Assert that the caller is the owner or the primary DAO contract.
+    pub(crate) fn assert_owner_or_dao(&self) {
+        let predecessor = env::predecessor_account_id();
+        assert!(
+            predecessor.eq(&self.owner) || predecessor.eq(&self.primary_dao_contract_account),
+            "Only owner or the primary DAO contract can call this function."
+        );
+    }
+    // The rank of the validator if it is in the ranked validators.
+    pub(crate) fn ranked_rank_of(&self, validator_stake: &InternalValidatorStake) -> Option<u32> {
+        match self.ranked_validators.get(validator_stake.overall_rank) {
+            Some(validator_id) if validator_id.eq(&validator_stake.validator_id) => {
+                Some(validator_stake.overall_rank)
+            }
+            _ => None,
+        }
+    }
+    // Get the stake of a validator, which may still be stored in old layout during migration.
+    pub(crate) fn get_validator_stake(
+        &self,
+        validator_id: &AccountId,
+    ) -> Option<InternalValidatorStake> {
+        self.validator_stakes.get(validator_id).or_else(|| {
+            match self.is_migrating_validator_stakes {
+                true => self.get_unmigrated_validator_stake(validator_id),
+                false => None,
+            }
+        })
+    }
+}
+
+impl StakeInAppchains {
+    //
+    pub fn get(&self, appchain_id: &String) -> Option<U128> {
+        match self {
+            StakeInAppchains::Inline(stakes) => stakes
+                .iter()
+                .find(|(id, _)| id.eq(appchain_id))
+                .map(|(_, stake)| *stake),
+            StakeInAppchains::Spilled(stakes) => stakes.get(appchain_id),
+        }
+    }
+    // The inline stakes spill to a collection, when the number of appchains
+    // exceeds `MAX_INLINE_STAKE_IN_APPCHAINS`.
+    pub fn insert(&mut self, validator_id: &AccountId, appchain_id: &String, stake: &U128) {
+        match self {
+            StakeInAppchains::Inline(stakes) => {
+                if let Some(record) = stakes.iter_mut().find(|(id, _)| id.eq(appchain_id)) {
+                    record.1 = *stake;
+                } else if stakes.len() < MAX_INLINE_STAKE_IN_APPCHAINS {
+                    stakes.push((appchain_id.clone(), *stake));
+                } else {
+                    let mut spilled_stakes = UnorderedMap::new(
+                        StorageKey::ValidatorStakeInAppchains(validator_id.clone()),
+                    );
+                    for (id, value) in stakes.iter() {
+                        spilled_stakes.insert(id, value);
+                    }
+                    spilled_stakes.insert(appchain_id, stake);
+                    *self = StakeInAppchains::Spilled(spilled_stakes);
+                }
+            }
+            StakeInAppchains::Spilled(stakes) => {
+                stakes.insert(appchain_id, stake);
+            }
+        }
+    }
+    //
+    pub fn to_hash_map(&self) -> HashMap<String, U128> {
+        match self {
+            StakeInAppchains::Inline(stakes) => stakes.iter().cloned().collect(),
+            StakeInAppchains::Spilled(stakes) => stakes.iter().collect(),
+        }
+    }
+}
+
 impl InternalValidatorStake {
     //
     pub fn new(validator_id: &AccountId) -> Self {
         Self {
             validator_id: validator_id.clone(),
-            stake_in_appchains: UnorderedMap::new(StorageKey::ValidatorStakeInAppchains(
-                validator_id.clone(),
-            )),
+            stake_in_appchains: StakeInAppchains::Inline(Vec::new()),
             total_stake: U128(0),
             overall_rank: u32::MAX,
         }
@@ -476,9 +1008,14 @@ impl InternalValidatorStake {
         );
         let old_value = self.stake_in_appchains.get(&appchain_id).unwrap_or(U128(0));
         if stake_record.total_stake != old_value {
+            let validator_id = self.validator_id.clone();
             self.stake_in_appchains
-                .insert(appchain_id, &stake_record.total_stake);
-            self.total_stake.0 = self.total_stake.0 - old_value.0 + stake_record.total_stake.0;
+                .insert(&validator_id, appchain_id, &stake_record.total_stake);
+            self.total_stake.0 = council_core::updated_total_stake(
+                self.total_stake.0,
+                old_value.0,
+                stake_record.total_stake.0,
+            );
             true
         } else {
             false
@@ -486,16 +1023,9 @@ impl InternalValidatorStake {
     }
     //
     pub fn to_json_type(&self) -> ValidatorStake {
-        let mut stake_in_appchains = HashMap::<String, U128>::new();
-        for appchain_id in self.stake_in_appchains.keys() {
-            stake_in_appchains.insert(
-                appchain_id.clone(),
-                self.stake_in_appchains.get(&appchain_id).unwrap(),
-            );
-        }
         ValidatorStake {
             validator_id: self.validator_id.clone(),
-            stake_in_appchains,
+            stake_in_appchains: self.stake_in_appchains.to_hash_map(),
             total_stake: self.total_stake.clone(),
             overall_rank: self.overall_rank,
         }
@@ -518,50 +1048,708 @@ impl RankValueHolder<AccountId> for LookupMap<AccountId, InternalValidatorStake>
 #[near_bindgen]
 impl ResolverForSelfCallback for CouncilKeeper {
     //
-    fn resolve_add_proposal(&mut self, change_history: &mut CouncilChangeHistory) {
+    fn resolve_add_proposals(&mut self, dao_calls: Vec<DaoCallOfChangeHistory>) {
         assert_self();
-        match env::promise_result(0) {
-            PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Successful(bytes) => {
-                change_history.state = CouncilChangeHistoryState::ProposalAdded(
-                    near_sdk::serde_json::from_slice::<u64>(&bytes).unwrap(),
-                );
-                self.change_histories
-                    .insert(&change_history.index.0, change_history);
-                //
-                self.act_proposal_on_dao_contract(change_history);
+        assert_eq!(
+            env::promise_results_count(),
+            dao_calls.len() as u64,
+            "Mismatch count of promise results."
+        );
+        let mut votes = Vec::<(CouncilChangeHistory, DaoTarget, u64)>::new();
+        let mut submitted_proposals = Vec::new();
+        let mut approved_proposals = Vec::new();
+        let mut failed_proposals = Vec::new();
+        for (result_index, dao_call) in dao_calls.into_iter().enumerate() {
+            match env::promise_result(result_index as u64) {
+                PromiseResult::NotReady => unreachable!(),
+                PromiseResult::Successful(bytes) => {
+                    let dao_target = match self.dao_targets.get(&dao_call.dao_contract_account) {
+                        Some(dao_target) => dao_target,
+                        None => {
+                            log!(
+                                "DAO contract '{}' is no longer a target of council changes, ignore the result.",
+                                dao_call.dao_contract_account
+                            );
+                            continue;
+                        }
+                    };
+                    // the result is ignored if the change history has been changed since
+                    // the call, as the state transition of it is not applied
+                    let mut change_history = match self.get_change_history_in_state(
+                        &dao_call.index.0,
+                        &dao_call.dao_contract_account,
+                        &dao_call.expected_state,
+                    ) {
+                        Some(change_history) => change_history,
+                        None => continue,
+                    };
+                    let state = match dao_target
+                        .dao_adapter
+                        .adapter()
+                        .state_after_applying(&bytes)
+                    {
+                        Some(state) => state,
+                        None => {
+                            log!(
+                                "Failed to parse the result of applying change history '{}' to DAO contract '{}'.",
+                                dao_call.index.0,
+                                dao_call.dao_contract_account
+                            );
+                            change_history.target_states.insert(
+                                dao_call.dao_contract_account.clone(),
+                                CouncilChangeHistoryState::WaitingForApplying,
+                            );
+                            self.change_histories
+                                .insert(&dao_call.index.0, &change_history);
+                            failed_proposals.push(DaoProposalFailedEventData {
+                                state: Some(CouncilChangeHistoryState::WaitingForApplying),
+                                dao_contract_account: dao_call.dao_contract_account,
+                                change_history_index: dao_call.index,
+                                stage: DaoCallStage::AddProposal,
+                            });
+                            continue;
+                        }
+                    };
+                    // the bond is held by DAO contract until the proposal is approved
+                    self.lock_proposal_bond_of(
+                        &dao_call.index.0,
+                        &dao_call.dao_contract_account,
+                        dao_call.proposal_bond.0,
+                    );
+                    change_history
+                        .target_states
+                        .insert(dao_call.dao_contract_account.clone(), state.clone());
+                    self.change_histories
+                        .insert(&dao_call.index.0, &change_history);
+                    match state {
+                        CouncilChangeHistoryState::ProposalAdded(proposal_id) => {
+                            submitted_proposals.push(DaoProposalEventData {
+                                dao_contract_account: dao_call.dao_contract_account,
+                                change_history_index: dao_call.index,
+                                proposal_id: Some(proposal_id),
+                                state,
+                            });
+                            votes.push((change_history, dao_target, proposal_id));
+                        }
+                        CouncilChangeHistoryState::AppliedDirectly => {
+                            approved_proposals.push(DaoProposalEventData {
+                                dao_contract_account: dao_call.dao_contract_account,
+                                change_history_index: dao_call.index,
+                                proposal_id: None,
+                                state,
+                            });
+                        }
+                        _ => (),
+                    }
+                }
+                PromiseResult::Failed => {
+                    if let Some(mut change_history) = self.get_change_history_in_state(
+                        &dao_call.index.0,
+                        &dao_call.dao_contract_account,
+                        &dao_call.expected_state,
+                    ) {
+                        change_history.target_states.insert(
+                            dao_call.dao_contract_account.clone(),
+                            CouncilChangeHistoryState::WaitingForApplying,
+                        );
+                        self.change_histories
+                            .insert(&dao_call.index.0, &change_history);
+                    }
+                    failed_proposals.push(DaoProposalFailedEventData {
+                        state: self.get_state_of_change_history(
+                            &dao_call.index.0,
+                            &dao_call.dao_contract_account,
+                        ),
+                        dao_contract_account: dao_call.dao_contract_account,
+                        change_history_index: dao_call.index,
+                        stage: DaoCallStage::AddProposal,
+                    });
+                }
             }
-            PromiseResult::Failed => {
-                log!(
-                    "Failed to add proposal for change history: '{}'",
-                    near_sdk::serde_json::to_string(&change_history).unwrap()
-                );
+        }
+        if !submitted_proposals.is_empty() {
+            CouncilKeeperEvent::ProposalSubmitted(submitted_proposals).emit();
+        }
+        if !approved_proposals.is_empty() {
+            CouncilKeeperEvent::ProposalApproved(approved_proposals).emit();
+        }
+        if !failed_proposals.is_empty() {
+            CouncilKeeperEvent::ProposalFailed(failed_proposals).emit();
+        }
+        //
+        self.act_proposals_on_dao_contracts(votes);
+    }
+    //
+    fn resolve_act_proposals(&mut self, dao_calls: Vec<DaoCallOfChangeHistory>) {
+        assert_self();
+        assert_eq!(
+            env::promise_results_count(),
+            dao_calls.len() as u64,
+            "Mismatch count of promise results."
+        );
+        let mut approved_proposals = Vec::new();
+        let mut failed_proposals = Vec::new();
+        for (result_index, dao_call) in dao_calls.into_iter().enumerate() {
+            match env::promise_result(result_index as u64) {
+                PromiseResult::NotReady => unreachable!(),
+                PromiseResult::Successful(_) => {
+                    let proposal_id = match dao_call.expected_state {
+                        CouncilChangeHistoryState::ProposalAdded(id) => id,
+                        _ => panic!(
+                            "Invalid expected state of council change history: '{}'",
+                            near_sdk::serde_json::to_string(&dao_call.expected_state).unwrap()
+                        ),
+                    };
+                    // the bond is returned by DAO contract once the proposal is approved
+                    self.release_proposal_bond_of(
+                        &dao_call.index.0,
+                        &dao_call.dao_contract_account,
+                    );
+                    if let Some(mut change_history) = self.get_change_history_in_state(
+                        &dao_call.index.0,
+                        &dao_call.dao_contract_account,
+                        &dao_call.expected_state,
+                    ) {
+                        change_history.target_states.insert(
+                            dao_call.dao_contract_account.clone(),
+                            CouncilChangeHistoryState::ProposalApproved(proposal_id),
+                        );
+                        self.change_histories
+                            .insert(&dao_call.index.0, &change_history);
+                        approved_proposals.push(DaoProposalEventData {
+                            dao_contract_account: dao_call.dao_contract_account,
+                            change_history_index: dao_call.index,
+                            proposal_id: Some(proposal_id),
+                            state: CouncilChangeHistoryState::ProposalApproved(proposal_id),
+                        });
+                    }
+                }
+                PromiseResult::Failed => {
+                    // the proposal will be voted again
+                    failed_proposals.push(DaoProposalFailedEventData {
+                        state: self.get_state_of_change_history(
+                            &dao_call.index.0,
+                            &dao_call.dao_contract_account,
+                        ),
+                        dao_contract_account: dao_call.dao_contract_account,
+                        change_history_index: dao_call.index,
+                        stage: DaoCallStage::ActProposal,
+                    });
+                }
             }
         }
+        if !approved_proposals.is_empty() {
+            CouncilKeeperEvent::ProposalApproved(approved_proposals).emit();
+        }
+        if !failed_proposals.is_empty() {
+            CouncilKeeperEvent::ProposalFailed(failed_proposals).emit();
+        }
     }
     //
-    fn resolve_act_proposal(&mut self, change_history: &mut CouncilChangeHistory) {
+    fn resolve_get_policy(&mut self, dao_contract_account: AccountId) {
         assert_self();
+        #[derive(Serialize, Deserialize, Clone)]
+        #[serde(crate = "near_sdk::serde")]
+        struct Policy {
+            /// Bond for a proposal.
+            pub proposal_bond: U128,
+        }
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Successful(_) => {
-                let proposal_id = match change_history.state {
-                    CouncilChangeHistoryState::ProposalAdded(id) => id,
-                    _ => panic!(
-                        "Invalid state of council change history: '{}'",
-                        near_sdk::serde_json::to_string(change_history).unwrap()
-                    ),
-                };
-                change_history.state = CouncilChangeHistoryState::ProposalApproved(proposal_id);
-                self.change_histories
-                    .insert(&change_history.index.0, change_history);
+            PromiseResult::Successful(bytes) => {
+                let policy = near_sdk::serde_json::from_slice::<Policy>(&bytes)
+                    .expect("Failed to parse the policy of DAO contract.");
+                let mut dao_target = self
+                    .dao_targets
+                    .get(&dao_contract_account)
+                    .expect("The DAO contract is no longer a target of council changes.");
+                dao_target.proposal_bond = Some(policy.proposal_bond);
+                self.dao_targets.insert(&dao_contract_account, &dao_target);
+                log!(
+                    "Proposal bond of DAO contract '{}' is synced: {}",
+                    dao_contract_account,
+                    policy.proposal_bond.0
+                );
             }
             PromiseResult::Failed => {
                 log!(
-                    "Failed to act proposal for change history: '{}'",
-                    near_sdk::serde_json::to_string(&change_history).unwrap()
+                    "Failed to get policy of DAO contract '{}'.",
+                    dao_contract_account
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+    use test_utils::*;
+
+    const GRANTS_DAO_ACCOUNT: &str = "grants-dao.testnet";
+
+    fn add_direct_dao_target(contract: &mut CouncilKeeper, dao_contract_account: &str) {
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.set_dao_target(
+            account(dao_contract_account),
+            "council".to_string(),
+            DaoAdapterKind::Direct {
+                add_member_method: "add_member".to_string(),
+                remove_member_method: "remove_member".to_string(),
+            },
+        );
+    }
+
+    fn toggle_exclusion_of(contract: &mut CouncilKeeper, validator_id: &str) {
+        set_context(validator_id, Vec::new());
+        match contract
+            .excluding_validator_accounts
+            .contains(&account(validator_id))
+        {
+            true => contract.recover_excluding_validator(),
+            false => contract.exclude_validator_from_council(),
+        }
+    }
+
+    fn states_of_histories(contract: &CouncilKeeper) -> Vec<Option<CouncilChangeHistoryState>> {
+        let index_range = contract.change_histories.index_range();
+        (index_range.start_index.0..index_range.end_index.0 + 1)
+            .map(|index| state_of(contract, index, DAO_CONTRACT_ACCOUNT))
+            .collect()
+    }
+
+    #[test]
+    fn test_select_council_members_by_ranking_and_exclusion() {
+        let mut contract = new_contract(2);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 100),
+                ("bob.testnet", 300),
+                ("charlie.testnet", 200),
+                ("dave.testnet", 50),
+            ],
+        );
+        assert_eq!(
+            contract.select_council_members(),
+            accounts(&["bob.testnet", "charlie.testnet"])
+        );
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["bob.testnet", "charlie.testnet"])
+        );
+        // the excluded validator is skipped, the next one takes the seat
+        toggle_exclusion_of(&mut contract, "bob.testnet");
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "charlie.testnet"])
+        );
+        // the stakes in all appchains are summed up
+        sync_and_update_stakes(&mut contract, "appchain2", &[("dave.testnet", 400)]);
+        assert_eq!(
+            contract
+                .get_validator_stake_of(account("dave.testnet"))
+                .total_stake,
+            U128(450 * NEAR)
+        );
+        assert_eq!(
+            contract.select_council_members(),
+            accounts(&["dave.testnet", "charlie.testnet"])
+        );
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["charlie.testnet", "dave.testnet"])
+        );
+    }
+
+    #[test]
+    fn test_diff_with_latest_members() {
+        let mut contract = new_contract(2);
+        for account_id in accounts(&["alice.testnet", "bob.testnet"]) {
+            contract.latest_members.insert(&account_id);
+        }
+        let council_diff =
+            contract.diff_with_latest_members(&accounts(&["bob.testnet", "alice.testnet"]));
+        assert!(council_diff.added.is_empty());
+        assert!(council_diff.removed.is_empty());
+        let council_diff =
+            contract.diff_with_latest_members(&accounts(&["charlie.testnet", "bob.testnet"]));
+        assert_eq!(council_diff.added, accounts(&["charlie.testnet"]));
+        assert_eq!(council_diff.removed, accounts(&["alice.testnet"]));
+        let council_diff = contract.diff_with_latest_members(&accounts(&["dave.testnet"]));
+        assert_eq!(council_diff.added, accounts(&["dave.testnet"]));
+        assert_eq!(
+            council_diff.removed,
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+    }
+
+    #[test]
+    fn test_coalesce_cancelling_change_histories() {
+        let mut contract = new_contract(2);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+            ],
+        );
+        // alice is replaced by charlie, and then returns to the council
+        toggle_exclusion_of(&mut contract, "alice.testnet");
+        toggle_exclusion_of(&mut contract, "alice.testnet");
+        assert_eq!(
+            states_of_histories(&contract),
+            vec![
+                Some(CouncilChangeHistoryState::NoNeedToApply),
+                Some(CouncilChangeHistoryState::WaitingForApplying),
+                Some(CouncilChangeHistoryState::NoNeedToApply),
+                Some(CouncilChangeHistoryState::NoNeedToApply),
+                Some(CouncilChangeHistoryState::WaitingForApplying),
+                Some(CouncilChangeHistoryState::NoNeedToApply),
+            ]
+        );
+        assert_eq!(
+            contract
+                .pending_change_history_indexes
+                .get(&account("alice.testnet")),
+            Some(4)
+        );
+        assert_eq!(
+            contract
+                .pending_change_history_indexes
+                .get(&account("charlie.testnet")),
+            None
+        );
+        assert_eq!(
+            contract.previous_pending_history_indexes.get(&3),
+            Some((0, true))
+        );
+        assert_eq!(
+            contract.previous_pending_history_indexes.get(&5),
+            Some((2, true))
+        );
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+    }
+
+    #[test]
+    fn test_change_history_in_flight_is_not_coalesced() {
+        let mut contract = new_contract(1);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 0);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[("alice.testnet", 300), ("bob.testnet", 200)],
+        );
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        assert_eq!(dao_calls.len(), 1);
+        // the proposal of adding alice may be added already, so removing her needs
+        // to be applied as well
+        toggle_exclusion_of(&mut contract, "alice.testnet");
+        assert!(matches!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalSubmitting(_))
+        ));
+        assert_eq!(
+            states_of_histories(&contract)[1..],
+            [
+                Some(CouncilChangeHistoryState::WaitingForApplying),
+                Some(CouncilChangeHistoryState::WaitingForApplying),
+            ]
+        );
+        assert_eq!(
+            contract.previous_pending_history_indexes.get(&2),
+            Some((0, false))
+        );
+    }
+
+    #[test]
+    fn test_apply_change_histories_to_multiple_dao_targets() {
+        let mut contract = new_contract(2);
+        add_direct_dao_target(&mut contract, GRANTS_DAO_ACCOUNT);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[("alice.testnet", 300), ("bob.testnet", 200)],
+        );
+        // the proposals and the direct calls are sent in one batch
+        let (result, dao_calls) = apply_change_histories(&mut contract);
+        assert!(result.is_ok());
+        assert_eq!(dao_calls.len(), 4);
+        let function_calls = sent_function_calls();
+        for (method_name, receiver_id, deposit) in [
+            ("add_proposal", DAO_CONTRACT_ACCOUNT, NEAR),
+            ("add_member", GRANTS_DAO_ACCOUNT, 0),
+        ] {
+            let calls: Vec<&SentFunctionCall> = function_calls
+                .iter()
+                .filter(|function_call| function_call.method_name.eq(method_name))
+                .collect();
+            assert_eq!(calls.len(), 2);
+            assert!(calls.iter().all(|function_call| {
+                function_call.receiver_id.eq(&account(receiver_id))
+                    && function_call.deposit == deposit
+            }));
+        }
+        for index in 0..2 {
+            for dao_contract_account in [DAO_CONTRACT_ACCOUNT, GRANTS_DAO_ACCOUNT] {
+                assert_eq!(
+                    state_of(&contract, index, dao_contract_account),
+                    Some(CouncilChangeHistoryState::ProposalSubmitting(
+                        env::block_timestamp()
+                    ))
                 );
             }
         }
+        // the results are resolved in one callback,
+        // and the added proposals are voted in one batch
+        let results = dao_calls
+            .iter()
+            .map(|dao_call| {
+                match dao_call
+                    .dao_contract_account
+                    .eq(&account(DAO_CONTRACT_ACCOUNT))
+                {
+                    true => proposal_id_result(10 + dao_call.index.0),
+                    false => PromiseResult::Successful(Vec::new()),
+                }
+            })
+            .collect();
+        let act_dao_calls = resolve_add_proposals(&mut contract, &dao_calls, results);
+        assert_eq!(act_dao_calls.len(), 2);
+        assert_eq!(
+            sent_function_calls()
+                .iter()
+                .filter(|function_call| function_call.method_name.eq("act_proposal"))
+                .count(),
+            2
+        );
+        for index in 0..2 {
+            assert_eq!(
+                state_of(&contract, index, DAO_CONTRACT_ACCOUNT),
+                Some(CouncilChangeHistoryState::ProposalAdded(10 + index))
+            );
+            assert_eq!(
+                state_of(&contract, index, GRANTS_DAO_ACCOUNT),
+                Some(CouncilChangeHistoryState::AppliedDirectly)
+            );
+        }
+        assert_eq!(contract.locked_proposal_bonds, 2 * NEAR);
+        //
+        resolve_act_proposals(
+            &mut contract,
+            &act_dao_calls,
+            vec![
+                PromiseResult::Successful(Vec::new()),
+                PromiseResult::Successful(Vec::new()),
+            ],
+        );
+        for index in 0..2 {
+            assert_eq!(
+                state_of(&contract, index, DAO_CONTRACT_ACCOUNT),
+                Some(CouncilChangeHistoryState::ProposalApproved(10 + index))
+            );
+            assert!(contract.change_histories.get(&index).unwrap().is_applied());
+        }
+        assert_eq!(contract.locked_proposal_bonds, 0);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The DAO contract 'octopus-dao.sputnikv2.testnet' has pending proposals."
+    )]
+    fn test_adapter_of_dao_target_with_pending_proposals_can_not_be_changed() {
+        let mut contract = new_contract(2);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("alice.testnet", 300)]);
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        resolve_add_proposals(&mut contract, &dao_calls, vec![proposal_id_result(10)]);
+        // the role name can be changed while the proposal is pending
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.set_dao_target(
+            account(DAO_CONTRACT_ACCOUNT),
+            "members".to_string(),
+            DaoAdapterKind::SputnikV2,
+        );
+        assert_eq!(
+            contract
+                .dao_targets
+                .get(&account(DAO_CONTRACT_ACCOUNT))
+                .unwrap()
+                .role_name,
+            "members"
+        );
+        add_direct_dao_target(&mut contract, DAO_CONTRACT_ACCOUNT);
+    }
+
+    #[test]
+    fn test_dao_calls_are_sent_in_limited_batches() {
+        let mut contract = new_contract(7);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 0);
+        let stakes: Vec<(String, u128)> = (0..7)
+            .map(|index| (format!("validator{}.testnet", index), 100 + index))
+            .collect();
+        let stakes: Vec<(&str, u128)> = stakes
+            .iter()
+            .map(|(validator_id, stake)| (validator_id.as_str(), *stake))
+            .collect();
+        sync_and_update_stakes(&mut contract, "appchain1", &stakes);
+        let (result, dao_calls) = apply_change_histories(&mut contract);
+        assert!(result.is_need_more_gas());
+        assert_eq!(dao_calls.len(), MAX_DAO_CALLS_IN_BATCH);
+        // the change histories in flight are skipped
+        let (result, dao_calls) = apply_change_histories(&mut contract);
+        assert!(result.is_ok());
+        assert_eq!(
+            dao_calls
+                .iter()
+                .map(|dao_call| dao_call.index.0)
+                .collect::<Vec<u64>>(),
+            vec![5, 6]
+        );
+        let (result, dao_calls) = apply_change_histories(&mut contract);
+        assert!(result.is_ok());
+        assert!(dao_calls.is_empty());
+        assert!(sent_function_calls().is_empty());
+    }
+
+    #[test]
+    fn test_failed_submission_is_waiting_for_applying_again() {
+        let mut contract = new_contract(2);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[("alice.testnet", 300), ("bob.testnet", 200)],
+        );
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        resolve_add_proposals(
+            &mut contract,
+            &dao_calls,
+            vec![PromiseResult::Failed, proposal_id_result(1)],
+        );
+        assert_eq!(
+            states_of_histories(&contract),
+            vec![
+                Some(CouncilChangeHistoryState::WaitingForApplying),
+                Some(CouncilChangeHistoryState::ProposalAdded(1)),
+            ]
+        );
+        // the bond of the failed submission is returned with the failed receipt
+        assert_eq!(contract.locked_proposal_bonds, NEAR);
+        // the failed one is submitted again, and the added one is voted again
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        assert_eq!(dao_calls.len(), 1);
+        assert_eq!(dao_calls[0].index, U64(0));
+        assert_eq!(dao_calls_of("resolve_act_proposals").len(), 1);
+    }
+
+    #[test]
+    fn test_proposal_submission_times_out() {
+        let mut contract = new_contract(2);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 0);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[("alice.testnet", 300), ("bob.testnet", 200)],
+        );
+        let (_, lost_dao_calls) = apply_change_histories(&mut contract);
+        assert_eq!(lost_dao_calls.len(), 2);
+        // the callback is lost, the proposals are not submitted again before the timeout
+        advance_time(PROPOSAL_SUBMITTING_TIMEOUT_IN_NANOS);
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        assert!(dao_calls.is_empty());
+        advance_time(1);
+        let (result, dao_calls) = apply_change_histories(&mut contract);
+        assert!(result.is_ok());
+        assert_eq!(dao_calls.len(), 2);
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains("\"event\":\"proposal_submission_timed_out\"")));
+        for dao_call in &dao_calls {
+            assert_eq!(
+                dao_call.expected_state,
+                CouncilChangeHistoryState::ProposalSubmitting(env::block_timestamp())
+            );
+        }
+        // the late callback of the timed out submission is ignored
+        let act_dao_calls = resolve_add_proposals(
+            &mut contract,
+            &lost_dao_calls,
+            vec![proposal_id_result(1), proposal_id_result(2)],
+        );
+        assert!(act_dao_calls.is_empty());
+        for dao_call in &dao_calls {
+            assert_eq!(
+                state_of(&contract, dao_call.index.0, DAO_CONTRACT_ACCOUNT),
+                Some(dao_call.expected_state.clone())
+            );
+        }
+        let act_dao_calls = resolve_add_proposals(
+            &mut contract,
+            &dao_calls,
+            vec![proposal_id_result(3), proposal_id_result(4)],
+        );
+        assert_eq!(act_dao_calls.len(), 2);
+        assert_eq!(
+            states_of_histories(&contract),
+            vec![
+                Some(CouncilChangeHistoryState::ProposalAdded(3)),
+                Some(CouncilChangeHistoryState::ProposalAdded(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_callbacks_ignore_results_of_changed_change_histories() {
+        let mut contract = new_contract(1);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 0);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("alice.testnet", 300)]);
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        let act_dao_calls =
+            resolve_add_proposals(&mut contract, &dao_calls, vec![proposal_id_result(1)]);
+        assert_eq!(act_dao_calls.len(), 1);
+        // the result for a change history which is no longer in the expected state is ignored
+        let ignored_act_dao_calls =
+            resolve_add_proposals(&mut contract, &dao_calls, vec![proposal_id_result(2)]);
+        assert!(ignored_act_dao_calls.is_empty());
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalAdded(1))
+        );
+        // a failed vote keeps the proposal to be voted again
+        resolve_act_proposals(&mut contract, &act_dao_calls, vec![PromiseResult::Failed]);
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalAdded(1))
+        );
+        resolve_act_proposals(
+            &mut contract,
+            &act_dao_calls,
+            vec![PromiseResult::Successful(Vec::new())],
+        );
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalApproved(1))
+        );
+        resolve_act_proposals(
+            &mut contract,
+            &act_dao_calls,
+            vec![PromiseResult::Successful(Vec::new())],
+        );
+        assert!(!get_logs()
+            .iter()
+            .any(|log| log.contains("\"event\":\"proposal_approved\"")));
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalApproved(1))
+        );
     }
 }
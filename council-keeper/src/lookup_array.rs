@@ -86,11 +86,14 @@ where
             end_index: U64::from(self.end_index),
         }
     }
-    ///
+    /// The index of appended record is always greater than all of the records
+    /// which have been appended, even if they are removed.
     pub fn append(&mut self, record: &mut T) -> T {
-        let index = match self.lookup_map.contains_key(&0) {
+        let index = match self.start_index > self.end_index
+            || self.lookup_map.contains_key(&self.end_index)
+        {
             true => self.end_index + 1,
-            false => 0,
+            false => self.end_index,
         };
         record.set_index(&index);
         self.lookup_map.insert(&index, &record);
@@ -141,8 +144,7 @@ where
             self.start_index = index;
             MultiTxsOperationProcessingResult::NeedMoreGas
         } else {
-            self.start_index = 0;
-            self.end_index = 0;
+            self.start_index = self.end_index + 1;
             MultiTxsOperationProcessingResult::Ok
         }
     }
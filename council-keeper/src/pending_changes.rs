@@ -0,0 +1,335 @@
+use crate::*;
+use types::{CouncilMemberRecord, CouncilSimulation, CrankTransactionsEstimate, PendingRankUpdate};
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Get the number of validators whose stake is synced but not ranked yet.
+    pub fn get_number_of_validators_waiting_to_update_rank(&self) -> U64 {
+        U64::from(self.validators_waiting_to_update_rank.len())
+    }
+    /// Get the validators waiting to update rank, in the order of the queue, with pagination.
+    pub fn get_validators_waiting_to_update_rank(
+        &self,
+        start_index: u32,
+        quantity: Option<u32>,
+    ) -> Vec<PendingRankUpdate> {
+        let validator_ids = self.validators_waiting_to_update_rank.as_vector();
+        let end_index = match quantity {
+            Some(quantity) => validator_ids
+                .len()
+                .min(u64::from(start_index) + u64::from(quantity)),
+            None => validator_ids.len(),
+        };
+        (u64::from(start_index)..end_index)
+            .filter_map(|index| validator_ids.get(index))
+            .filter_map(|validator_id| self.get_validator_stake(&validator_id))
+            .map(|validator_stake| PendingRankUpdate {
+                current_rank: self.ranked_rank_of(&validator_stake),
+                validator_id: validator_stake.validator_id,
+                total_stake: validator_stake.total_stake,
+            })
+            .collect()
+    }
+    /// Get the council which will be selected once the validators waiting to update rank
+    /// are re-ranked, without changing the state.
+    ///
+    /// The validators with the same total stake may be ordered differently from
+    /// the actual re-ranking. The `is_ranking_up_to_date` is `false` while the ranking
+    /// is being rebuilt, in which case the projection is based on the incomplete ranking.
+    pub fn get_projected_council(&self) -> CouncilSimulation {
+        let waiting_ids: HashSet<AccountId> =
+            self.validators_waiting_to_update_rank.iter().collect();
+        // the sorting is stable, the waiting validators with the same total stake
+        // keep the order of the queue
+        let mut waiting_validators: Vec<(AccountId, u128)> = self
+            .validators_waiting_to_update_rank
+            .iter()
+            .filter_map(|validator_id| self.get_validator_stake(&validator_id))
+            .map(|validator_stake| (validator_stake.validator_id, validator_stake.total_stake.0))
+            .collect();
+        waiting_validators.sort_by_key(|validator| std::cmp::Reverse(validator.1));
+        // the ranked validators which are not waiting keep their order,
+        // so the waiting ones only need to be merged into them by total stake
+        let ranked_validators = (0..self.ranked_validators.len())
+            .filter_map(|index| self.ranked_validators.get(index))
+            .filter(|validator_id| !waiting_ids.contains(validator_id))
+            .filter_map(|validator_id| self.get_validator_stake(&validator_id))
+            .map(|validator_stake| (validator_stake.validator_id, validator_stake.total_stake.0));
+        let members: Vec<CouncilMemberRecord> = council_core::select_council_members(
+            council_core::merge_by_rank_value(ranked_validators, waiting_validators)
+                .enumerate()
+                .map(|(rank, (account_id, total_stake))| CouncilMemberRecord {
+                    account_id,
+                    total_stake: U128::from(total_stake),
+                    overall_rank: rank as u32,
+                }),
+            self.max_number_of_council_members,
+            |member| {
+                self.excluding_validator_accounts
+                    .contains(&member.account_id)
+            },
+        );
+        let council_diff = self.diff_with_latest_members(
+            &members
+                .iter()
+                .map(|member| member.account_id.clone())
+                .collect::<Vec<AccountId>>(),
+        );
+        CouncilSimulation {
+            members,
+            members_to_add: council_diff.added,
+            members_to_remove: council_diff.removed,
+            is_ranking_up_to_date: self.rank_rebuilding.is_none(),
+        }
+    }
+    /// Estimate the transactions needed by `update_council_change_histories` and
+    /// `apply_change_histories_to_dao_contract` to process the pending changes.
+    ///
+    /// The change histories are scanned from `start_index`, which should be the same as
+    /// the one passed to `apply_change_histories_to_dao_contract`. The estimates are rough,
+    /// as the actual gas used by each transaction depends on the state.
+    pub fn estimate_crank_transactions(&self, start_index: U64) -> CrankTransactionsEstimate {
+        let waiting_count = self.validators_waiting_to_update_rank.len();
+        let is_rebuilding_ranks_in_bulk =
+            self.rank_rebuilding.is_some() || self.should_rebuild_ranks_in_bulk();
+        // the last call generates the change histories when no validator is waiting
+        let update_transactions = match is_rebuilding_ranks_in_bulk {
+            true => self.estimate_transactions_of_rebuilding_ranks(),
+            false => waiting_count.div_ceil(events::MAX_ENTRIES_IN_EVENT_DATA as u64) as u32,
+        } + 1;
+        //
+        let dao_targets = self.dao_targets.values_as_vector().to_vec();
+        let index_range = self.change_histories.index_range();
+        let mut submissions_waiting_for_applying = 0;
+        let mut proposals_waiting_for_votes = 0;
+        let mut index = start_index.0;
+        while index <= index_range.end_index.0
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+        {
+            if let Some(change_history) = self.change_histories.get(&index) {
+                for dao_target in &dao_targets {
+                    match change_history
+                        .target_states
+                        .get(&dao_target.dao_contract_account)
+                    {
+                        Some(CouncilChangeHistoryState::WaitingForApplying) => {
+                            submissions_waiting_for_applying += 1
+                        }
+                        Some(CouncilChangeHistoryState::ProposalSubmitting(submitted_at))
+                            if env::block_timestamp()
+                                > submitted_at + PROPOSAL_SUBMITTING_TIMEOUT_IN_NANOS =>
+                        {
+                            submissions_waiting_for_applying += 1
+                        }
+                        Some(CouncilChangeHistoryState::ProposalAdded(_)) => {
+                            proposals_waiting_for_votes += 1
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            index += 1;
+        }
+        // the changes may cancel out the pending ones, so this is an upper bound
+        let projected_change_histories = match waiting_count > 0 {
+            true => {
+                let projected_council = self.get_projected_council();
+                (projected_council.members_to_add.len() + projected_council.members_to_remove.len())
+                    as u64
+            }
+            false => 0,
+        };
+        // the added proposals are voted in the callback of submitting,
+        // so the submissions and the votes of existing proposals are processed in parallel
+        let apply_transactions = (submissions_waiting_for_applying
+            + projected_change_histories * dao_targets.len() as u64)
+            .max(proposals_waiting_for_votes)
+            .div_ceil(MAX_DAO_CALLS_IN_BATCH as u64) as u32;
+        CrankTransactionsEstimate {
+            validators_waiting_to_update_rank: U64::from(waiting_count),
+            is_rebuilding_ranks_in_bulk,
+            update_transactions,
+            submissions_waiting_for_applying,
+            proposals_waiting_for_votes,
+            projected_change_histories,
+            apply_transactions,
+            next_scan_index: match index <= index_range.end_index.0 {
+                true => Some(U64::from(index)),
+                false => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+    use types::CouncilChangeAction;
+
+    // Assert that the projected council is the same as the council selected
+    // by actually updating the change histories.
+    fn assert_projected_council_is_updated(contract: &mut CouncilKeeper) {
+        let projected_council = contract.get_projected_council();
+        assert!(projected_council.is_ranking_up_to_date);
+        let start_index = contract.next_change_history_index;
+        update_change_histories(contract);
+        let simulation = contract.simulate_council(None, None, None);
+        assert_eq!(projected_council.members, simulation.members);
+        let mut projected_members: Vec<AccountId> = projected_council
+            .members
+            .iter()
+            .map(|member| member.account_id.clone())
+            .collect();
+        projected_members.sort();
+        assert_eq!(council_members_of(contract), projected_members);
+        // a change history is generated for each of the projected changes
+        let mut added_members = Vec::new();
+        let mut removed_members = Vec::new();
+        for index in start_index..contract.next_change_history_index {
+            match contract.change_histories.get(&index).unwrap().action {
+                CouncilChangeAction::MemberAdded(account_id) => added_members.push(account_id),
+                CouncilChangeAction::MemberRemoved(account_id) => removed_members.push(account_id),
+            }
+        }
+        for (mut projected, mut actual) in [
+            (projected_council.members_to_add, added_members),
+            (projected_council.members_to_remove, removed_members),
+        ] {
+            projected.sort();
+            actual.sort();
+            assert_eq!(projected, actual);
+        }
+    }
+
+    #[test]
+    fn test_projected_council_is_the_updated_council() {
+        let mut contract = new_contract(3);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+                ("dave.testnet", 50),
+            ],
+        );
+        sync_stakes(
+            &mut contract,
+            "appchain1",
+            &[("bob.testnet", 90), ("dave.testnet", 350)],
+        );
+        sync_stakes(&mut contract, "appchain2", &[("eve.testnet", 250)]);
+        let projected_council = contract.get_projected_council();
+        assert_eq!(
+            projected_council
+                .members
+                .iter()
+                .map(|member| (member.account_id.clone(), member.overall_rank))
+                .collect::<Vec<(AccountId, u32)>>(),
+            vec![
+                (account("dave.testnet"), 0),
+                (account("alice.testnet"), 1),
+                (account("eve.testnet"), 2),
+            ]
+        );
+        assert_projected_council_is_updated(&mut contract);
+        // the excluded validator is skipped in the projection as well
+        set_context("dave.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        sync_stakes(&mut contract, "appchain2", &[("charlie.testnet", 300)]);
+        assert_projected_council_is_updated(&mut contract);
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "charlie.testnet", "eve.testnet"])
+        );
+    }
+
+    #[test]
+    fn test_projected_council_is_the_council_rebuilt_in_bulk() {
+        let mut contract = new_contract(5);
+        let validator_ids: Vec<String> = (0..30)
+            .map(|index| format!("validator{}.testnet", index))
+            .collect();
+        let stakes: Vec<(&str, u128)> = validator_ids
+            .iter()
+            .zip(1..)
+            .map(|(validator_id, stake)| (validator_id.as_str(), stake * 10))
+            .collect();
+        sync_and_update_stakes(&mut contract, "appchain1", &stakes);
+        // the stakes of most validators are reversed
+        let stakes: Vec<(&str, u128)> = validator_ids
+            .iter()
+            .zip((1..31).rev())
+            .map(|(validator_id, stake)| (validator_id.as_str(), stake * 10 + 5))
+            .collect();
+        sync_stakes(&mut contract, "appchain1", &stakes);
+        assert!(contract.should_rebuild_ranks_in_bulk());
+        assert_projected_council_is_updated(&mut contract);
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&[
+                "validator0.testnet",
+                "validator1.testnet",
+                "validator2.testnet",
+                "validator3.testnet",
+                "validator4.testnet",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_estimate_crank_transactions_with_pending_change_histories() {
+        let mut contract = new_contract(2);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[("alice.testnet", 300), ("bob.testnet", 200)],
+        );
+        let estimate = contract.estimate_crank_transactions(U64(0));
+        assert_eq!(estimate.validators_waiting_to_update_rank, U64(0));
+        assert!(!estimate.is_rebuilding_ranks_in_bulk);
+        assert_eq!(estimate.update_transactions, 1);
+        assert_eq!(estimate.submissions_waiting_for_applying, 2);
+        assert_eq!(estimate.proposals_waiting_for_votes, 0);
+        assert_eq!(estimate.projected_change_histories, 0);
+        assert_eq!(estimate.apply_transactions, 1);
+        assert!(estimate.next_scan_index.is_none());
+        // the submissions in flight are not counted until they time out
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        let estimate = contract.estimate_crank_transactions(U64(0));
+        assert_eq!(estimate.submissions_waiting_for_applying, 0);
+        assert_eq!(estimate.apply_transactions, 0);
+        advance_time(PROPOSAL_SUBMITTING_TIMEOUT_IN_NANOS + 1);
+        let estimate = contract.estimate_crank_transactions(U64(0));
+        assert_eq!(estimate.submissions_waiting_for_applying, 2);
+        //
+        resolve_add_proposals(
+            &mut contract,
+            &dao_calls,
+            vec![proposal_id_result(1), proposal_id_result(2)],
+        );
+        assert_eq!(
+            state_of(&contract, 1, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalAdded(2))
+        );
+        // the new change histories are waiting for applying,
+        // while the pending rank update will generate more of them
+        sync_and_update_stakes(&mut contract, "appchain2", &[("charlie.testnet", 500)]);
+        sync_stakes(&mut contract, "appchain2", &[("dave.testnet", 400)]);
+        let estimate = contract.estimate_crank_transactions(U64(0));
+        assert_eq!(estimate.validators_waiting_to_update_rank, U64(1));
+        assert!(!estimate.is_rebuilding_ranks_in_bulk);
+        assert_eq!(estimate.update_transactions, 2);
+        assert_eq!(estimate.submissions_waiting_for_applying, 2);
+        assert_eq!(estimate.proposals_waiting_for_votes, 2);
+        assert_eq!(estimate.projected_change_histories, 2);
+        assert_eq!(estimate.apply_transactions, 1);
+        // the change histories before the start index are not scanned
+        let estimate = contract.estimate_crank_transactions(U64(2));
+        assert_eq!(estimate.submissions_waiting_for_applying, 2);
+        assert_eq!(estimate.proposals_waiting_for_votes, 0);
+    }
+}
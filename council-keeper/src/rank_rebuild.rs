@@ -0,0 +1,456 @@
+use crate::*;
+use council_core::{MergeCursor, MergeStep};
+use events::MAX_ENTRIES_IN_EVENT_DATA;
+use types::{CouncilKeeperEvent, RankChangedEventData};
+
+/// The minimum number of validators waiting to update rank, to rebuild the ranking in bulk.
+pub(crate) const MIN_WAITING_VALIDATORS_FOR_BULK_REBUILD: u64 = 20;
+/// The percentage of waiting validators in ranked validators, above which the ranking
+/// is rebuilt in bulk rather than updated validator by validator.
+pub(crate) const BULK_REBUILD_THRESHOLD_PERCENT: u64 = 30;
+/// The rough number of collecting or merging steps which can be processed in one transaction,
+/// only used for estimating the transactions needed by the rebuilding.
+const ESTIMATED_REBUILDING_STEPS_PER_TRANSACTION: u64 = 200;
+
+/// The progress of rebuilding the ranking of validators, which may take multiple transactions.
+/// The validators with their total stake are copied to a buffer, sorted by the bottom-up
+/// merge sort of `council_core` between two buffers, then written back to `ranked_validators`
+/// in one pass.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy)]
+pub enum RankRebuildingState {
+    /// Draining the validators waiting to update rank, and copying the ranked validators.
+    /// The ranked validators are copied to `[0, ranked_count)` of the first buffer,
+    /// and the new validators are copied after them.
+    Collecting {
+        ranked_count: u32,
+        unranked_count: u32,
+        next_ranked_index: u32,
+    },
+    /// Merging the sorted runs of `width` in the `source` buffer to the other buffer.
+    Sorting {
+        count: u32,
+        width: u32,
+        source: u8,
+        run_start: u32,
+        left: u32,
+        right: u32,
+        out: u32,
+    },
+    /// Writing the sorted validators in the `source` buffer back to `ranked_validators`.
+    Writing {
+        count: u32,
+        source: u8,
+        next_index: u32,
+    },
+}
+
+/// The id of the first buffer for rebuilding the ranking of validators.
+/// The second buffer of each rebuilding is next to the first one.
+pub(crate) const OVERALL_RANK_BUFFER_BASE: u8 = 0;
+/// The id of the first buffer for rebuilding the ranking of an appchain.
+pub(crate) const APPCHAIN_RANK_BUFFER_BASE: u8 = 2;
+
+pub(crate) fn rank_rebuilding_buffer(id: u8) -> LookupMap<u32, (AccountId, U128)> {
+    LookupMap::new(StorageKey::RankRebuildingBuffer(id))
+}
+
+/// The buffer indexes of the validators which were not ranked when they were collected
+/// by the rebuilding with the first buffer `id`. They are removed when written back.
+pub(crate) fn collected_unranked_validators(id: u8) -> LookupMap<AccountId, u32> {
+    LookupMap::new(StorageKey::RankRebuildingCollectedValidators(id))
+}
+
+impl CouncilKeeper {
+    // Whether a large share of validators is waiting to update rank,
+    // in which case rebuilding the ranking is cheaper than updating them one by one.
+    pub(crate) fn should_rebuild_ranks_in_bulk(&self) -> bool {
+        let waiting_count = self.validators_waiting_to_update_rank.len();
+        waiting_count >= MIN_WAITING_VALIDATORS_FOR_BULK_REBUILD
+            && waiting_count * 100
+                >= u64::from(self.ranked_validators.len()) * BULK_REBUILD_THRESHOLD_PERCENT
+    }
+    // A rough estimate of the transactions needed to finish the rebuilding, or to do
+    // a whole rebuilding if it is not started. All validators are assumed to change rank
+    // in the writing, so that the number of them in each transaction is capped by events.
+    pub(crate) fn estimate_transactions_of_rebuilding_ranks(&self) -> u32 {
+        let waiting_count = self.validators_waiting_to_update_rank.len();
+        let (collecting_steps, count, width, merged_in_pass) = match self.rank_rebuilding {
+            None => {
+                let count = u64::from(self.ranked_validators.len()) + waiting_count;
+                (count, count, 1, 0)
+            }
+            Some(RankRebuildingState::Collecting {
+                ranked_count,
+                unranked_count,
+                next_ranked_index,
+            }) => (
+                u64::from(ranked_count - next_ranked_index) + waiting_count,
+                u64::from(ranked_count + unranked_count) + waiting_count,
+                1,
+                0,
+            ),
+            Some(RankRebuildingState::Sorting {
+                count, width, out, ..
+            }) => (0, u64::from(count), u64::from(width), u64::from(out)),
+            Some(RankRebuildingState::Writing {
+                count, next_index, ..
+            }) => {
+                return u64::from(count.saturating_sub(next_index))
+                    .div_ceil(MAX_ENTRIES_IN_EVENT_DATA as u64)
+                    .max(1) as u32
+            }
+        };
+        let mut merging_steps = 0;
+        let mut width = width;
+        while width < count {
+            merging_steps += count;
+            width *= 2;
+        }
+        ((collecting_steps + merging_steps.saturating_sub(merged_in_pass))
+            .div_ceil(ESTIMATED_REBUILDING_STEPS_PER_TRANSACTION)
+            + count.div_ceil(MAX_ENTRIES_IN_EVENT_DATA as u64))
+        .max(1) as u32
+    }
+    //
+    pub(crate) fn start_rebuilding_ranks(&mut self) {
+        assert!(
+            self.rank_rebuilding.is_none(),
+            "The ranking of validators is already being rebuilt."
+        );
+        self.rank_rebuilding = Some(RankRebuildingState::Collecting {
+            ranked_count: self.ranked_validators.len(),
+            unranked_count: 0,
+            next_ranked_index: 0,
+        });
+        log!(
+            "Start rebuilding the ranking of validators, with {} validators waiting to update rank.",
+            self.validators_waiting_to_update_rank.len()
+        );
+    }
+    // Continue rebuilding the ranking until it is finished or the gas is running out.
+    pub(crate) fn continue_rebuilding_ranks(&mut self) -> MultiTxsOperationProcessingResult {
+        let mut state = match self.rank_rebuilding {
+            Some(state) => state,
+            None => return MultiTxsOperationProcessingResult::Ok,
+        };
+        let mut changed_ranks = Vec::new();
+        while env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+            && changed_ranks.len() < MAX_ENTRIES_IN_EVENT_DATA
+        {
+            state = match state {
+                RankRebuildingState::Collecting {
+                    ranked_count,
+                    unranked_count,
+                    next_ranked_index,
+                } => self.collect_validator_for_rebuilding(
+                    ranked_count,
+                    unranked_count,
+                    next_ranked_index,
+                ),
+                RankRebuildingState::Sorting {
+                    count,
+                    width,
+                    source,
+                    run_start,
+                    left,
+                    right,
+                    out,
+                } => merge_validator_for_rebuilding(
+                    OVERALL_RANK_BUFFER_BASE,
+                    MergeCursor {
+                        count,
+                        width,
+                        source,
+                        run_start,
+                        left,
+                        right,
+                        out,
+                    },
+                ),
+                RankRebuildingState::Writing {
+                    count,
+                    source,
+                    next_index,
+                } => {
+                    if next_index >= count {
+                        self.rank_rebuilding = None;
+                        emit_rank_changed_event(changed_ranks);
+                        log!(
+                            "The ranking of {} validators has been rebuilt.",
+                            self.ranked_validators.len()
+                        );
+                        return MultiTxsOperationProcessingResult::Ok;
+                    }
+                    if let Some(event_data) =
+                        self.write_back_validator_of_rebuilding(source, next_index)
+                    {
+                        changed_ranks.push(event_data);
+                    }
+                    RankRebuildingState::Writing {
+                        count,
+                        source,
+                        next_index: next_index + 1,
+                    }
+                }
+            };
+        }
+        self.rank_rebuilding = Some(state);
+        emit_rank_changed_event(changed_ranks);
+        MultiTxsOperationProcessingResult::NeedMoreGas
+    }
+    // The validators waiting to update rank are drained before copying each ranked validator,
+    // so that the latest stake of them is in the buffer. The validators whose stake is changed
+    // after the collecting will be waiting to update rank again, and will be updated
+    // one by one after the rebuilding. A validator synced again during the collecting is
+    // copied to the same index of the buffer, so that it is not collected twice.
+    fn collect_validator_for_rebuilding(
+        &mut self,
+        ranked_count: u32,
+        unranked_count: u32,
+        next_ranked_index: u32,
+    ) -> RankRebuildingState {
+        let mut buffer = rank_rebuilding_buffer(OVERALL_RANK_BUFFER_BASE);
+        let waiting_count = self.validators_waiting_to_update_rank.len();
+        if waiting_count > 0 {
+            let validator_id = self
+                .validators_waiting_to_update_rank
+                .as_vector()
+                .get(waiting_count - 1)
+                .unwrap();
+            self.validators_waiting_to_update_rank.remove(&validator_id);
+            let validator_stake = self.validator_stakes.get(&validator_id).unwrap();
+            let is_ranked = self.ranked_validators.get(validator_stake.overall_rank)
+                == Some(validator_id.clone());
+            let mut collected_validators = collected_unranked_validators(OVERALL_RANK_BUFFER_BASE);
+            let (buffer_index, unranked_count) = match is_ranked {
+                true => (validator_stake.overall_rank, unranked_count),
+                false => match collected_validators.get(&validator_id) {
+                    Some(buffer_index) => (buffer_index, unranked_count),
+                    None => {
+                        let buffer_index = ranked_count + unranked_count;
+                        collected_validators.insert(&validator_id, &buffer_index);
+                        (buffer_index, unranked_count + 1)
+                    }
+                },
+            };
+            buffer.insert(&buffer_index, &(validator_id, validator_stake.total_stake));
+            return RankRebuildingState::Collecting {
+                ranked_count,
+                unranked_count,
+                next_ranked_index,
+            };
+        }
+        if next_ranked_index < ranked_count {
+            let validator_id = self.ranked_validators.get(next_ranked_index).unwrap();
+            let validator_stake = self.validator_stakes.get(&validator_id).unwrap();
+            buffer.insert(
+                &next_ranked_index,
+                &(validator_id, validator_stake.total_stake),
+            );
+            return RankRebuildingState::Collecting {
+                ranked_count,
+                unranked_count,
+                next_ranked_index: next_ranked_index + 1,
+            };
+        }
+        let cursor = MergeCursor::new(ranked_count + unranked_count);
+        RankRebuildingState::Sorting {
+            count: cursor.count,
+            width: cursor.width,
+            source: cursor.source,
+            run_start: cursor.run_start,
+            left: cursor.left,
+            right: cursor.right,
+            out: cursor.out,
+        }
+    }
+    // Return the data of event if the rank of the validator is changed.
+    fn write_back_validator_of_rebuilding(
+        &mut self,
+        source: u8,
+        index: u32,
+    ) -> Option<RankChangedEventData> {
+        let (validator_id, total_stake) = rank_rebuilding_buffer(OVERALL_RANK_BUFFER_BASE + source)
+            .remove(&index)
+            .unwrap();
+        rank_rebuilding_buffer(OVERALL_RANK_BUFFER_BASE + 1 - source).remove(&index);
+        collected_unranked_validators(OVERALL_RANK_BUFFER_BASE).remove(&validator_id);
+        // the validators before the index have been written back,
+        // so the previous rank can only be found at or after the index
+        let validator_stake = self.validator_stakes.get(&validator_id).unwrap();
+        let previous_rank = match validator_stake.overall_rank >= index {
+            true => self.ranked_rank_of(&validator_stake),
+            false => None,
+        };
+        self.ranked_validators
+            .rewrite(index, &validator_id, &mut self.validator_stakes);
+        self.fill_rank_of_stake_history(&validator_id, index);
+        match previous_rank == Some(index) {
+            true => None,
+            false => Some(RankChangedEventData {
+                validator_id,
+                total_stake,
+                previous_rank,
+                rank: index,
+            }),
+        }
+    }
+}
+
+//
+fn emit_rank_changed_event(changed_ranks: Vec<RankChangedEventData>) {
+    if !changed_ranks.is_empty() {
+        CouncilKeeperEvent::RankChanged(changed_ranks).emit();
+    }
+}
+
+// Move one validator of the merging runs to the other buffer, by the merge sort of `council_core`.
+// The buffers of the rebuilding are the ones with id `buffer_base` and `buffer_base + 1`.
+pub(crate) fn merge_validator_for_rebuilding(
+    buffer_base: u8,
+    cursor: MergeCursor,
+) -> RankRebuildingState {
+    let count = cursor.count;
+    let source = cursor.source;
+    let source_buffer = rank_rebuilding_buffer(buffer_base + source);
+    let next =
+        match council_core::merge_step(cursor, |index| source_buffer.get(&index).unwrap().1 .0) {
+            MergeStep::Finished { source } => {
+                return RankRebuildingState::Writing {
+                    count,
+                    source,
+                    next_index: 0,
+                }
+            }
+            MergeStep::Continue(next) => next,
+            MergeStep::Move { from, to, next } => {
+                rank_rebuilding_buffer(buffer_base + 1 - source)
+                    .insert(&to, &source_buffer.get(&from).unwrap());
+                next
+            }
+        };
+    RankRebuildingState::Sorting {
+        count: next.count,
+        width: next.width,
+        source: next.source,
+        run_start: next.run_start,
+        left: next.left,
+        right: next.right,
+        out: next.out,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn test_rebuild_ranks_in_bulk() {
+        let mut contract = new_contract(3);
+        // the stakes are synced in an order different from the ranking
+        let stakes: Vec<(String, u128)> = (0..30)
+            .map(|index| {
+                (
+                    format!("validator{}.testnet", index),
+                    100 + (index * 7) % 30,
+                )
+            })
+            .collect();
+        let stakes: Vec<(&str, u128)> = stakes
+            .iter()
+            .map(|(validator_id, stake)| (validator_id.as_str(), *stake))
+            .collect();
+        sync_stakes(&mut contract, "appchain1", &stakes);
+        assert!(contract.should_rebuild_ranks_in_bulk());
+        assert!(contract.estimate_transactions_of_rebuilding_ranks() > 0);
+        update_change_histories(&mut contract);
+        assert!(contract.rank_rebuilding.is_none());
+        assert!(contract.validators_waiting_to_update_rank.is_empty());
+        let ranked_validators = contract.get_ranked_validator_stakes(0, None);
+        assert_eq!(ranked_validators.len(), 30);
+        for (index, validator_stake) in ranked_validators.iter().enumerate() {
+            assert_eq!(validator_stake.overall_rank, index as u32);
+            assert_eq!(
+                validator_stake.total_stake,
+                U128((129 - index as u128) * NEAR)
+            );
+            // the rank is filled in the stake history when the validator is written back
+            assert_eq!(
+                contract
+                    .get_validator_stake_history(validator_stake.validator_id.clone(), None, None)
+                    .last()
+                    .unwrap()
+                    .overall_rank,
+                Some(index as u32)
+            );
+        }
+        // the buffers are emptied after the rebuilding
+        for buffer_id in [OVERALL_RANK_BUFFER_BASE, OVERALL_RANK_BUFFER_BASE + 1] {
+            assert!((0..30).all(|index| !rank_rebuilding_buffer(buffer_id).contains_key(&index)));
+        }
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&[
+                "validator17.testnet",
+                "validator21.testnet",
+                "validator4.testnet"
+            ])
+        );
+        // a few stake changes are updated validator by validator
+        sync_stakes(&mut contract, "appchain1", &[("validator0.testnet", 1000)]);
+        assert!(!contract.should_rebuild_ranks_in_bulk());
+        update_change_histories(&mut contract);
+        assert_eq!(
+            contract
+                .get_validator_stake_of(account("validator0.testnet"))
+                .overall_rank,
+            0
+        );
+        assert_eq!(
+            contract.get_ranked_validator_stakes(1, Some(1))[0].validator_id,
+            account("validator17.testnet")
+        );
+    }
+
+    #[test]
+    fn test_validator_synced_during_collecting_is_collected_once() {
+        let mut contract = new_contract(3);
+        let stakes: Vec<(String, u128)> = (0..30)
+            .map(|index| (format!("validator{}.testnet", index), 100 + index))
+            .collect();
+        let stakes: Vec<(&str, u128)> = stakes
+            .iter()
+            .map(|(validator_id, stake)| (validator_id.as_str(), *stake))
+            .collect();
+        sync_stakes(&mut contract, "appchain1", &stakes);
+        contract.start_rebuilding_ranks();
+        for _ in 0..5 {
+            if let Some(RankRebuildingState::Collecting {
+                ranked_count,
+                unranked_count,
+                next_ranked_index,
+            }) = contract.rank_rebuilding
+            {
+                contract.rank_rebuilding = Some(contract.collect_validator_for_rebuilding(
+                    ranked_count,
+                    unranked_count,
+                    next_ranked_index,
+                ));
+            }
+        }
+        // the first collected validator is synced again before the collecting is finished
+        let (validator_id, _) = rank_rebuilding_buffer(OVERALL_RANK_BUFFER_BASE)
+            .get(&0)
+            .unwrap();
+        sync_stakes(&mut contract, "appchain1", &[(validator_id.as_str(), 1000)]);
+        update_change_histories(&mut contract);
+        let ranked_validators = contract.get_ranked_validator_stakes(0, None);
+        assert_eq!(ranked_validators.len(), 30);
+        assert_eq!(ranked_validators[0].validator_id, validator_id);
+        assert_eq!(ranked_validators[0].total_stake, U128(1000 * NEAR));
+        assert!(collected_unranked_validators(OVERALL_RANK_BUFFER_BASE)
+            .get(&validator_id)
+            .is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use council_core::RankedStore;
 
 pub trait RankValueHolder<T: BorshDeserialize + BorshSerialize> {
     ///
@@ -30,96 +31,57 @@ where
     pub fn get(&self, index: u32) -> Option<T> {
         self.lookup_map.get(&index)
     }
-    ///
+    /// Get the records in `[start_index, start_index + quantity)`, the range is truncated
+    /// to the end of the array, and the result is empty if `start_index` is out of bound.
     pub fn get_slice_of(&self, start_index: u32, quantity: Option<u32>) -> Vec<T> {
-        let mut results = Vec::<T>::new();
-        assert!(
-            start_index < self.length,
-            "Start index is out of bound of the array."
-        );
         let end_index = match quantity {
-            Some(quantity) => match quantity > self.length - start_index - 1 {
-                true => self.length - 1,
-                false => start_index + quantity - 1,
-            },
-            None => self.length - 1,
+            Some(quantity) => self.length.min(start_index.saturating_add(quantity)),
+            None => self.length,
         };
-        for index in start_index..end_index + 1 {
-            if let Some(record) = self.get(index) {
-                results.push(record);
-            }
-        }
-        results
+        (start_index..end_index)
+            .filter_map(|index| self.get(index))
+            .collect()
     }
-    ///
+    /// Append a record and move it up by its rank value, return the new index of it.
     pub fn append<S: RankValueHolder<T>>(&mut self, record: &T, rank_value_holder: &mut S) -> u32 {
-        self.lookup_map.insert(&self.length, &record);
-        rank_value_holder.update_rank_of(record, self.length);
-        self.length += 1;
-        //
-        self.move_up_by_rank_value(record, self.length - 1, rank_value_holder)
-    }
-    //
-    fn move_up_by_rank_value<S: RankValueHolder<T>>(
-        &mut self,
-        record: &T,
-        index: u32,
-        rank_value_holder: &mut S,
-    ) -> u32 {
-        let mut current_index = index;
-        while current_index > 0 {
-            let previous_index = current_index - 1;
-            let previous_rank_value =
-                rank_value_holder.get_rank_value_of(&self.get(previous_index).unwrap());
-            let current_rank_value = rank_value_holder.get_rank_value_of(record);
-            if current_rank_value <= previous_rank_value {
-                break;
-            } else {
-                self.swap((previous_index, current_index), rank_value_holder);
-                current_index = previous_index;
-            }
-        }
-        current_index
+        council_core::append_by_rank_value(
+            &mut RankedStoreAdapter {
+                array: self,
+                rank_value_holder,
+            },
+            record,
+        )
     }
-    ///
+    /// Write a record at the index and move it by its rank value, return the new index of it.
     pub fn insert<S: RankValueHolder<T>>(
         &mut self,
         index: u32,
         record: &T,
         rank_value_holder: &mut S,
     ) -> u32 {
-        assert!(index < self.length, "Index is out of bound of the array.");
-        self.lookup_map.insert(&index, record);
-        //
-        let mut new_index = self.move_up_by_rank_value(record, index, rank_value_holder);
-        if new_index == index {
-            new_index = self.move_down_by_rank_value(record, index, rank_value_holder);
-        }
-        new_index
+        council_core::reposition_by_rank_value(
+            &mut RankedStoreAdapter {
+                array: self,
+                rank_value_holder,
+            },
+            record,
+            index,
+        )
     }
-    //
-    fn move_down_by_rank_value<S: RankValueHolder<T>>(
+    /// Write a record at the index directly, without moving it by rank value.
+    /// The array is extended if the index is at the end of it.
+    pub fn rewrite<S: RankValueHolder<T>>(
         &mut self,
-        record: &T,
         index: u32,
+        record: &T,
         rank_value_holder: &mut S,
-    ) -> u32 {
-        let mut current_index = index;
-        if self.length > 1 {
-            while current_index < self.length - 1 {
-                let next_index = current_index + 1;
-                let next_rank_value =
-                    rank_value_holder.get_rank_value_of(&self.get(next_index).unwrap());
-                let current_rank_value = rank_value_holder.get_rank_value_of(record);
-                if current_rank_value >= next_rank_value {
-                    break;
-                } else {
-                    self.swap((next_index, current_index), rank_value_holder);
-                    current_index = next_index;
-                }
-            }
+    ) {
+        assert!(index <= self.length, "Index is out of bound of the array.");
+        self.lookup_map.insert(&index, record);
+        rank_value_holder.update_rank_of(record, index);
+        if index == self.length {
+            self.length += 1;
         }
-        current_index
     }
     ///
     pub fn len(&self) -> u32 {
@@ -141,19 +103,33 @@ where
             MultiTxsOperationProcessingResult::Ok
         }
     }
-    ///
-    fn swap<S: RankValueHolder<T>>(&mut self, index_pair: (u32, u32), rank_value_holder: &mut S) {
-        assert!(
-            index_pair.0 < self.length
-                && index_pair.1 < self.length
-                && index_pair.0 != index_pair.1,
-            "Invalid index pair to swap."
-        );
-        let t0 = self.lookup_map.get(&index_pair.0).unwrap();
-        let t1 = self.lookup_map.get(&index_pair.1).unwrap();
-        self.lookup_map.insert(&index_pair.0, &t1);
-        rank_value_holder.update_rank_of(&t1, index_pair.0);
-        self.lookup_map.insert(&index_pair.1, &t0);
-        rank_value_holder.update_rank_of(&t0, index_pair.1);
+}
+
+// The ranking rules of `council_core` over the lookup array and the holder of rank values.
+struct RankedStoreAdapter<'a, T: BorshDeserialize + BorshSerialize, S: RankValueHolder<T>> {
+    array: &'a mut RankedLookupArray<T>,
+    rank_value_holder: &'a mut S,
+}
+
+impl<'a, T, S> RankedStore<T> for RankedStoreAdapter<'a, T, S>
+where
+    T: BorshDeserialize + BorshSerialize,
+    S: RankValueHolder<T>,
+{
+    //
+    fn len(&self) -> u32 {
+        self.array.length
+    }
+    //
+    fn get(&self, index: u32) -> Option<T> {
+        self.array.get(index)
+    }
+    //
+    fn put(&mut self, index: u32, member: &T) {
+        self.array.rewrite(index, member, self.rank_value_holder);
+    }
+    //
+    fn rank_value_of(&self, member: &T) -> u128 {
+        self.rank_value_holder.get_rank_value_of(member)
     }
 }
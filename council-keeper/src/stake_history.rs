@@ -0,0 +1,153 @@
+use crate::*;
+use types::{CouncilKeeperEvent, ValidatorStakeHistoryEntry};
+
+/// The default number of stake history entries kept for each validator.
+pub const DEFAULT_STAKE_HISTORY_RETENTION: u32 = 30;
+/// The max number of stake history entries kept for each validator.
+const MAX_STAKE_HISTORY_RETENTION: u32 = 1000;
+/// The max number of stake history entries of a validator removed when a new entry
+/// is appended, which keeps the cost of syncing stakes bounded after the retention is lowered.
+const MAX_STAKE_HISTORY_REMOVALS_PER_APPEND: u64 = 2;
+/// The max number of stake history entries returned by the view function.
+pub const MAX_STAKE_HISTORY_ENTRIES_IN_VIEW: u32 = 50;
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Set the number of stake history entries kept for each validator.
+    /// The older entries of a validator are removed when a new entry is appended,
+    /// at most two of them at a time, so the entries beyond a lowered retention
+    /// are removed gradually.
+    pub fn set_stake_history_retention(&mut self, retention: u32) {
+        self.assert_owner();
+        assert!(
+            retention > 0 && retention <= MAX_STAKE_HISTORY_RETENTION,
+            "The retention must be in range [1, {}].",
+            MAX_STAKE_HISTORY_RETENTION
+        );
+        CouncilKeeperEvent::emit_parameter_changed(
+            "stake_history_retention",
+            &self.stake_history_retention,
+            &retention,
+        );
+        self.stake_history_retention = retention;
+    }
+}
+
+impl CouncilKeeper {
+    // Append a stake history entry of the validator, and remove the oldest entries
+    // which are beyond the retention, up to `MAX_STAKE_HISTORY_REMOVALS_PER_APPEND`.
+    pub(crate) fn record_stake_history_of(&mut self, validator_stake: &InternalValidatorStake) {
+        let validator_id = &validator_stake.validator_id;
+        let (mut first_index, next_index) = self
+            .stake_history_index_ranges
+            .get(validator_id)
+            .unwrap_or((0, 0));
+        let mut removals = 0;
+        while next_index - first_index >= u64::from(self.stake_history_retention)
+            && removals < MAX_STAKE_HISTORY_REMOVALS_PER_APPEND
+        {
+            removals += 1;
+            self.stake_history_entries
+                .remove(&(validator_id.clone(), first_index));
+            first_index += 1;
+        }
+        self.stake_history_entries.insert(
+            &(validator_id.clone(), next_index),
+            &ValidatorStakeHistoryEntry {
+                index: U64::from(next_index),
+                timestamp: U64::from(env::block_timestamp()),
+                stake_in_appchains: validator_stake.stake_in_appchains.to_hash_map(),
+                total_stake: validator_stake.total_stake,
+                overall_rank: None,
+            },
+        );
+        self.stake_history_index_ranges
+            .insert(validator_id, &(first_index, next_index + 1));
+    }
+    // Fill the rank of the latest stake history entry of the validator,
+    // after the stake change is applied to the ranking.
+    pub(crate) fn fill_rank_of_stake_history(&mut self, validator_id: &AccountId, rank: u32) {
+        if let Some((first_index, next_index)) = self.stake_history_index_ranges.get(validator_id) {
+            if next_index > first_index {
+                let key = (validator_id.clone(), next_index - 1);
+                if let Some(mut entry) = self.stake_history_entries.get(&key) {
+                    if entry.overall_rank.is_none() {
+                        entry.overall_rank = Some(rank);
+                        self.stake_history_entries.insert(&key, &entry);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn test_stake_history_is_bounded_by_retention() {
+        let mut contract = new_contract(1);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.set_stake_history_retention(3);
+        for stake in 100..105 {
+            sync_and_update_stakes(&mut contract, "appchain1", &[("alice.testnet", stake)]);
+        }
+        sync_and_update_stakes(&mut contract, "appchain2", &[("alice.testnet", 50)]);
+        let entries = contract.get_validator_stake_history(account("alice.testnet"), None, None);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.index.0)
+                .collect::<Vec<u64>>(),
+            vec![3, 4, 5]
+        );
+        let latest_entry = entries.last().unwrap();
+        assert_eq!(latest_entry.total_stake, U128(154 * NEAR));
+        assert_eq!(latest_entry.stake_in_appchains.len(), 2);
+        assert_eq!(latest_entry.overall_rank, Some(0));
+        assert_eq!(
+            contract
+                .stake_history_index_ranges
+                .get(&account("alice.testnet")),
+            Some((3, 6))
+        );
+        // the entries beyond a lowered retention are hidden at once,
+        // and removed gradually when new entries are appended
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.set_stake_history_retention(1);
+        assert_eq!(
+            contract
+                .get_validator_stake_history(account("alice.testnet"), None, None)
+                .len(),
+            1
+        );
+        sync_stakes(&mut contract, "appchain2", &[("alice.testnet", 60)]);
+        assert_eq!(
+            contract
+                .stake_history_index_ranges
+                .get(&account("alice.testnet")),
+            Some((5, 7))
+        );
+        let entries = contract.get_validator_stake_history(account("alice.testnet"), None, None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].index, U64(6));
+        // the rank is filled after the ranking is updated
+        assert_eq!(entries[0].overall_rank, None);
+        update_change_histories(&mut contract);
+        assert_eq!(
+            contract.get_validator_stake_history(account("alice.testnet"), Some(U64(6)), Some(1))
+                [0]
+            .overall_rank,
+            Some(0)
+        );
+        sync_stakes(&mut contract, "appchain2", &[("alice.testnet", 70)]);
+        assert_eq!(
+            contract
+                .stake_history_index_ranges
+                .get(&account("alice.testnet")),
+            Some((7, 8))
+        );
+    }
+}
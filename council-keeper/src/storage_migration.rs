@@ -2,6 +2,56 @@ use crate::*;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LookupMap;
 use near_sdk::{env, near_bindgen, AccountId};
+use types::CouncilChangeAction;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldCouncilChangeHistory {
+    pub index: U64,
+    pub action: CouncilChangeAction,
+    pub state: CouncilChangeHistoryState,
+    pub timestamp: U64,
+}
+
+impl IndexedAndClearable for OldCouncilChangeHistory {
+    //
+    fn set_index(&mut self, index: &u64) {
+        self.index = U64::from(*index);
+    }
+    //
+    fn clear_extra_storage(&mut self) -> MultiTxsOperationProcessingResult {
+        MultiTxsOperationProcessingResult::Ok
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldInternalValidatorStake {
+    //
+    validator_id: AccountId,
+    //
+    stake_in_appchains: UnorderedMap<String, U128>,
+    //
+    total_stake: U128,
+    //
+    overall_rank: u32,
+}
+
+impl OldInternalValidatorStake {
+    // The old collection of stakes is kept as the spilled stakes if it is large,
+    // otherwise the stakes are copied inline.
+    fn into_compact_type(self) -> InternalValidatorStake {
+        let stake_in_appchains =
+            match self.stake_in_appchains.len() as usize > MAX_INLINE_STAKE_IN_APPCHAINS {
+                true => StakeInAppchains::Spilled(self.stake_in_appchains),
+                false => StakeInAppchains::Inline(self.stake_in_appchains.to_vec()),
+            };
+        InternalValidatorStake {
+            validator_id: self.validator_id,
+            stake_in_appchains,
+            total_stake: self.total_stake,
+            overall_rank: self.overall_rank,
+        }
+    }
+}
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct OldOctopusCouncil {
@@ -12,9 +62,9 @@ pub struct OldOctopusCouncil {
     //
     dao_contract_account: AccountId,
     //
-    living_appchain_ids: Vec<String>,
+    living_appchain_ids: UnorderedSet<String>,
     //
-    validator_stakes: LookupMap<AccountId, InternalValidatorStake>,
+    validator_stakes: LookupMap<AccountId, OldInternalValidatorStake>,
     //
     ranked_validators: RankedLookupArray<AccountId>,
     //
@@ -22,48 +72,496 @@ pub struct OldOctopusCouncil {
     //
     latest_members: UnorderedSet<AccountId>,
     //
-    excluding_validator_accounts: Vec<AccountId>,
+    excluding_validator_accounts: UnorderedSet<AccountId>,
     //
-    change_histories: LookupArray<CouncilChangeHistory>,
+    change_histories: LookupArray<OldCouncilChangeHistory>,
     //
     validators_waiting_to_update_rank: UnorderedSet<AccountId>,
 }
 
 #[near_bindgen]
 impl CouncilKeeper {
+    /// The `dao_contract_account` is the DAO contract to which the change histories
+    /// in old layout were applied, it is passed through by `update_self`.
     #[init(ignore_state)]
-    pub fn migrate_state() -> Self {
+    pub fn migrate_state(dao_contract_account: AccountId) -> Self {
         // Deserialize the state using the old contract structure.
         let old_contract: OldOctopusCouncil = env::state_read().expect("Old state doesn't exist");
         //
         near_sdk::assert_self();
+        assert!(
+            dao_contract_account.eq(&old_contract.dao_contract_account),
+            "Mismatch DAO contract account. The DAO contract of old state is '{}'.",
+            old_contract.dao_contract_account
+        );
+        // same as the index decided by `LookupArray::append`
+        let next_change_history_index = match old_contract
+            .change_histories
+            .contains(&old_contract.change_histories.end_index)
+        {
+            true => old_contract.change_histories.end_index + 1,
+            false => old_contract.change_histories.end_index,
+        };
         //
         // Create the new contract using the data from the old contract.
+        // The change histories need to be moved to new storage by `migrate_change_histories`,
+        // and the validator stakes need to be moved by `migrate_validator_stakes`.
         let mut new_contract = CouncilKeeper {
             owner: old_contract.owner,
             appchain_registry_account: old_contract.appchain_registry_account,
-            dao_contract_account: old_contract.dao_contract_account,
-            living_appchain_ids: UnorderedSet::new(StorageKey::LivingAppchainIds),
-            validator_stakes: old_contract.validator_stakes,
+            living_appchain_ids: old_contract.living_appchain_ids,
+            validator_stakes: LookupMap::new(StorageKey::CompactValidatorStakes),
             ranked_validators: old_contract.ranked_validators,
             max_number_of_council_members: old_contract.max_number_of_council_members,
             latest_members: old_contract.latest_members,
-            excluding_validator_accounts: UnorderedSet::new(StorageKey::ExcludingValidatorAccounts),
-            change_histories: old_contract.change_histories,
-            validators_waiting_to_update_rank: UnorderedSet::new(
-                StorageKey::ValidatorsWaitingToUpdateRank,
+            excluding_validator_accounts: old_contract.excluding_validator_accounts,
+            change_histories: LookupArray::migrate_from(
+                StorageKey::TargetedCouncilChangeHistories,
+                old_contract.change_histories.start_index,
+                old_contract.change_histories.end_index,
+            ),
+            next_change_history_index,
+            validators_waiting_to_update_rank: old_contract.validators_waiting_to_update_rank,
+            funded_balance: 0,
+            locked_proposal_bonds: 0,
+            proposal_bonds_of_histories: LookupMap::new(StorageKey::ProposalBondsOfHistories),
+            pending_change_history_indexes: LookupMap::new(StorageKey::PendingChangeHistoryIndexes),
+            dao_targets: UnorderedMap::new(StorageKey::DaoTargets),
+            dao_target_removal: None,
+            primary_dao_contract_account: dao_contract_account.clone(),
+            is_migrating_validator_stakes: true,
+            is_migrating_change_histories: true,
+            next_migrating_change_history_index: old_contract.change_histories.start_index,
+            next_migrating_validator_stake_index: 0,
+            dao_contract_account_of_old_histories: Some(dao_contract_account.clone()),
+            rank_rebuilding: None,
+            council_reset: None,
+            council_reset_records: Vector::new(StorageKey::CouncilResetRecords),
+            latest_change_history_hash: Vec::new(),
+            previous_pending_history_indexes: LookupMap::new(
+                StorageKey::PreviousPendingHistoryIndexes,
             ),
+            pruned_change_history_hash: String::new(),
+            council_snapshots: LookupArray::new(StorageKey::CouncilSnapshots),
+            stake_history_retention: DEFAULT_STAKE_HISTORY_RETENTION,
+            stake_history_index_ranges: LookupMap::new(StorageKey::StakeHistoryIndexRanges),
+            stake_history_entries: LookupMap::new(StorageKey::StakeHistoryEntries),
+            member_tenures: LookupMap::new(StorageKey::MemberTenures),
+            council_terms: LookupMap::new(StorageKey::CouncilTerms),
+            tenure_accounts: UnorderedSet::new(StorageKey::TenureAccounts),
+            appchain_rankings: LookupMap::new(StorageKey::AppchainRankings),
+            appchain_validator_ranks: LookupMap::new(StorageKey::AppchainValidatorRanks),
+            appchains_waiting_to_update_rank: UnorderedSet::new(
+                StorageKey::AppchainsWaitingToUpdateRank,
+            ),
+            appchain_rank_rebuilding: None,
+            ranking_version: 0,
+            stats_calculation: None,
+            decentralization_stats: None,
         };
+        new_contract.dao_targets.insert(
+            &dao_contract_account,
+            &DaoTarget::new(&dao_contract_account),
+        );
+        // the tenure and the snapshots of current council members are tracked since the migration
+        for account_id in new_contract.latest_members.to_vec() {
+            new_contract.start_council_term_of(&account_id, env::block_timestamp());
+        }
+        new_contract.take_council_snapshot_if_changed();
         //
-        for appchain_id in old_contract.living_appchain_ids {
-            new_contract.living_appchain_ids.insert(&appchain_id);
+        new_contract
+    }
+    /// Move the change histories stored in old layout to the storage of new layout.
+    /// The states of old change histories are regarded as the states of the DAO contract
+    /// passed to `migrate_state`.
+    ///
+    /// The running hashes of change histories are calculated in order of index,
+    /// so each call continues from the index where the previous call stopped.
+    pub fn migrate_change_histories(&mut self) -> MultiTxsOperationProcessingResult {
+        self.assert_owner();
+        assert!(
+            self.is_migrating_change_histories,
+            "Change histories are already migrated."
+        );
+        let mut old_histories: LookupMap<u64, OldCouncilChangeHistory> =
+            LookupMap::new(StorageKey::CouncilChangeHistories);
+        let dao_contract_account = self
+            .dao_contract_account_of_old_histories
+            .clone()
+            .expect("The DAO contract of old change histories is unknown.");
+        let index_range = self.change_histories.index_range();
+        let mut index = self.next_migrating_change_history_index;
+        while index <= index_range.end_index.0
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+        {
+            if let Some(old_history) = old_histories.get(&index) {
+                let mut target_states = HashMap::new();
+                target_states.insert(dao_contract_account.clone(), old_history.state);
+                let mut change_history = CouncilChangeHistory {
+                    index: old_history.index,
+                    action: old_history.action,
+                    target_states,
+                    timestamp: old_history.timestamp,
+                    running_hash: String::new(),
+                };
+                self.latest_change_history_hash =
+                    change_history.running_hash_with(&self.latest_change_history_hash);
+                change_history.running_hash = types::to_hex(&self.latest_change_history_hash);
+                self.change_histories.insert(&index, &change_history);
+                old_histories.remove_raw(&index.try_to_vec().unwrap());
+            }
+            index += 1;
+        }
+        self.next_migrating_change_history_index = index;
+        if index > index_range.end_index.0 {
+            self.is_migrating_change_histories = false;
+            self.dao_contract_account_of_old_histories = None;
+            MultiTxsOperationProcessingResult::Ok
+        } else {
+            MultiTxsOperationProcessingResult::NeedMoreGas
+        }
+    }
+    /// Move the validator stakes stored in old layout to the storage of compact layout.
+    /// The validators are walked by their ranks first, then the validators waiting
+    /// to update rank are walked, by a combined index kept in the contract state.
+    pub fn migrate_validator_stakes(&mut self) -> MultiTxsOperationProcessingResult {
+        self.assert_owner();
+        assert!(
+            self.is_migrating_validator_stakes,
+            "Validator stakes are already migrated."
+        );
+        let ranked_count = self.ranked_validators.len();
+        let total_count =
+            ranked_count + self.validators_waiting_to_update_rank.as_vector().len() as u32;
+        let mut index = self.next_migrating_validator_stake_index;
+        while index < total_count
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
+        {
+            let validator_id = match index < ranked_count {
+                true => self.ranked_validators.get(index),
+                false => self
+                    .validators_waiting_to_update_rank
+                    .as_vector()
+                    .get((index - ranked_count) as u64),
+            };
+            if let Some(validator_id) = validator_id {
+                self.migrate_validator_stake_of(&validator_id);
+            }
+            index += 1;
         }
-        for account_id in old_contract.excluding_validator_accounts {
-            new_contract
-                .excluding_validator_accounts
-                .insert(&account_id);
+        self.next_migrating_validator_stake_index = index;
+        if index >= total_count {
+            self.is_migrating_validator_stakes = false;
+            MultiTxsOperationProcessingResult::Ok
+        } else {
+            MultiTxsOperationProcessingResult::NeedMoreGas
         }
+    }
+}
+
+impl CouncilKeeper {
+    // The change histories must not be generated or changed
+    // before the ones in old layout are fully migrated.
+    pub(crate) fn assert_change_histories_migrated(&self) {
+        assert!(
+            !self.is_migrating_change_histories,
+            "Change histories are being migrated. Please call 'migrate_change_histories' first."
+        );
+    }
+    //
+    fn old_validator_stakes() -> LookupMap<AccountId, OldInternalValidatorStake> {
+        LookupMap::new(StorageKey::ValidatorStakes)
+    }
+    // Read the stake of a validator in old layout, without moving it.
+    pub(crate) fn get_unmigrated_validator_stake(
+        &self,
+        validator_id: &AccountId,
+    ) -> Option<InternalValidatorStake> {
+        Self::old_validator_stakes()
+            .get(validator_id)
+            .map(|old_stake| old_stake.into_compact_type())
+    }
+    // Move the stake of a validator in old layout to the storage of compact layout.
+    pub(crate) fn migrate_validator_stake_of(&mut self, validator_id: &AccountId) {
+        if !self.is_migrating_validator_stakes {
+            return;
+        }
+        let mut old_validator_stakes = Self::old_validator_stakes();
+        if let Some(old_stake) = old_validator_stakes.get(validator_id) {
+            let stake = old_stake.into_compact_type();
+            // the old collection of stakes is only kept by the spilled stakes
+            let mut old_stake = old_validator_stakes.remove(validator_id).unwrap();
+            if let StakeInAppchains::Inline(_) = stake.stake_in_appchains {
+                old_stake.stake_in_appchains.clear();
+            }
+            self.validator_stakes.insert(validator_id, &stake);
+            // the rankings of appchains are built as the validators are migrated
+            for (appchain_id, _) in stake.stake_in_appchains.to_hash_map() {
+                self.queue_appchain_rank_update_of(validator_id, &appchain_id, 0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    impl RankValueHolder<AccountId> for LookupMap<AccountId, OldInternalValidatorStake> {
         //
-        new_contract
+        fn get_rank_value_of(&self, member: &AccountId) -> u128 {
+            self.get(member).unwrap().total_stake.0
+        }
+        //
+        fn update_rank_of(&mut self, member: &AccountId, new_rank: u32) {
+            let mut validator_stake = self.get(member).unwrap();
+            validator_stake.overall_rank = new_rank;
+            self.insert(member, &validator_stake);
+        }
+    }
+
+    // Write the state in old layout: alice stakes in 5 appchains (spilled after migration),
+    // bob and charlie stake in `appchain1` only, and alice and bob are the council members.
+    fn write_old_state() {
+        set_context_at(COUNCIL_KEEPER_ACCOUNT, START_TIMESTAMP, 0, Vec::new());
+        let mut living_appchain_ids = UnorderedSet::new(StorageKey::LivingAppchainIds);
+        let mut validator_stakes: LookupMap<AccountId, OldInternalValidatorStake> =
+            LookupMap::new(StorageKey::ValidatorStakes);
+        let mut ranked_validators = RankedLookupArray::new(StorageKey::OrderedValidators);
+        let stakes: [(&str, &[&str], u128); 3] = [
+            (
+                "alice.testnet",
+                &[
+                    "appchain1",
+                    "appchain2",
+                    "appchain3",
+                    "appchain4",
+                    "appchain5",
+                ],
+                100,
+            ),
+            ("bob.testnet", &["appchain1"], 300),
+            ("charlie.testnet", &["appchain1"], 100),
+        ];
+        for (validator_id, appchain_ids, stake) in stakes {
+            let validator_id = account(validator_id);
+            let mut stake_in_appchains =
+                UnorderedMap::new(StorageKey::ValidatorStakeInAppchains(validator_id.clone()));
+            for appchain_id in appchain_ids {
+                living_appchain_ids.insert(&appchain_id.to_string());
+                stake_in_appchains.insert(&appchain_id.to_string(), &U128(stake * NEAR));
+            }
+            validator_stakes.insert(
+                &validator_id,
+                &OldInternalValidatorStake {
+                    validator_id: validator_id.clone(),
+                    stake_in_appchains,
+                    total_stake: U128(stake * NEAR * appchain_ids.len() as u128),
+                    overall_rank: 0,
+                },
+            );
+            ranked_validators.append(&validator_id, &mut validator_stakes);
+        }
+        let mut latest_members = UnorderedSet::new(StorageKey::LatestMembers);
+        let mut change_histories = LookupArray::new(StorageKey::CouncilChangeHistories);
+        for (validator_id, state) in [
+            (
+                "alice.testnet",
+                CouncilChangeHistoryState::ProposalApproved(1),
+            ),
+            ("bob.testnet", CouncilChangeHistoryState::WaitingForApplying),
+        ] {
+            latest_members.insert(&account(validator_id));
+            change_histories.append(&mut OldCouncilChangeHistory {
+                index: U64(0),
+                action: CouncilChangeAction::MemberAdded(account(validator_id)),
+                state,
+                timestamp: U64(START_TIMESTAMP),
+            });
+        }
+        env::state_write(&OldOctopusCouncil {
+            owner: account(COUNCIL_KEEPER_ACCOUNT),
+            appchain_registry_account: account("registry.test_oct.testnet"),
+            dao_contract_account: account(DAO_CONTRACT_ACCOUNT),
+            living_appchain_ids,
+            validator_stakes,
+            ranked_validators,
+            max_number_of_council_members: 2,
+            latest_members,
+            excluding_validator_accounts: UnorderedSet::new(StorageKey::ExcludingValidatorAccounts),
+            change_histories,
+            validators_waiting_to_update_rank: UnorderedSet::new(
+                StorageKey::ValidatorsWaitingToUpdateRank,
+            ),
+        });
+    }
+
+    fn migrate_old_state() -> CouncilKeeper {
+        write_old_state();
+        advance_time(NANOS_PER_DAY);
+        CouncilKeeper::migrate_state(account(DAO_CONTRACT_ACCOUNT))
+    }
+
+    #[test]
+    fn test_migrate_state_and_change_histories() {
+        let mut contract = migrate_old_state();
+        assert_eq!(contract.next_change_history_index, 2);
+        assert!(contract
+            .dao_targets
+            .get(&account(DAO_CONTRACT_ACCOUNT))
+            .is_some());
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["alice.testnet", "bob.testnet"])
+        );
+        // the tenure of the members is tracked since the migration
+        let tenure = contract
+            .get_council_member_tenure(account("alice.testnet"))
+            .unwrap();
+        assert_eq!(tenure.first_joined_at, U64(START_TIMESTAMP + NANOS_PER_DAY));
+        assert!(tenure.is_serving);
+        // the members are recorded in a snapshot with their stakes in old layout
+        let mut members = contract.get_council_members_at(U64(START_TIMESTAMP + NANOS_PER_DAY));
+        members.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].account_id, account("alice.testnet"));
+        assert_eq!(members[0].total_stake, U128(500 * NEAR));
+        assert_eq!(members[1].account_id, account("bob.testnet"));
+        // the change histories can not be generated before they are migrated
+        set_context("alice.testnet", Vec::new());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.exclude_validator_from_council()
+        }));
+        assert!(result.is_err());
+        //
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.migrate_change_histories().is_ok());
+        assert!(!contract.is_migrating_change_histories);
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalApproved(1))
+        );
+        assert_eq!(
+            state_of(&contract, 1, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::WaitingForApplying)
+        );
+        let old_histories: LookupMap<u64, OldCouncilChangeHistory> =
+            LookupMap::new(StorageKey::CouncilChangeHistories);
+        assert!(!old_histories.contains_key(&0));
+        assert!(!old_histories.contains_key(&1));
+        // the running hashes are calculated in order of index
+        let first_history = contract.change_histories.get(&0).unwrap();
+        assert_eq!(
+            first_history.running_hash,
+            types::to_hex(&first_history.running_hash_with(&[]))
+        );
+        let second_history = contract.change_histories.get(&1).unwrap();
+        assert_eq!(
+            second_history.running_hash,
+            types::to_hex(
+                &second_history.running_hash_with(&types::from_hex(&first_history.running_hash))
+            )
+        );
+        assert_eq!(
+            contract.get_change_history_chain().latest_running_hash,
+            second_history.running_hash
+        );
+    }
+
+    #[test]
+    fn test_migrate_validator_stakes() {
+        let mut contract = migrate_old_state();
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.migrate_change_histories().is_ok());
+        // the stakes in old layout can be read before they are migrated
+        let alice_stake = contract.get_validator_stake_of(account("alice.testnet"));
+        assert_eq!(alice_stake.total_stake, U128(500 * NEAR));
+        assert_eq!(alice_stake.stake_in_appchains.len(), 5);
+        // the stake of a validator is migrated when it is synced
+        sync_stakes(&mut contract, "appchain1", &[("bob.testnet", 350)]);
+        assert!(CouncilKeeper::old_validator_stakes()
+            .get(&account("bob.testnet"))
+            .is_none());
+        let bob_stake = contract
+            .validator_stakes
+            .get(&account("bob.testnet"))
+            .unwrap();
+        assert!(matches!(
+            bob_stake.stake_in_appchains,
+            StakeInAppchains::Inline(_)
+        ));
+        assert_eq!(bob_stake.total_stake, U128(350 * NEAR));
+        // the ranking can not be updated before the stakes are migrated
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.update_council_change_histories()
+        }));
+        assert!(result.is_err());
+        //
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.migrate_validator_stakes().is_ok());
+        assert!(!contract.is_migrating_validator_stakes);
+        assert_eq!(
+            contract.next_migrating_validator_stake_index,
+            contract.ranked_validators.len()
+                + contract.validators_waiting_to_update_rank.len() as u32
+        );
+        for validator_id in ["alice.testnet", "charlie.testnet"] {
+            assert!(CouncilKeeper::old_validator_stakes()
+                .get(&account(validator_id))
+                .is_none());
+        }
+        // the old collection of stakes is kept by the spilled stakes
+        let alice_stake = contract
+            .validator_stakes
+            .get(&account("alice.testnet"))
+            .unwrap();
+        assert!(matches!(
+            alice_stake.stake_in_appchains,
+            StakeInAppchains::Spilled(_)
+        ));
+        assert_eq!(
+            contract
+                .get_validator_stake_of(account("alice.testnet"))
+                .stake_in_appchains
+                .get("appchain5"),
+            Some(&U128(100 * NEAR))
+        );
+        // the rankings of appchains are built as the validators are migrated
+        update_change_histories(&mut contract);
+        let validators_of_appchain1 =
+            contract.get_appchain_validator_stakes("appchain1".to_string(), 0, None);
+        assert_eq!(validators_of_appchain1.len(), 3);
+        assert_eq!(
+            validators_of_appchain1[0].validator_id,
+            account("bob.testnet")
+        );
+        assert_eq!(
+            contract
+                .get_appchain_validator_stakes("appchain5".to_string(), 0, None)
+                .len(),
+            1
+        );
+        // the new change histories are indexed and chained after the migrated ones
+        let running_hash = contract.change_histories.get(&1).unwrap().running_hash;
+        sync_and_update_stakes(&mut contract, "appchain1", &[("charlie.testnet", 1000)]);
+        let change_history = contract.change_histories.get(&2).unwrap();
+        assert!(matches!(
+            &change_history.action,
+            CouncilChangeAction::MemberAdded(account_id)
+                if account_id.eq(&account("charlie.testnet"))
+        ));
+        assert_eq!(
+            change_history.running_hash,
+            types::to_hex(&change_history.running_hash_with(&types::from_hex(&running_hash)))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Mismatch DAO contract account.")]
+    fn test_migrate_state_with_another_dao_contract() {
+        write_old_state();
+        CouncilKeeper::migrate_state(account("another-dao.sputnikv2.testnet"));
     }
 }
@@ -1,28 +1,260 @@
 use crate::*;
+use events::MAX_ENTRIES_IN_EVENT_DATA;
+use types::{CouncilKeeperEvent, CouncilMemberEventData, CouncilResetRecord};
+
+/// The progress of resetting council, which may take multiple transactions.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct CouncilResetState {
+    //
+    pub operator: AccountId,
+    //
+    pub reason: String,
+    //
+    pub started_at: u64,
+    // the index range of change histories which will be discarded
+    pub start_index: u64,
+    pub end_index: u64,
+    // the number of change histories which have been discarded
+    pub discarded_count: u64,
+    // the chained sha256 hash of the discarded change histories
+    pub digest: Vec<u8>,
+}
 
 #[near_bindgen]
 impl CouncilKeeper {
+    /// Discard all of the change histories, clear the council members and
+    /// regenerate change histories by the current ranking of validators. The proposal bonds
+    /// of the discarded change histories are released.
     ///
-    pub fn clear_council_members_and_regenerate_change_histories(&mut self) {
+    /// This function needs to be called repeatedly until it returns `Ok`.
+    /// The `reason` is only recorded by the call which starts the reset.
+    /// The ranking of validators needs to be up to date, as the council is selected by it.
+    pub fn clear_council_members_and_regenerate_change_histories(
+        &mut self,
+        reason: String,
+    ) -> MultiTxsOperationProcessingResult {
         self.assert_owner();
-        //
-        self.latest_members.clear();
-        //
-        // self.change_histories.clear();
-        let change_history_index_range = self.change_histories.index_range();
-        for index in
-            change_history_index_range.start_index.0..change_history_index_range.end_index.0 + 1
+        self.assert_change_histories_migrated();
+        assert!(
+            self.rank_rebuilding.is_none() && self.validators_waiting_to_update_rank.is_empty(),
+            "The ranking of validators is not up to date. Please call 'update_council_change_histories' first."
+        );
+        let mut reset_state = match self.council_reset.take() {
+            Some(reset_state) => reset_state,
+            None => {
+                assert!(
+                    !reason.is_empty(),
+                    "The reason of resetting council is needed."
+                );
+                let index_range = self.change_histories.index_range();
+                log!(
+                    "Start resetting council, change histories '{}' - '{}' will be discarded.",
+                    index_range.start_index.0,
+                    index_range.end_index.0
+                );
+                CouncilResetState {
+                    operator: env::predecessor_account_id(),
+                    reason,
+                    started_at: env::block_timestamp(),
+                    start_index: index_range.start_index.0,
+                    end_index: index_range.end_index.0,
+                    discarded_count: 0,
+                    digest: Vec::new(),
+                }
+            }
+        };
+        // the index of change histories is kept monotonic,
+        // the discarded ones are removed from the start of the array
+        while self.change_histories.start_index <= reset_state.end_index
+            && env::used_gas() < Gas::ONE_TERA.mul(T_GAS_CAP_FOR_MULTI_TXS_PROCESSING)
         {
-            env::storage_remove(&get_storage_key_in_lookup_array(
-                &StorageKey::CouncilChangeHistories,
-                &index,
-            ));
+            let index = self.change_histories.start_index;
+            if let Some(change_history) = self.change_histories.get(&index) {
+                reset_state.digest = env::sha256(
+                    &[
+                        reset_state.digest.clone(),
+                        change_history.try_to_vec().unwrap(),
+                    ]
+                    .concat(),
+                );
+                reset_state.discarded_count += 1;
+                // the pending states and proposal bonds of the discarded change history
+                // are no longer tracked
+                let account_id = change_history.action.account_id();
+                if self.pending_change_history_indexes.get(account_id) == Some(index) {
+                    self.pending_change_history_indexes.remove(account_id);
+                }
+                self.previous_pending_history_indexes.remove(&index);
+                for dao_contract_account in change_history.target_states.keys() {
+                    self.release_proposal_bond_of(&index, dao_contract_account);
+                }
+                // the kept change histories are chained after the discarded ones
+                self.pruned_change_history_hash = change_history.running_hash;
+            }
+            self.change_histories.remove_before(&(index + 1));
+        }
+        if self.change_histories.start_index <= reset_state.end_index {
+            self.council_reset = Some(reset_state);
+            return MultiTxsOperationProcessingResult::NeedMoreGas;
+        }
+        // the members which are selected again keep serving in their current terms
+        let previous_members = self.latest_members.to_vec();
+        self.latest_members.clear();
+        let record = CouncilResetRecord {
+            operator: reset_state.operator,
+            reason: reset_state.reason,
+            started_at: U64::from(reset_state.started_at),
+            finished_at: U64::from(env::block_timestamp()),
+            discarded_start_index: U64::from(reset_state.start_index),
+            discarded_end_index: U64::from(reset_state.end_index),
+            discarded_count: U64::from(reset_state.discarded_count),
+            digest_of_discarded_histories: types::to_hex(&reset_state.digest),
+        };
+        self.council_reset_records.push(&record);
+        CouncilKeeperEvent::CouncilReset(vec![record]).emit();
+        // all of the previous members are dropped, the selected ones are added again
+        // by the regenerated change histories
+        for members in previous_members.chunks(MAX_ENTRIES_IN_EVENT_DATA) {
+            CouncilKeeperEvent::MemberRemoved(
+                members
+                    .iter()
+                    .map(|account_id| CouncilMemberEventData {
+                        account_id: account_id.clone(),
+                        change_history_index: None,
+                        running_hash: None,
+                        timestamp: U64::from(env::block_timestamp()),
+                        target_states: HashMap::new(),
+                        cancelled_change_history_index: None,
+                    })
+                    .collect(),
+            )
+            .emit();
         }
         //
         self.check_and_generate_change_histories();
+        for account_id in previous_members {
+            if !self.latest_members.contains(&account_id) {
+                self.end_council_term_of(&account_id, env::block_timestamp());
+            }
+        }
+        MultiTxsOperationProcessingResult::Ok
     }
 }
 
-fn get_storage_key_in_lookup_array<T: BorshSerialize>(prefix: &StorageKey, index: &T) -> Vec<u8> {
-    [prefix.try_to_vec().unwrap(), index.try_to_vec().unwrap()].concat()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+    use test_utils::*;
+
+    #[test]
+    fn test_reset_council_discards_change_histories() {
+        let mut contract = new_contract(2);
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        sync_and_update_stakes(
+            &mut contract,
+            "appchain1",
+            &[
+                ("alice.testnet", 300),
+                ("bob.testnet", 200),
+                ("charlie.testnet", 100),
+            ],
+        );
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        resolve_add_proposals(
+            &mut contract,
+            &dao_calls,
+            vec![proposal_id_result(1), proposal_id_result(2)],
+        );
+        assert_eq!(contract.locked_proposal_bonds, 2 * NEAR);
+        set_context("alice.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        assert_eq!(contract.change_histories.end_index, 3);
+        //
+        advance_time(NANOS_PER_DAY);
+        let result = contract.clear_council_members_and_regenerate_change_histories(
+            "Council is out of sync with DAO contract.".to_string(),
+        );
+        assert!(result.is_ok());
+        assert!(contract.council_reset.is_none());
+        let records = contract.get_council_reset_records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].operator, account(COUNCIL_KEEPER_ACCOUNT));
+        assert_eq!(records[0].discarded_start_index, U64(0));
+        assert_eq!(records[0].discarded_end_index, U64(3));
+        assert_eq!(records[0].discarded_count, U64(4));
+        let logs = get_logs();
+        assert!(logs
+            .iter()
+            .any(|log| log.contains("\"event\":\"council_reset\"")));
+        assert!(logs
+            .iter()
+            .any(|log| log.contains("\"event\":\"member_removed\"")
+                && !log.contains("\"change_history_index\"")));
+        // the pending indexes and proposal bonds of the discarded histories are cleared
+        assert_eq!(contract.locked_proposal_bonds, 0);
+        for index in 0..4 {
+            assert!(contract
+                .proposal_bonds_of_histories
+                .get(&(index, account(DAO_CONTRACT_ACCOUNT)))
+                .is_none());
+            assert!(contract
+                .previous_pending_history_indexes
+                .get(&index)
+                .is_none());
+        }
+        assert!(contract
+            .pending_change_history_indexes
+            .get(&account("alice.testnet"))
+            .is_none());
+        // the change histories are regenerated after the discarded ones
+        let index_range = contract.change_histories.index_range();
+        assert_eq!(index_range.start_index, U64(4));
+        assert_eq!(index_range.end_index, U64(5));
+        assert_eq!(
+            council_members_of(&contract),
+            accounts(&["bob.testnet", "charlie.testnet"])
+        );
+        assert_eq!(
+            contract
+                .pending_change_history_indexes
+                .get(&account("bob.testnet")),
+            Some(4)
+        );
+        let change_history = contract.change_histories.get(&4).unwrap();
+        assert_eq!(
+            change_history.running_hash,
+            types::to_hex(
+                &change_history
+                    .running_hash_with(&types::from_hex(&contract.pruned_change_history_hash))
+            )
+        );
+        // the members which are selected again keep serving in their current terms
+        let tenure = contract
+            .get_council_member_tenure(account("bob.testnet"))
+            .unwrap();
+        assert_eq!(tenure.terms, 1);
+        assert_eq!(tenure.first_joined_at, U64(START_TIMESTAMP));
+        assert!(tenure.is_serving);
+    }
+
+    #[test]
+    #[should_panic(expected = "The reason of resetting council is needed.")]
+    fn test_reset_council_requires_reason() {
+        let mut contract = new_contract(2);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.clear_council_members_and_regenerate_change_histories(String::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "The ranking of validators is not up to date.")]
+    fn test_reset_council_requires_up_to_date_ranking() {
+        let mut contract = new_contract(2);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("alice.testnet", 300)]);
+        sync_stakes(&mut contract, "appchain1", &[("bob.testnet", 200)]);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        contract.clear_council_members_and_regenerate_change_histories(
+            "Council is out of sync with DAO contract.".to_string(),
+        );
+    }
 }
@@ -0,0 +1,176 @@
+use crate::*;
+use types::{CouncilMemberTenure, CouncilTerm};
+
+const NANOS_PER_DAY: u64 = 24 * 3600 * 1_000_000_000;
+
+impl CouncilKeeper {
+    // Start a new term of the account if it is not serving.
+    pub(crate) fn start_council_term_of(&mut self, account_id: &AccountId, timestamp: u64) {
+        let (terms, served_nanos) = self.member_tenures.get(account_id).unwrap_or((0, 0));
+        if self.is_serving_in_council(account_id, terms) {
+            return;
+        }
+        self.council_terms.insert(
+            &(account_id.clone(), terms),
+            &CouncilTerm {
+                joined_at: U64::from(timestamp),
+                left_at: None,
+            },
+        );
+        self.member_tenures
+            .insert(account_id, &(terms + 1, served_nanos));
+        self.tenure_accounts.insert(account_id);
+    }
+    // End the current term of the account if it is serving.
+    pub(crate) fn end_council_term_of(&mut self, account_id: &AccountId, timestamp: u64) {
+        if let Some((terms, served_nanos)) = self.member_tenures.get(account_id) {
+            if let Some(mut term) = self.get_last_council_term_of(account_id, terms) {
+                if term.left_at.is_none() {
+                    term.left_at = Some(U64::from(timestamp));
+                    self.council_terms
+                        .insert(&(account_id.clone(), terms - 1), &term);
+                    self.member_tenures.insert(
+                        account_id,
+                        &(
+                            terms,
+                            served_nanos + timestamp.saturating_sub(term.joined_at.0),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+    // Undo `start_council_term_of`, the last term is removed if it is serving.
+    pub(crate) fn undo_start_council_term_of(&mut self, account_id: &AccountId) {
+        if let Some((terms, served_nanos)) = self.member_tenures.get(account_id) {
+            if self.is_serving_in_council(account_id, terms) {
+                self.council_terms.remove(&(account_id.clone(), terms - 1));
+                if terms > 1 {
+                    self.member_tenures
+                        .insert(account_id, &(terms - 1, served_nanos));
+                } else {
+                    self.member_tenures.remove(account_id);
+                    self.tenure_accounts.remove(account_id);
+                }
+            }
+        }
+    }
+    // Undo `end_council_term_of`, the last term is serving again.
+    pub(crate) fn undo_end_council_term_of(&mut self, account_id: &AccountId) {
+        if let Some((terms, served_nanos)) = self.member_tenures.get(account_id) {
+            if let Some(mut term) = self.get_last_council_term_of(account_id, terms) {
+                if let Some(left_at) = term.left_at {
+                    term.left_at = None;
+                    self.council_terms
+                        .insert(&(account_id.clone(), terms - 1), &term);
+                    self.member_tenures.insert(
+                        account_id,
+                        &(
+                            terms,
+                            served_nanos.saturating_sub(left_at.0.saturating_sub(term.joined_at.0)),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+    //
+    fn is_serving_in_council(&self, account_id: &AccountId, terms: u32) -> bool {
+        self.get_last_council_term_of(account_id, terms)
+            .is_some_and(|term| term.left_at.is_none())
+    }
+    //
+    fn get_last_council_term_of(&self, account_id: &AccountId, terms: u32) -> Option<CouncilTerm> {
+        match terms > 0 {
+            true => self.council_terms.get(&(account_id.clone(), terms - 1)),
+            false => None,
+        }
+    }
+    //
+    pub(crate) fn get_council_member_tenure_of(
+        &self,
+        account_id: &AccountId,
+    ) -> Option<CouncilMemberTenure> {
+        let (terms, served_nanos) = self.member_tenures.get(account_id)?;
+        let first_term = self.council_terms.get(&(account_id.clone(), 0))?;
+        let last_term = self.get_last_council_term_of(account_id, terms)?;
+        let served_nanos = match last_term.left_at {
+            Some(_) => served_nanos,
+            None => served_nanos + env::block_timestamp().saturating_sub(last_term.joined_at.0),
+        };
+        Some(CouncilMemberTenure {
+            account_id: account_id.clone(),
+            first_joined_at: first_term.joined_at,
+            last_joined_at: last_term.joined_at,
+            last_left_at: last_term.left_at,
+            is_serving: last_term.left_at.is_none(),
+            terms,
+            served_duration: U64::from(served_nanos),
+            days_served: served_nanos / NANOS_PER_DAY,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NANOS_PER_DAY;
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn test_council_member_tenures() {
+        let mut contract = new_contract(1);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("alice.testnet", 300)]);
+        advance_time(2 * NANOS_PER_DAY);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("bob.testnet", 400)]);
+        advance_time(NANOS_PER_DAY);
+        set_context("bob.testnet", Vec::new());
+        contract.exclude_validator_from_council();
+        assert_eq!(council_members_of(&contract), accounts(&["alice.testnet"]));
+        advance_time(NANOS_PER_DAY);
+        //
+        let tenure = contract
+            .get_council_member_tenure(account("alice.testnet"))
+            .unwrap();
+        assert_eq!(tenure.terms, 2);
+        assert!(tenure.is_serving);
+        assert_eq!(tenure.first_joined_at, U64(START_TIMESTAMP));
+        assert_eq!(
+            tenure.last_joined_at,
+            U64(START_TIMESTAMP + 3 * NANOS_PER_DAY)
+        );
+        assert!(tenure.last_left_at.is_none());
+        assert_eq!(tenure.served_duration, U64(3 * NANOS_PER_DAY));
+        assert_eq!(tenure.days_served, 3);
+        let tenure = contract
+            .get_council_member_tenure(account("bob.testnet"))
+            .unwrap();
+        assert_eq!(tenure.terms, 1);
+        assert!(!tenure.is_serving);
+        assert_eq!(
+            tenure.last_left_at,
+            Some(U64(START_TIMESTAMP + 3 * NANOS_PER_DAY))
+        );
+        assert_eq!(tenure.days_served, 1);
+        assert_eq!(contract.get_council_member_tenures(0, None).len(), 2);
+        assert_eq!(contract.get_council_member_tenures(1, Some(5)).len(), 1);
+        assert!(contract
+            .get_council_member_tenure(account("charlie.testnet"))
+            .is_none());
+        // the terms are restored by rolling back the change histories
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract.rollback_change_histories_after(U64(2)).is_ok());
+        let tenure = contract
+            .get_council_member_tenure(account("alice.testnet"))
+            .unwrap();
+        assert_eq!(tenure.terms, 1);
+        assert!(!tenure.is_serving);
+        assert_eq!(tenure.served_duration, U64(2 * NANOS_PER_DAY));
+        let tenure = contract
+            .get_council_member_tenure(account("bob.testnet"))
+            .unwrap();
+        assert_eq!(tenure.terms, 1);
+        assert!(tenure.is_serving);
+        assert_eq!(tenure.served_duration, U64(2 * NANOS_PER_DAY));
+    }
+}
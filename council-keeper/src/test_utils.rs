@@ -0,0 +1,232 @@
+use crate::*;
+use near_sdk::mock::VmAction;
+use near_sdk::test_utils::{get_created_receipts, VMContextBuilder};
+use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+pub const COUNCIL_KEEPER_ACCOUNT: &str = "octopus-council.registry.test_oct.testnet";
+pub const DAO_CONTRACT_ACCOUNT: &str = "octopus-dao.sputnikv2.testnet";
+pub const START_TIMESTAMP: u64 = 1672531200000000000;
+pub const NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+pub const NANOS_PER_DAY: u64 = 24 * 3600 * 1_000_000_000;
+
+/// A function call sent by the contract in the current call.
+pub struct SentFunctionCall {
+    pub receiver_id: AccountId,
+    pub method_name: String,
+    pub args: Vec<u8>,
+    pub deposit: u128,
+}
+
+pub fn account(account_id: &str) -> AccountId {
+    account_id.parse().unwrap()
+}
+
+pub fn anchor_account(appchain_id: &str) -> String {
+    format!("{}.registry.test_oct.testnet", appchain_id)
+}
+
+// Set the context of the next call at the given timestamp. The storage is kept,
+// while the logs and the created receipts of previous calls are dropped.
+pub fn set_context_at(
+    predecessor_id: &str,
+    timestamp: u64,
+    deposit: u128,
+    promise_results: Vec<PromiseResult>,
+) {
+    let context = VMContextBuilder::new()
+        .current_account_id(account(COUNCIL_KEEPER_ACCOUNT))
+        .signer_account_id(account(predecessor_id))
+        .predecessor_account_id(account(predecessor_id))
+        .block_timestamp(timestamp)
+        .attached_deposit(deposit)
+        .account_balance(100 * NEAR)
+        .build();
+    testing_env!(
+        context,
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        promise_results
+    );
+}
+
+// Set the context of the next call, at the timestamp of the previous call.
+pub fn set_context(predecessor_id: &str, promise_results: Vec<PromiseResult>) {
+    set_context_at(predecessor_id, env::block_timestamp(), 0, promise_results);
+}
+
+//
+pub fn advance_time(nanos: u64) {
+    set_context_at(
+        COUNCIL_KEEPER_ACCOUNT,
+        env::block_timestamp() + nanos,
+        0,
+        Vec::new(),
+    );
+}
+
+//
+pub fn new_contract(max_number_of_council_members: u32) -> CouncilKeeper {
+    set_context_at(COUNCIL_KEEPER_ACCOUNT, START_TIMESTAMP, 0, Vec::new());
+    CouncilKeeper::new(max_number_of_council_members, account(DAO_CONTRACT_ACCOUNT))
+}
+
+// Sync the stakes (in NEAR) of validators from the anchor of the appchain,
+// without updating the ranking.
+pub fn sync_stakes(contract: &mut CouncilKeeper, appchain_id: &str, stakes: &[(&str, u128)]) {
+    set_context(&anchor_account(appchain_id), Vec::new());
+    contract.sync_validator_stakes_of_anchor(
+        stakes
+            .iter()
+            .map(|(validator_id, stake)| ValidatorStakeRecord {
+                validator_id: account(validator_id),
+                total_stake: U128(stake * NEAR),
+            })
+            .collect(),
+    );
+}
+
+// Call `update_council_change_histories` until it returns `Ok`.
+pub fn update_change_histories(contract: &mut CouncilKeeper) {
+    for _ in 0..100 {
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        match contract.update_council_change_histories() {
+            MultiTxsOperationProcessingResult::Ok => return,
+            MultiTxsOperationProcessingResult::NeedMoreGas => (),
+            MultiTxsOperationProcessingResult::Error(message) => panic!("{}", message),
+        }
+    }
+    panic!("The change histories are not updated.");
+}
+
+//
+pub fn sync_and_update_stakes(
+    contract: &mut CouncilKeeper,
+    appchain_id: &str,
+    stakes: &[(&str, u128)],
+) {
+    sync_stakes(contract, appchain_id, stakes);
+    update_change_histories(contract);
+}
+
+// Resolve the policy of the DAO contract with the given proposal bond (in NEAR).
+pub fn sync_proposal_bond(contract: &mut CouncilKeeper, dao_contract_account: &str, bond: u128) {
+    set_context(
+        COUNCIL_KEEPER_ACCOUNT,
+        vec![PromiseResult::Successful(
+            near_sdk::serde_json::to_vec(
+                &near_sdk::serde_json::json!({ "proposal_bond": U128(bond * NEAR) }),
+            )
+            .unwrap(),
+        )],
+    );
+    contract.resolve_get_policy(account(dao_contract_account));
+}
+
+// Call `apply_change_histories_to_dao_contract` from the first change history,
+// and return the result with the DAO calls passed to `resolve_add_proposals`.
+pub fn apply_change_histories(
+    contract: &mut CouncilKeeper,
+) -> (
+    MultiTxsOperationProcessingResult,
+    Vec<DaoCallOfChangeHistory>,
+) {
+    set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+    let result = contract.apply_change_histories_to_dao_contract(U64(0));
+    (result, dao_calls_of("resolve_add_proposals"))
+}
+
+// Resolve the `add_proposal` calls by the given results,
+// and return the DAO calls passed to `resolve_act_proposals`.
+pub fn resolve_add_proposals(
+    contract: &mut CouncilKeeper,
+    dao_calls: &[DaoCallOfChangeHistory],
+    results: Vec<PromiseResult>,
+) -> Vec<DaoCallOfChangeHistory> {
+    set_context(COUNCIL_KEEPER_ACCOUNT, results);
+    contract.resolve_add_proposals(dao_calls.to_vec());
+    dao_calls_of("resolve_act_proposals")
+}
+
+//
+pub fn resolve_act_proposals(
+    contract: &mut CouncilKeeper,
+    dao_calls: &[DaoCallOfChangeHistory],
+    results: Vec<PromiseResult>,
+) {
+    set_context(COUNCIL_KEEPER_ACCOUNT, results);
+    contract.resolve_act_proposals(dao_calls.to_vec());
+}
+
+// The DAO calls passed to the callback with the given name in the current call.
+pub fn dao_calls_of(function_name: &str) -> Vec<DaoCallOfChangeHistory> {
+    #[derive(Deserialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct Args {
+        dao_calls: Vec<DaoCallOfChangeHistory>,
+    }
+    sent_function_calls()
+        .into_iter()
+        .find(|function_call| function_call.method_name.eq(function_name))
+        .map_or(Vec::new(), |function_call| {
+            near_sdk::serde_json::from_slice::<Args>(&function_call.args)
+                .unwrap()
+                .dao_calls
+        })
+}
+
+// The function calls sent by the contract in the current call.
+pub fn sent_function_calls() -> Vec<SentFunctionCall> {
+    get_created_receipts()
+        .into_iter()
+        .flat_map(|receipt| {
+            let receiver_id = receipt.receiver_id;
+            receipt
+                .actions
+                .into_iter()
+                .filter_map(move |action| match action {
+                    VmAction::FunctionCall {
+                        function_name,
+                        args,
+                        deposit,
+                        ..
+                    } => Some(SentFunctionCall {
+                        receiver_id: receiver_id.clone(),
+                        method_name: function_name,
+                        args,
+                        deposit,
+                    }),
+                    _ => None,
+                })
+        })
+        .collect()
+}
+
+//
+pub fn proposal_id_result(proposal_id: u64) -> PromiseResult {
+    PromiseResult::Successful(near_sdk::serde_json::to_vec(&proposal_id).unwrap())
+}
+
+// The state of the change history for the DAO contract.
+pub fn state_of(
+    contract: &CouncilKeeper,
+    index: u64,
+    dao_contract_account: &str,
+) -> Option<CouncilChangeHistoryState> {
+    contract.get_state_of_change_history(&index, &account(dao_contract_account))
+}
+
+// The sorted council members.
+pub fn council_members_of(contract: &CouncilKeeper) -> Vec<AccountId> {
+    let mut council_members = contract.get_council_members();
+    council_members.sort();
+    council_members
+}
+
+//
+pub fn accounts(account_ids: &[&str]) -> Vec<AccountId> {
+    account_ids
+        .iter()
+        .map(|account_id| account(account_id))
+        .collect()
+}
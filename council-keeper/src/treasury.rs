@@ -0,0 +1,239 @@
+use crate::*;
+
+/// The balance reserved for the storage written by callbacks of pending promises.
+const STORAGE_BALANCE_RESERVE: Balance = 100_000_000_000_000_000_000_000;
+
+#[near_bindgen]
+impl CouncilKeeper {
+    /// Deposit NEAR to this contract, which will be used for proposal bonds and storage.
+    #[payable]
+    pub fn fund_keeper(&mut self) {
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Attached deposit must be greater than 0.");
+        self.funded_balance += amount;
+        log!(
+            "Council keeper is funded by '{}' with {} yoctoNEAR.",
+            env::predecessor_account_id(),
+            amount
+        );
+    }
+    /// Read the `proposal_bond` from the policy of a DAO contract and store it.
+    pub fn sync_proposal_bond_of_dao_contract(&mut self, dao_contract_account: AccountId) {
+        assert!(
+            self.dao_targets.get(&dao_contract_account).is_some(),
+            "The DAO contract '{}' is not a target of council changes.",
+            dao_contract_account
+        );
+        Promise::new(dao_contract_account.clone())
+            .function_call(
+                "get_policy".to_string(),
+                Vec::new(),
+                0,
+                Gas::ONE_TERA.mul(T_GAS_FOR_GET_POLICY),
+            )
+            .then(
+                ext_self::ext(env::current_account_id())
+                    .with_attached_deposit(0)
+                    .with_static_gas(Gas::ONE_TERA.mul(T_GAS_FOR_RESOLVE_GET_POLICY))
+                    .with_unused_gas_weight(0)
+                    .resolve_get_policy(dao_contract_account),
+            );
+    }
+}
+
+impl CouncilKeeper {
+    // The balance which is needed to keep the storage of this contract,
+    // including a reserve for the storage written by pending callbacks.
+    pub(crate) fn get_storage_reserved_balance(&self) -> Balance {
+        Balance::from(env::storage_usage()) * env::storage_byte_cost() + STORAGE_BALANCE_RESERVE
+    }
+    // The balance which can be attached as proposal bonds.
+    pub(crate) fn get_available_balance(&self) -> Balance {
+        env::account_balance().saturating_sub(self.get_storage_reserved_balance())
+    }
+    //
+    pub(crate) fn lock_proposal_bond_of(
+        &mut self,
+        change_history_index: &u64,
+        dao_contract_account: &AccountId,
+        bond: Balance,
+    ) {
+        if bond > 0 {
+            self.proposal_bonds_of_histories.insert(
+                &(*change_history_index, dao_contract_account.clone()),
+                &bond,
+            );
+            self.locked_proposal_bonds += bond;
+        }
+    }
+    // The proposal bond is returned by DAO contract once the proposal is approved.
+    pub(crate) fn release_proposal_bond_of(
+        &mut self,
+        change_history_index: &u64,
+        dao_contract_account: &AccountId,
+    ) {
+        if let Some(bond) = self
+            .proposal_bonds_of_histories
+            .remove(&(*change_history_index, dao_contract_account.clone()))
+        {
+            self.locked_proposal_bonds = self.locked_proposal_bonds.saturating_sub(bond);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::get_logs;
+    use test_utils::*;
+
+    fn setup_contract_with_one_history() -> CouncilKeeper {
+        let mut contract = new_contract(1);
+        sync_and_update_stakes(&mut contract, "appchain1", &[("alice.testnet", 300)]);
+        contract
+    }
+
+    #[test]
+    fn test_fund_keeper() {
+        let mut contract = new_contract(1);
+        set_context_at(
+            COUNCIL_KEEPER_ACCOUNT,
+            START_TIMESTAMP,
+            5 * NEAR,
+            Vec::new(),
+        );
+        contract.fund_keeper();
+        let treasury = contract.get_keeper_treasury();
+        assert_eq!(treasury.funded_balance, U128(5 * NEAR));
+        assert_eq!(treasury.locked_proposal_bonds, U128(0));
+        assert_eq!(
+            treasury.available_balance.0,
+            env::account_balance() - treasury.storage_reserved_balance.0
+        );
+    }
+
+    #[test]
+    fn test_proposal_bonds_are_locked_until_approved() {
+        let mut contract = setup_contract_with_one_history();
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        assert_eq!(dao_calls[0].proposal_bond, U128(NEAR));
+        assert_eq!(contract.locked_proposal_bonds, 0);
+        let act_dao_calls =
+            resolve_add_proposals(&mut contract, &dao_calls, vec![proposal_id_result(1)]);
+        assert_eq!(
+            contract.get_keeper_treasury().locked_proposal_bonds,
+            U128(NEAR)
+        );
+        assert_eq!(
+            contract
+                .proposal_bonds_of_histories
+                .get(&(0, account(DAO_CONTRACT_ACCOUNT))),
+            Some(NEAR)
+        );
+        resolve_act_proposals(
+            &mut contract,
+            &act_dao_calls,
+            vec![PromiseResult::Successful(Vec::new())],
+        );
+        assert_eq!(
+            contract.get_keeper_treasury().locked_proposal_bonds,
+            U128(0)
+        );
+        assert_eq!(
+            contract
+                .proposal_bonds_of_histories
+                .get(&(0, account(DAO_CONTRACT_ACCOUNT))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_requires_synced_and_affordable_proposal_bond() {
+        let mut contract = setup_contract_with_one_history();
+        let (result, dao_calls) = apply_change_histories(&mut contract);
+        assert!(matches!(
+            result,
+            MultiTxsOperationProcessingResult::Error(message) if message.contains("is not synced")
+        ));
+        assert!(dao_calls.is_empty());
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::WaitingForApplying)
+        );
+        //
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1000);
+        let (result, dao_calls) = apply_change_histories(&mut contract);
+        assert!(matches!(
+            result,
+            MultiTxsOperationProcessingResult::Error(message) if message.contains("Insufficient balance")
+        ));
+        assert!(dao_calls.is_empty());
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::WaitingForApplying)
+        );
+    }
+
+    #[test]
+    fn test_proposal_bond_is_not_locked_for_removed_dao_target() {
+        let mut contract = setup_contract_with_one_history();
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        set_context(COUNCIL_KEEPER_ACCOUNT, Vec::new());
+        assert!(contract
+            .remove_dao_target(account(DAO_CONTRACT_ACCOUNT))
+            .is_ok());
+        let act_dao_calls =
+            resolve_add_proposals(&mut contract, &dao_calls, vec![proposal_id_result(1)]);
+        assert!(act_dao_calls.is_empty());
+        assert_eq!(contract.locked_proposal_bonds, 0);
+        assert_eq!(state_of(&contract, 0, DAO_CONTRACT_ACCOUNT), None);
+    }
+
+    #[test]
+    fn test_proposal_bond_is_not_locked_by_late_callback() {
+        let mut contract = setup_contract_with_one_history();
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        let (_, lost_dao_calls) = apply_change_histories(&mut contract);
+        // the submission times out and the change history is submitted again
+        set_context_at(
+            COUNCIL_KEEPER_ACCOUNT,
+            START_TIMESTAMP + PROPOSAL_SUBMITTING_TIMEOUT_IN_NANOS + 1,
+            0,
+            Vec::new(),
+        );
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        assert_eq!(dao_calls.len(), 1);
+        // the callback of the lost submission arrives late
+        resolve_add_proposals(&mut contract, &lost_dao_calls, vec![proposal_id_result(1)]);
+        assert_eq!(contract.locked_proposal_bonds, 0);
+        resolve_add_proposals(&mut contract, &dao_calls, vec![proposal_id_result(2)]);
+        assert_eq!(contract.locked_proposal_bonds, NEAR);
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::ProposalAdded(2))
+        );
+    }
+
+    #[test]
+    fn test_unparsable_proposal_id_is_regarded_as_failed() {
+        let mut contract = setup_contract_with_one_history();
+        sync_proposal_bond(&mut contract, DAO_CONTRACT_ACCOUNT, 1);
+        let (_, dao_calls) = apply_change_histories(&mut contract);
+        let act_dao_calls = resolve_add_proposals(
+            &mut contract,
+            &dao_calls,
+            vec![PromiseResult::Successful(b"invalid".to_vec())],
+        );
+        assert!(act_dao_calls.is_empty());
+        assert_eq!(contract.locked_proposal_bonds, 0);
+        assert_eq!(
+            state_of(&contract, 0, DAO_CONTRACT_ACCOUNT),
+            Some(CouncilChangeHistoryState::WaitingForApplying)
+        );
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains("\"event\":\"proposal_failed\"")));
+    }
+}
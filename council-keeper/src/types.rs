@@ -1,10 +1,24 @@
 use crate::*;
+use council_core::ChangeKind;
 use near_sdk::{
     json_types::U64,
     serde::{Deserialize, Serialize},
 };
 use std::collections::HashMap;
 
+/// Encode bytes as lowercase hex string.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode hex string to bytes.
+pub fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("Invalid hex string."))
+        .collect()
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct IndexRange {
@@ -64,6 +78,99 @@ pub struct ValidatorStake {
     pub overall_rank: u32,
 }
 
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ValidatorStakeHistoryEntry {
+    // the sequence number of the entry of the validator
+    pub index: U64,
+    //
+    pub timestamp: U64,
+    // key: appchain id, value: total stake in the appchain anchor
+    pub stake_in_appchains: HashMap<String, U128>,
+    // total stake in all appchain anchors
+    pub total_stake: U128,
+    // the rank of the validator right after the stake change is applied to the ranking,
+    // `None` if the validator is still waiting to update rank
+    pub overall_rank: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeeperTreasury {
+    // the total amount of NEAR deposited by `fund_keeper`
+    pub funded_balance: U128,
+    // the total amount of proposal bonds held by DAO contracts
+    pub locked_proposal_bonds: U128,
+    // the balance reserved for the storage of this contract
+    pub storage_reserved_balance: U128,
+    // the balance which can be attached as proposal bonds
+    pub available_balance: U128,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum InternalStateViolation {
+    // there is no validator at the index of ranked validators, which can not be repaired
+    MissingRankedValidator {
+        index: u32,
+    },
+    // the validator at the index of ranked validators has no stake record,
+    // which can not be repaired
+    MissingValidatorStake {
+        index: u32,
+        validator_id: AccountId,
+    },
+    // the total stake of the validator is greater than the one ranked before it
+    UnorderedRank {
+        index: u32,
+        validator_id: AccountId,
+        total_stake: U128,
+        previous_total_stake: U128,
+    },
+    // the `overall_rank` of the validator doesn't match its index in ranked validators
+    MismatchedOverallRank {
+        index: u32,
+        validator_id: AccountId,
+        overall_rank: u32,
+    },
+    // the `total_stake` of the validator doesn't equal the sum of its stake in appchains
+    MismatchedTotalStake {
+        validator_id: AccountId,
+        total_stake: U128,
+        sum_of_stake_in_appchains: U128,
+    },
+    // the account is in latest members, but is not selected by the rule
+    UnexpectedCouncilMember {
+        account_id: AccountId,
+    },
+    // the account is selected by the rule, but is not in latest members
+    MissingCouncilMember {
+        account_id: AccountId,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InternalStateViolationRecord {
+    pub violation: InternalStateViolation,
+    // whether the violation has been repaired, or the repairing has been scheduled
+    pub repaired: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InternalStateAuditReport {
+    // the index of ranked validators which the audit starts from
+    pub start_index: u32,
+    // the index to continue the audit from, `None` if the audit is finished
+    pub next_index: Option<u32>,
+    // whether `latest_members` is checked, which is done at the end of the audit
+    // only if there is no validator waiting to update rank
+    pub council_members_checked: bool,
+    //
+    pub violations: Vec<InternalStateViolationRecord>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub enum CouncilChangeAction {
@@ -71,13 +178,167 @@ pub enum CouncilChangeAction {
     MemberRemoved(AccountId),
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+impl CouncilChangeAction {
+    /// The account which is added to or removed from council.
+    pub fn account_id(&self) -> &AccountId {
+        match self {
+            CouncilChangeAction::MemberAdded(account_id) => account_id,
+            CouncilChangeAction::MemberRemoved(account_id) => account_id,
+        }
+    }
+    /// The kind of the change, without the account.
+    pub fn kind(&self) -> ChangeKind {
+        match self {
+            CouncilChangeAction::MemberAdded(_) => ChangeKind::MemberAdded,
+            CouncilChangeAction::MemberRemoved(_) => ChangeKind::MemberRemoved,
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(crate = "near_sdk::serde")]
 pub enum CouncilChangeHistoryState {
     NoNeedToApply,
     WaitingForApplying,
     ProposalAdded(u64),
     ProposalApproved(u64),
+    AppliedDirectly,
+    // the proposal is being submitted at the timestamp, waiting for the callback
+    ProposalSubmitting(u64),
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DaoAdapterKind {
+    // sputnik DAO v2, council changes are applied by proposals
+    SputnikV2,
+    // sputnik DAO v3, council changes are applied by proposals
+    SputnikV3,
+    // a contract with custom functions for setting members directly
+    Direct {
+        add_member_method: String,
+        remove_member_method: String,
+    },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DaoTarget {
+    //
+    pub dao_contract_account: AccountId,
+    // the name of the role in DAO contract which the council members belong to
+    pub role_name: String,
+    // the way of applying council changes to DAO contract
+    pub dao_adapter: DaoAdapterKind,
+    // the proposal bond of DAO contract, synced from the policy of DAO contract,
+    // `None` if it has not been synced
+    pub proposal_bond: Option<U128>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DaoCallOfChangeHistory {
+    // the index of change history
+    pub index: U64,
+    //
+    pub dao_contract_account: AccountId,
+    // the state of change history for the DAO contract when the call is sent,
+    // the result of the call is ignored if the state is changed
+    pub expected_state: CouncilChangeHistoryState,
+    // the proposal bond attached to the call
+    pub proposal_bond: U128,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChangeHistoryChain {
+    // the index range of change histories which are kept in contract
+    pub index_range: IndexRange,
+    // the running hash of the latest change history in hex
+    pub latest_running_hash: String,
+    // the running hash of the last pruned change history in hex,
+    // which the running hash of the first kept change history is based on
+    pub pruned_running_hash: String,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilMemberRecord {
+    pub account_id: AccountId,
+    // total stake in all appchain anchors
+    pub total_stake: U128,
+    // the rank of the validator in all validators
+    pub overall_rank: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilTerm {
+    pub joined_at: U64,
+    // `None` if the member is still serving in the term
+    pub left_at: Option<U64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilMemberTenure {
+    pub account_id: AccountId,
+    // the time when the account joined council for the first time
+    pub first_joined_at: U64,
+    // the time when the latest term started
+    pub last_joined_at: U64,
+    // the time when the latest term ended, `None` if the account is serving
+    pub last_left_at: Option<U64>,
+    //
+    pub is_serving: bool,
+    // the number of terms the account served
+    pub terms: u32,
+    // the total duration of all terms in nanoseconds, including the current one
+    pub served_duration: U64,
+    // the total days of all terms, including the current one
+    pub days_served: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilSnapshot {
+    pub index: U64,
+    // the time since which the council members are as the snapshot
+    pub timestamp: U64,
+    // ordered by rank
+    pub members: Vec<CouncilMemberRecord>,
+}
+
+impl IndexedAndClearable for CouncilSnapshot {
+    //
+    fn set_index(&mut self, index: &u64) {
+        self.index = U64::from(*index);
+    }
+    //
+    fn clear_extra_storage(&mut self) -> MultiTxsOperationProcessingResult {
+        MultiTxsOperationProcessingResult::Ok
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilResetRecord {
+    // the account which reset the council
+    pub operator: AccountId,
+    //
+    pub reason: String,
+    //
+    pub started_at: U64,
+    //
+    pub finished_at: U64,
+    // the index range of discarded change histories
+    pub discarded_start_index: U64,
+    pub discarded_end_index: U64,
+    // the number of discarded change histories
+    pub discarded_count: U64,
+    // the chained sha256 hash of the borsh-serialized discarded change histories in hex,
+    // `h(i) = sha256(h(i - 1) ++ history(i))` with an empty `h` before the first one
+    pub digest_of_discarded_histories: String,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
@@ -85,8 +346,50 @@ pub enum CouncilChangeHistoryState {
 pub struct CouncilChangeHistory {
     pub index: U64,
     pub action: CouncilChangeAction,
-    pub state: CouncilChangeHistoryState,
+    // key: account id of DAO contract, value: the state of applying the change to the DAO contract
+    pub target_states: HashMap<AccountId, CouncilChangeHistoryState>,
     pub timestamp: U64,
+    // the hash over this change history and the previous one in hex
+    pub running_hash: String,
+}
+
+impl CouncilChangeHistory {
+    /// The running hash is `sha256(previous_running_hash ++ borsh((index, action, timestamp)))`,
+    /// the `target_states` is not included as it changes while applying.
+    pub fn running_hash_with(&self, previous_running_hash: &[u8]) -> Vec<u8> {
+        env::sha256(
+            &[
+                previous_running_hash.to_vec(),
+                (self.index.0, &self.action, self.timestamp.0)
+                    .try_to_vec()
+                    .unwrap(),
+            ]
+            .concat(),
+        )
+    }
+    /// Whether the change is applied to all of the DAO contracts, or need not to be applied.
+    pub fn is_applied(&self) -> bool {
+        self.target_states.values().all(|state| {
+            matches!(
+                state,
+                CouncilChangeHistoryState::NoNeedToApply
+                    | CouncilChangeHistoryState::ProposalApproved(_)
+                    | CouncilChangeHistoryState::AppliedDirectly
+            )
+        })
+    }
+    /// Whether the change is not applied to any DAO contract yet.
+    pub fn is_waiting_for_applying(&self) -> bool {
+        self.target_states
+            .values()
+            .all(|state| matches!(state, CouncilChangeHistoryState::WaitingForApplying))
+    }
+    /// Set the same state for all of the DAO contracts.
+    pub fn set_state_of_all_targets(&mut self, state: CouncilChangeHistoryState) {
+        for target_state in self.target_states.values_mut() {
+            *target_state = state.clone();
+        }
+    }
 }
 
 impl IndexedAndClearable for CouncilChangeHistory {
@@ -99,3 +402,292 @@ impl IndexedAndClearable for CouncilChangeHistory {
         MultiTxsOperationProcessingResult::Ok
     }
 }
+
+/// The events emitted by council keeper, in the format of NEP-297.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum CouncilKeeperEvent {
+    StakeSynced(Vec<StakeSyncedEventData>),
+    RankChanged(Vec<RankChangedEventData>),
+    MemberAdded(Vec<CouncilMemberEventData>),
+    MemberRemoved(Vec<CouncilMemberEventData>),
+    ProposalSubmitting(Vec<DaoProposalEventData>),
+    ProposalSubmitted(Vec<DaoProposalEventData>),
+    ProposalApproved(Vec<DaoProposalEventData>),
+    ProposalFailed(Vec<DaoProposalFailedEventData>),
+    ExclusionToggled(Vec<ExclusionToggledEventData>),
+    ParameterChanged(Vec<ParameterChangedEventData>),
+    CouncilReset(Vec<CouncilResetRecord>),
+    ChangeHistoriesRolledBack(Vec<ChangeHistoryRolledBackEventData>),
+    ChangeHistoriesPruned(Vec<ChangeHistoriesPrunedEventData>),
+    ProposalSubmissionTimedOut(Vec<ProposalSubmissionTimedOutEventData>),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeSyncedEventData {
+    pub validator_id: AccountId,
+    pub appchain_id: String,
+    // the stake of the validator in the appchain
+    pub stake: U128,
+    // the total stake of the validator in all appchains
+    pub total_stake: U128,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RankChangedEventData {
+    pub validator_id: AccountId,
+    pub total_stake: U128,
+    // `None` if the validator was not ranked before
+    pub previous_rank: Option<u32>,
+    pub rank: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilMemberEventData {
+    pub account_id: AccountId,
+    // `None` if the member is dropped by resetting council, without a change history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_history_index: Option<U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running_hash: Option<String>,
+    pub timestamp: U64,
+    // the states of the change history for DAO contracts after it is generated
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub target_states: HashMap<AccountId, CouncilChangeHistoryState>,
+    // the pending change history of the same account which is cancelled out by this one,
+    // the states of both of them are `NoNeedToApply`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancelled_change_history_index: Option<U64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DaoProposalEventData {
+    pub dao_contract_account: AccountId,
+    pub change_history_index: U64,
+    // `None` if the change is applied to the DAO contract directly
+    pub proposal_id: Option<u64>,
+    // the state of the change history for the DAO contract after the call
+    pub state: CouncilChangeHistoryState,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum DaoCallStage {
+    AddProposal,
+    ActProposal,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DaoProposalFailedEventData {
+    pub dao_contract_account: AccountId,
+    pub change_history_index: U64,
+    pub stage: DaoCallStage,
+    // the state of the change history for the DAO contract after the call,
+    // `None` if the change history or the DAO target no longer exists
+    pub state: Option<CouncilChangeHistoryState>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExclusionToggledEventData {
+    pub validator_id: AccountId,
+    pub is_excluded: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ParameterChangedEventData {
+    pub parameter: String,
+    // the values are in JSON, `null` if the parameter was not set or is removed
+    pub previous_value: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChangeHistoryRolledBackEventData {
+    // the account which rolled back the change histories
+    pub operator: AccountId,
+    pub change_history_index: U64,
+    // the pending change history of the same account which was cancelled by the rolled back one,
+    // and is waiting for applying again
+    pub restored_change_history_index: Option<U64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChangeHistoriesPrunedEventData {
+    // the account which pruned the change histories
+    pub operator: AccountId,
+    // the change histories before the index are pruned
+    pub before_index: U64,
+    // the running hash of the last pruned change history
+    pub running_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProposalSubmissionTimedOutEventData {
+    pub dao_contract_account: AccountId,
+    pub change_history_index: U64,
+    // the time when the proposal was submitted, the change history is waiting for applying again
+    pub submitted_at: U64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilSimulation {
+    // the council members selected by the hypothetical parameters, in the order of rank
+    pub members: Vec<CouncilMemberRecord>,
+    // the members which would be added to `latest_members`
+    pub members_to_add: Vec<AccountId>,
+    // the members which would be removed from `latest_members`
+    pub members_to_remove: Vec<AccountId>,
+    // `false` if there are validators waiting to update rank, in which case
+    // the simulation is based on the ranks before their stake changed
+    pub is_ranking_up_to_date: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingRankUpdate {
+    //
+    pub validator_id: AccountId,
+    // the latest total stake, which the validator will be re-ranked by
+    pub total_stake: U128,
+    // `None` if the validator is not ranked yet
+    pub current_rank: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CrankTransactionsEstimate {
+    //
+    pub validators_waiting_to_update_rank: U64,
+    // `true` if the ranking is being rebuilt, or will be rebuilt in bulk
+    pub is_rebuilding_ranks_in_bulk: bool,
+    // the calls of `update_council_change_histories` until it returns `Ok`
+    pub update_transactions: u32,
+    // the pairs of change history and DAO contract which are waiting to be submitted,
+    // excluding the ones which will be generated by the pending rank updates
+    pub submissions_waiting_for_applying: u64,
+    // the pairs of change history and DAO contract whose proposal is waiting to be voted
+    pub proposals_waiting_for_votes: u64,
+    // the upper bound of change histories which will be generated by the pending rank updates
+    pub projected_change_histories: u64,
+    // the calls of `apply_change_histories_to_dao_contract` until all of the pending
+    // change histories are submitted or voted
+    pub apply_transactions: u32,
+    // the index to continue the estimate from, if the scanning of change histories
+    // is stopped by the gas cap of view
+    pub next_scan_index: Option<U64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ExclusionReason {
+    // the validator excluded itself by `exclude_validator_from_council`
+    SelfExcluded,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LeaderboardEntry {
+    //
+    pub validator_id: AccountId,
+    //
+    pub total_stake: U128,
+    //
+    pub overall_rank: u32,
+    // whether the validator is in `latest_members`
+    pub is_council_member: bool,
+    //
+    pub is_excluded: bool,
+    //
+    pub exclusion_reason: Option<ExclusionReason>,
+    // the stake to add to be selected by the current ranking,
+    // `None` if the validator is already selected, excluded or there is no seat
+    pub stake_to_enter: Option<U128>,
+    // the stake to withdraw to drop out of the council selected by the current ranking,
+    // `None` if the validator is not selected or no eligible validator is waiting for a seat
+    pub stake_to_drop_out: Option<U128>,
+    // the appchain which the validator has the most stake in
+    pub primary_appchain_id: Option<String>,
+    // `None` if the validator has never sat on the council
+    pub tenure: Option<CouncilMemberTenure>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AppchainValidatorStake {
+    //
+    pub validator_id: AccountId,
+    // the stake of the validator in the appchain
+    pub stake: U128,
+    // the rank of the validator in the validators of the appchain
+    pub rank_in_appchain: u32,
+    // the total stake of the validator in all appchains
+    pub total_stake: U128,
+    //
+    pub is_council_member: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AppchainStakeSummary {
+    //
+    pub appchain_id: String,
+    // the number of validators which have ever staked in the appchain
+    pub number_of_validators: u32,
+    // the total stake of all validators in the appchain
+    pub total_stake: U128,
+    // the share of the total stake of the appchain in the total stake of all appchains,
+    // in basis points
+    pub share_of_network_stake_bps: u32,
+    // the number of council members which have stake in the appchain,
+    // a member with stake in multiple appchains is counted in each of them
+    pub council_seats: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CouncilAppchainDistribution {
+    //
+    pub appchain_id: String,
+    // the number of council members which have stake in the appchain
+    pub council_seats: u32,
+    // the total stake of council members in the appchain
+    pub council_stake: U128,
+    // the share of `council_stake` in the total stake of council members, in basis points
+    pub share_of_council_stake_bps: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DecentralizationStats {
+    // the number of validators with non-zero total stake
+    pub number_of_validators: u32,
+    // the total stake of all validators
+    pub total_stake: U128,
+    // the least number of top validators holding more than one third of the total stake
+    pub nakamoto_coefficient: u32,
+    // the Gini coefficient of total stakes of validators, in basis points
+    pub gini_coefficient_bps: u32,
+    // the total stake of council members
+    pub council_stake: U128,
+    // the share of `council_stake` in `total_stake`, in basis points
+    pub council_stake_share_bps: u32,
+    // ordered by appchain id
+    pub council_appchain_distribution: Vec<CouncilAppchainDistribution>,
+    // the time when the calculation started
+    pub started_at: U64,
+    // the time when the calculation finished
+    pub calculated_at: U64,
+}
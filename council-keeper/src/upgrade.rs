@@ -42,10 +42,11 @@ pub fn update_self() {
         .expect("Wasm file for deployment is not staged yet.");
     let promise_id = env::promise_batch_create(&current_id);
     env::promise_batch_action_deploy_contract(promise_id, &input);
+    // the arguments of `migrate_state` are passed through
     env::promise_batch_action_function_call(
         promise_id,
         "migrate_state",
-        &[],
+        &env::input().unwrap_or_default(),
         0,
         env::prepaid_gas() - env::used_gas() - GAS_FOR_UPGRADE_SELF_DEPLOY,
     );
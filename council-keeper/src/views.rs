@@ -1,4 +1,6 @@
 use crate::*;
+use stake_history::MAX_STAKE_HISTORY_ENTRIES_IN_VIEW;
+use types::{ChangeHistoryChain, CouncilMemberRecord, CouncilMemberTenure, KeeperTreasury};
 
 #[near_bindgen]
 impl CouncilKeeper {
@@ -20,8 +22,7 @@ impl CouncilKeeper {
     }
     //
     pub fn get_validator_stake_of(&self, account_id: AccountId) -> ValidatorStake {
-        self.validator_stakes
-            .get(&account_id)
+        self.get_validator_stake(&account_id)
             .expect("Invalid validator id.")
             .to_json_type()
     }
@@ -31,24 +32,22 @@ impl CouncilKeeper {
         start_index: u32,
         quantity: Option<u32>,
     ) -> Vec<ValidatorStake> {
-        let all_members = match self.ranked_validators.len() > 0 {
-            true => self.ranked_validators.get_slice_of(start_index, quantity),
-            false => Vec::new(),
-        };
-        all_members
+        self.ranked_validators
+            .get_slice_of(start_index, quantity)
             .iter()
-            .map(|account_id| {
-                self.validator_stakes
-                    .get(account_id)
-                    .unwrap()
-                    .to_json_type()
-            })
+            .map(|account_id| self.get_validator_stake(account_id).unwrap().to_json_type())
             .collect()
     }
     //
     pub fn get_council_members(&self) -> Vec<AccountId> {
         self.latest_members.to_vec()
     }
+    /// Get the council members at the given timestamp, with the stake and rank of them
+    /// when the council was changed to them. The result is empty if the timestamp is before the first snapshot.
+    pub fn get_council_members_at(&self, timestamp: U64) -> Vec<CouncilMemberRecord> {
+        self.get_council_snapshot_at(timestamp.0)
+            .map_or(Vec::new(), |snapshot| snapshot.members)
+    }
     //
     pub fn get_council_change_histories(
         &self,
@@ -58,4 +57,84 @@ impl CouncilKeeper {
         self.change_histories
             .get_slice_of(&start_index.0, quantity.map(|q| q.0))
     }
+    //
+    pub fn get_keeper_treasury(&self) -> KeeperTreasury {
+        KeeperTreasury {
+            funded_balance: U128::from(self.funded_balance),
+            locked_proposal_bonds: U128::from(self.locked_proposal_bonds),
+            storage_reserved_balance: U128::from(self.get_storage_reserved_balance()),
+            available_balance: U128::from(self.get_available_balance()),
+        }
+    }
+    //
+    pub fn get_dao_targets(&self) -> Vec<DaoTarget> {
+        self.dao_targets.values_as_vector().to_vec()
+    }
+    //
+    pub fn get_council_reset_records(&self) -> Vec<CouncilResetRecord> {
+        self.council_reset_records.to_vec()
+    }
+    /// Get the range of kept change histories with the running hashes for verifying them.
+    pub fn get_change_history_chain(&self) -> ChangeHistoryChain {
+        ChangeHistoryChain {
+            index_range: self.change_histories.index_range(),
+            latest_running_hash: types::to_hex(&self.latest_change_history_hash),
+            pruned_running_hash: self.pruned_change_history_hash.clone(),
+        }
+    }
+    /// Get the number of stake history entries kept for each validator.
+    pub fn get_stake_history_retention(&self) -> u32 {
+        self.stake_history_retention
+    }
+    /// Get the stake history entries of a validator, starting from the given index,
+    /// or from the oldest kept entry if the index is not given.
+    pub fn get_validator_stake_history(
+        &self,
+        validator_id: AccountId,
+        start_index: Option<U64>,
+        quantity: Option<u32>,
+    ) -> Vec<ValidatorStakeHistoryEntry> {
+        let (first_index, next_index) = match self.stake_history_index_ranges.get(&validator_id) {
+            Some(index_range) => index_range,
+            None => return Vec::new(),
+        };
+        // the entries beyond the retention may not be removed yet
+        let first_index =
+            first_index.max(next_index.saturating_sub(u64::from(self.stake_history_retention)));
+        let start_index = match start_index {
+            Some(start_index) => start_index.0.max(first_index),
+            None => first_index,
+        };
+        let quantity = quantity
+            .unwrap_or(MAX_STAKE_HISTORY_ENTRIES_IN_VIEW)
+            .min(MAX_STAKE_HISTORY_ENTRIES_IN_VIEW);
+        (start_index..next_index.min(start_index + u64::from(quantity)))
+            .filter_map(|index| {
+                self.stake_history_entries
+                    .get(&(validator_id.clone(), index))
+            })
+            .collect()
+    }
+    /// Get the tenure of an account in council, `None` if it has never sat on the council.
+    pub fn get_council_member_tenure(&self, account_id: AccountId) -> Option<CouncilMemberTenure> {
+        self.get_council_member_tenure_of(&account_id)
+    }
+    /// Get the tenures of the accounts which have sat on the council, with pagination.
+    pub fn get_council_member_tenures(
+        &self,
+        start_index: u32,
+        quantity: Option<u32>,
+    ) -> Vec<CouncilMemberTenure> {
+        let accounts = self.tenure_accounts.as_vector();
+        let end_index = match quantity {
+            Some(quantity) => accounts
+                .len()
+                .min(u64::from(start_index) + u64::from(quantity)),
+            None => accounts.len(),
+        };
+        (u64::from(start_index)..end_index)
+            .filter_map(|index| accounts.get(index))
+            .filter_map(|account_id| self.get_council_member_tenure_of(&account_id))
+            .collect()
+    }
 }
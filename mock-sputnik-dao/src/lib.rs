@@ -0,0 +1,153 @@
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env,
+    json_types::U128,
+    log, near_bindgen,
+    serde::{Deserialize, Serialize},
+    AccountId, Balance, PanicOnDefault, Promise,
+};
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ProposalKind {
+    AddMemberToRole { member_id: AccountId, role: String },
+    RemoveMemberFromRole { member_id: AccountId, role: String },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProposalInput {
+    pub description: String,
+    pub kind: ProposalKind,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Action {
+    VoteApprove,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub proposer: AccountId,
+    pub kind: ProposalKind,
+    pub bond: U128,
+    pub is_approved: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Policy {
+    pub proposal_bond: U128,
+}
+
+/// A mock of sputnik DAO v2 contract, which only supports the proposals
+/// for adding and removing members of roles, and approves them by one vote.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    proposal_bond: Balance,
+    proposals: Vec<Proposal>,
+    roles: Vec<(String, Vec<AccountId>)>,
+    is_rejecting_proposals: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(proposal_bond: U128) -> Self {
+        Self {
+            proposal_bond: proposal_bond.0,
+            proposals: Vec::new(),
+            roles: Vec::new(),
+            is_rejecting_proposals: false,
+        }
+    }
+    //
+    pub fn get_policy(&self) -> Policy {
+        Policy {
+            proposal_bond: U128::from(self.proposal_bond),
+        }
+    }
+    //
+    pub fn get_proposal(&self, id: u64) -> Option<Proposal> {
+        self.proposals.get(id as usize).cloned()
+    }
+    //
+    pub fn get_members_of_role(&self, role: String) -> Vec<AccountId> {
+        self.roles
+            .iter()
+            .find(|(name, _)| name.eq(&role))
+            .map_or(Vec::new(), |(_, members)| members.clone())
+    }
+    /// Make the following calls of `add_proposal` fail, to simulate a failed submission.
+    pub fn set_rejecting_proposals(&mut self, is_rejecting_proposals: bool) {
+        self.is_rejecting_proposals = is_rejecting_proposals;
+    }
+    //
+    #[payable]
+    pub fn add_proposal(&mut self, proposal: ProposalInput) -> u64 {
+        assert!(
+            !self.is_rejecting_proposals,
+            "The proposals are rejected for testing."
+        );
+        assert!(
+            env::attached_deposit() >= self.proposal_bond,
+            "ERR_MIN_BOND"
+        );
+        self.proposals.push(Proposal {
+            proposer: env::predecessor_account_id(),
+            kind: proposal.kind,
+            bond: U128::from(env::attached_deposit()),
+            is_approved: false,
+        });
+        log!("Proposal: {}", proposal.description);
+        (self.proposals.len() - 1) as u64
+    }
+    /// Approve the proposal by one vote, and return the bond to the proposer.
+    pub fn act_proposal(&mut self, id: u64, action: Action, memo: Option<String>) {
+        let Action::VoteApprove = action;
+        let mut proposal = self
+            .proposals
+            .get(id as usize)
+            .cloned()
+            .expect("ERR_NO_PROPOSAL");
+        assert!(!proposal.is_approved, "ERR_PROPOSAL_NOT_READY_FOR_VOTE");
+        match &proposal.kind {
+            ProposalKind::AddMemberToRole { member_id, role } => {
+                let members = self.members_of_role_mut(role);
+                if !members.contains(member_id) {
+                    members.push(member_id.clone());
+                }
+            }
+            ProposalKind::RemoveMemberFromRole { member_id, role } => {
+                self.members_of_role_mut(role)
+                    .retain(|account_id| !account_id.eq(member_id));
+            }
+        }
+        proposal.is_approved = true;
+        if proposal.bond.0 > 0 {
+            Promise::new(proposal.proposer.clone()).transfer(proposal.bond.0);
+        }
+        self.proposals[id as usize] = proposal;
+        if let Some(memo) = memo {
+            log!("Memo: {}", memo);
+        }
+    }
+}
+
+impl Contract {
+    //
+    fn members_of_role_mut(&mut self, role: &String) -> &mut Vec<AccountId> {
+        if !self.roles.iter().any(|(name, _)| name.eq(role)) {
+            self.roles.push((role.clone(), Vec::new()));
+        }
+        &mut self
+            .roles
+            .iter_mut()
+            .find(|(name, _)| name.eq(role))
+            .unwrap()
+            .1
+    }
+}
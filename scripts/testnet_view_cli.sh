@@ -22,3 +22,34 @@ near view $COUNCIL_ACCOUNT_ID get_council_members
 near view $COUNCIL_ACCOUNT_ID get_ranked_validator_stakes '{"start_index":0,"quantity":null}'
 #
 near view $COUNCIL_ACCOUNT_ID get_council_change_histories '{"start_index":"0","quantity":null}'
+#
+near view $COUNCIL_ACCOUNT_ID get_keeper_treasury
+#
+near view $COUNCIL_ACCOUNT_ID get_dao_targets
+#
+near view $COUNCIL_ACCOUNT_ID audit_internal_state '{"start_index":0}'
+#
+near view $COUNCIL_ACCOUNT_ID get_council_reset_records
+#
+near view $COUNCIL_ACCOUNT_ID get_change_history_chain
+#
+near view $COUNCIL_ACCOUNT_ID get_council_members_at '{"timestamp":"1672531200000000000"}'
+#
+near view $COUNCIL_ACCOUNT_ID get_validator_stake_history '{"validator_id":"alice-octopus.testnet","start_index":null,"quantity":null}'
+#
+near view $COUNCIL_ACCOUNT_ID get_council_member_tenure '{"account_id":"alice-octopus.testnet"}'
+near view $COUNCIL_ACCOUNT_ID get_council_member_tenures '{"start_index":0,"quantity":null}'
+#
+near view $COUNCIL_ACCOUNT_ID simulate_council '{"max_number_of_council_members":15,"extra_excluding_accounts":["bob-octopus.testnet"],"min_total_stake":"10000000000000000000000"}'
+#
+near view $COUNCIL_ACCOUNT_ID get_number_of_validators_waiting_to_update_rank
+near view $COUNCIL_ACCOUNT_ID get_validators_waiting_to_update_rank '{"start_index":0,"quantity":null}'
+near view $COUNCIL_ACCOUNT_ID get_projected_council
+near view $COUNCIL_ACCOUNT_ID estimate_crank_transactions '{"start_index":"0"}'
+#
+near view $COUNCIL_ACCOUNT_ID get_validator_leaderboard '{"start_index":0,"quantity":50}'
+#
+near view $COUNCIL_ACCOUNT_ID get_appchain_validator_stakes '{"appchain_id":"appchain1","start_index":0,"quantity":50}'
+near view $COUNCIL_ACCOUNT_ID get_appchain_stake_summaries '{"start_index":0,"quantity":null}'
+#
+near view $COUNCIL_ACCOUNT_ID get_decentralization_stats
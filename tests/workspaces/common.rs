@@ -1,16 +1,23 @@
-use near_sdk::serde_json::json;
+use crate::old_state;
+use council_keeper::types::{CouncilChangeHistory, MultiTxsOperationProcessingResult};
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::{self, json};
+use near_sdk::AccountId;
 use near_units::parse_near;
 use workspaces::network::Sandbox;
 use workspaces::{Account, Contract, Worker};
 
 const BASE_APPCHAIN_ID: &str = "appchain";
 const BASE_VALIDATOR_ID: &str = "validator";
+const MAX_NUMBER_OF_COUNCIL_MEMBERS: u32 = 3;
+const MAX_CALLS_OF_MULTI_TXS_OPERATION: u32 = 20;
 
 pub async fn initialize_contracts_and_users(
     worker: &Worker<Sandbox>,
     appchain_count: u32,
     validator_count_per_appchain: u32,
-) -> anyhow::Result<(Account, Contract, Vec<Contract>, Vec<Account>)> {
+    with_old_state: bool,
+) -> anyhow::Result<(Account, Contract, Contract, Vec<Contract>, Vec<Account>)> {
     let root = worker.root_account().unwrap();
     let mut users: Vec<Account> = Vec::new();
     let mut anchors: Vec<Contract> = Vec::new();
@@ -37,7 +44,7 @@ pub async fn initialize_contracts_and_users(
         .await?
         .unwrap();
     //
-    // dao contract
+    // deploy dao contract
     //
     let dao_contract = root
         .create_subaccount("octopus-dao")
@@ -45,6 +52,20 @@ pub async fn initialize_contracts_and_users(
         .transact()
         .await?
         .unwrap();
+    let dao_contract = dao_contract
+        .deploy(&std::fs::read("res/mock_sputnik_dao.wasm")?)
+        .await?
+        .unwrap();
+    assert!(dao_contract
+        .call("new")
+        .args_json(json!({
+            "proposal_bond": U128(parse_near!("0.1 N")),
+        }))
+        .gas(300_000_000_000_000)
+        .transact()
+        .await
+        .unwrap()
+        .is_success());
     //
     // deploy octopus council contract
     //
@@ -58,17 +79,44 @@ pub async fn initialize_contracts_and_users(
         .deploy(&std::fs::read(format!("res/council_keeper.wasm"))?)
         .await?
         .unwrap();
-    assert!(council_keeper
-        .call("new")
-        .args_json(json!({
-            "max_number_of_council_members": 3,
-            "dao_contract_account": dao_contract.id().to_string(),
-        }))
-        .gas(300_000_000_000_000)
-        .transact()
-        .await
-        .unwrap()
-        .is_success());
+    if with_old_state {
+        // the validators of the first appchain are ranked in the state of old layout
+        let validator_stakes: Vec<(AccountId, u128)> = users
+            [0..validator_count_per_appchain as usize]
+            .iter()
+            .enumerate()
+            .map(|(index, account)| {
+                (
+                    to_near_account_id(account.id()),
+                    parse_near!("100 N") - index as u128,
+                )
+            })
+            .collect();
+        for (key, value) in old_state::old_state_of_council_keeper(
+            &to_near_account_id(council_keeper.id()),
+            &to_near_account_id(appchain_registry.id()),
+            &to_near_account_id(dao_contract.id()),
+            &format!("{}1", BASE_APPCHAIN_ID),
+            &validator_stakes,
+            MAX_NUMBER_OF_COUNCIL_MEMBERS,
+        ) {
+            worker
+                .patch_state(council_keeper.id(), &key, &value)
+                .await?;
+        }
+    } else {
+        assert!(council_keeper
+            .call("new")
+            .args_json(json!({
+                "max_number_of_council_members": MAX_NUMBER_OF_COUNCIL_MEMBERS,
+                "dao_contract_account": dao_contract.id().to_string(),
+            }))
+            .gas(300_000_000_000_000)
+            .transact()
+            .await
+            .unwrap()
+            .is_success());
+    }
     //
     // deploy appchain anchor contract
     //
@@ -105,5 +153,95 @@ pub async fn initialize_contracts_and_users(
         anchors.push(appchain_anchor);
     }
     //
-    Ok((root, council_keeper, anchors, users))
+    Ok((root, council_keeper, dao_contract, anchors, users))
+}
+
+//
+pub fn to_near_account_id(account_id: &workspaces::AccountId) -> AccountId {
+    account_id.as_str().parse().unwrap()
+}
+
+// Call a multi-txs operation of council keeper until it returns `Ok`.
+pub async fn call_until_ok(
+    council: &Contract,
+    function_name: &str,
+    args: serde_json::Value,
+) -> anyhow::Result<()> {
+    for _ in 0..MAX_CALLS_OF_MULTI_TXS_OPERATION {
+        let result = council
+            .call(function_name)
+            .args_json(args.clone())
+            .gas(200_000_000_000_000)
+            .transact()
+            .await?;
+        let result = result.json::<MultiTxsOperationProcessingResult>()?;
+        println!(
+            "Result of calling '{}': {}",
+            function_name,
+            serde_json::to_string::<MultiTxsOperationProcessingResult>(&result).unwrap()
+        );
+        println!();
+        match result {
+            MultiTxsOperationProcessingResult::Ok => return Ok(()),
+            MultiTxsOperationProcessingResult::NeedMoreGas => (),
+            MultiTxsOperationProcessingResult::Error(message) => {
+                panic!("Failed to call '{}': {}", function_name, &message);
+            }
+        }
+    }
+    panic!("Too many calls of '{}'.", function_name);
+}
+
+//
+pub async fn update_council_change_histories(council: &Contract) -> anyhow::Result<()> {
+    call_until_ok(council, "update_council_change_histories", json!({})).await
+}
+
+// The callbacks of DAO calls are resolved in the same transaction,
+// so the change histories are applied once this returns.
+pub async fn apply_change_histories_to_dao_contract(council: &Contract) -> anyhow::Result<()> {
+    call_until_ok(
+        council,
+        "apply_change_histories_to_dao_contract",
+        json!({ "start_index": "0" }),
+    )
+    .await
+}
+
+//
+pub async fn get_council_change_histories(
+    council: &Contract,
+) -> anyhow::Result<Vec<CouncilChangeHistory>> {
+    Ok(council
+        .call("get_council_change_histories")
+        .args_json(json!({
+            "start_index": "0",
+            "quantity": null,
+        }))
+        .view()
+        .await?
+        .json::<Vec<CouncilChangeHistory>>()?)
+}
+
+// The sorted council members in council keeper.
+pub async fn get_council_members(council: &Contract) -> anyhow::Result<Vec<AccountId>> {
+    let mut council_members = council
+        .call("get_council_members")
+        .view()
+        .await?
+        .json::<Vec<AccountId>>()?;
+    council_members.sort();
+    Ok(council_members)
+}
+
+// The sorted members of role `council` in DAO contract.
+pub async fn get_council_members_in_dao(dao_contract: &Contract) -> anyhow::Result<Vec<AccountId>> {
+    let mut council_members = dao_contract
+        .call("get_members_of_role")
+        .args_json(json!({ "role": "council" }))
+        .view()
+        .await?
+        .json::<Vec<AccountId>>()?;
+    council_members.sort();
+    Ok(council_members)
 }
@@ -1,52 +1,24 @@
 mod common;
+mod old_state;
 
-use council_keeper::types::{
-    CouncilChangeHistory, MultiTxsOperationProcessingResult, ValidatorStake,
-};
+use council_keeper::types::{CouncilChangeHistory, CouncilChangeHistoryState, ValidatorStake};
 use near_sdk::{
     serde_json::{self, json},
     AccountId,
 };
+use workspaces::Contract;
 
 #[tokio::test]
 async fn test_sync_staking_amount() -> anyhow::Result<()> {
     //
     let worker = workspaces::sandbox().await?;
-    let (_root, council, anchors, _users) =
-        common::initialize_contracts_and_users(&worker, 1, 60).await?;
-    //
+    let (_root, council, _dao_contract, anchors, _users) =
+        common::initialize_contracts_and_users(&worker, 1, 60, false).await?;
     //
     //
-    for anchor in anchors {
-        let result = anchor
-            .call("sync_validator_stakes_of_anchor")
-            .gas(200_000_000_000_000)
-            .transact()
-            .await;
-        println!("{:?}", result);
-        println!();
-    }
     //
-    loop {
-        let result = council
-            .call("update_council_change_histories")
-            .gas(200_000_000_000_000)
-            .transact()
-            .await?;
-        let result = result.json::<MultiTxsOperationProcessingResult>()?;
-        println!(
-            "Result of calling 'update_council_change_histories': {}",
-            serde_json::to_string::<MultiTxsOperationProcessingResult>(&result).unwrap()
-        );
-        println!();
-        match result {
-            MultiTxsOperationProcessingResult::Ok => break,
-            MultiTxsOperationProcessingResult::NeedMoreGas => (),
-            MultiTxsOperationProcessingResult::Error(message) => {
-                panic!("Failed to update council change histories: {}", &message);
-            }
-        }
-    }
+    sync_validator_stakes_of_anchors(&anchors).await?;
+    common::update_council_change_histories(&council).await?;
     //
     //
     //
@@ -104,3 +76,175 @@ async fn test_sync_staking_amount() -> anyhow::Result<()> {
     //
     Ok(())
 }
+
+#[tokio::test]
+async fn test_apply_change_histories_to_dao_contract() -> anyhow::Result<()> {
+    //
+    let worker = workspaces::sandbox().await?;
+    let (root, council, dao_contract, anchors, users) =
+        common::initialize_contracts_and_users(&worker, 1, 5, false).await?;
+    sync_validator_stakes_of_anchors(&anchors).await?;
+    common::update_council_change_histories(&council).await?;
+    //
+    // the proposal bond is needed before applying
+    //
+    assert!(root
+        .call(council.id(), "sync_proposal_bond_of_dao_contract")
+        .args_json(json!({
+            "dao_contract_account": dao_contract.id().to_string(),
+        }))
+        .gas(200_000_000_000_000)
+        .transact()
+        .await?
+        .is_success());
+    common::apply_change_histories_to_dao_contract(&council).await?;
+    assert_change_histories_approved(&council, &dao_contract).await?;
+    //
+    // the failed submissions are waiting for applying again
+    //
+    assert!(dao_contract
+        .call("set_rejecting_proposals")
+        .args_json(json!({ "is_rejecting_proposals": true }))
+        .transact()
+        .await?
+        .is_success());
+    let council_members = common::get_council_members(&council).await?;
+    let excluding_validator = users
+        .iter()
+        .find(|user| common::to_near_account_id(user.id()).eq(&council_members[0]))
+        .unwrap();
+    assert!(excluding_validator
+        .call(council.id(), "exclude_validator_from_council")
+        .gas(200_000_000_000_000)
+        .transact()
+        .await?
+        .is_success());
+    common::apply_change_histories_to_dao_contract(&council).await?;
+    let change_histories = common::get_council_change_histories(&council).await?;
+    let dao_contract_account = common::to_near_account_id(dao_contract.id());
+    for change_history in &change_histories[change_histories.len() - 2..] {
+        assert!(matches!(
+            change_history.target_states.get(&dao_contract_account),
+            Some(CouncilChangeHistoryState::WaitingForApplying)
+        ));
+    }
+    //
+    assert!(dao_contract
+        .call("set_rejecting_proposals")
+        .args_json(json!({ "is_rejecting_proposals": false }))
+        .transact()
+        .await?
+        .is_success());
+    common::apply_change_histories_to_dao_contract(&council).await?;
+    assert_change_histories_approved(&council, &dao_contract).await?;
+    //
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_state_from_old_layout() -> anyhow::Result<()> {
+    //
+    let worker = workspaces::sandbox().await?;
+    let (root, council, dao_contract, anchors, users) =
+        common::initialize_contracts_and_users(&worker, 1, 5, true).await?;
+    //
+    // the state can only be migrated by the contract itself
+    //
+    assert!(council
+        .call("migrate_state")
+        .args_json(json!({
+            "dao_contract_account": dao_contract.id().to_string(),
+        }))
+        .gas(300_000_000_000_000)
+        .transact()
+        .await?
+        .is_success());
+    common::call_until_ok(&council, "migrate_change_histories", json!({})).await?;
+    common::call_until_ok(&council, "migrate_validator_stakes", json!({})).await?;
+    //
+    let expected_members: Vec<AccountId> = users[0..3]
+        .iter()
+        .map(|user| common::to_near_account_id(user.id()))
+        .collect();
+    assert_eq!(
+        common::get_council_members(&council).await?,
+        expected_members
+    );
+    let dao_contract_account = common::to_near_account_id(dao_contract.id());
+    let change_histories = common::get_council_change_histories(&council).await?;
+    assert_eq!(change_histories.len(), 3);
+    for change_history in &change_histories {
+        assert!(matches!(
+            change_history.target_states.get(&dao_contract_account),
+            Some(CouncilChangeHistoryState::WaitingForApplying)
+        ));
+    }
+    let result = council
+        .call("get_ranked_validator_stakes")
+        .args_json(json!({
+            "start_index": 0,
+            "quantity": null,
+        }))
+        .view()
+        .await?
+        .json::<Vec<ValidatorStake>>()?;
+    assert_eq!(result.len(), 5);
+    //
+    // the migrated contract keeps working with the synced stakes
+    //
+    sync_validator_stakes_of_anchors(&anchors).await?;
+    common::update_council_change_histories(&council).await?;
+    assert!(root
+        .call(council.id(), "sync_proposal_bond_of_dao_contract")
+        .args_json(json!({
+            "dao_contract_account": dao_contract.id().to_string(),
+        }))
+        .gas(200_000_000_000_000)
+        .transact()
+        .await?
+        .is_success());
+    common::apply_change_histories_to_dao_contract(&council).await?;
+    assert_change_histories_approved(&council, &dao_contract).await?;
+    //
+    Ok(())
+}
+
+//
+async fn sync_validator_stakes_of_anchors(anchors: &[Contract]) -> anyhow::Result<()> {
+    for anchor in anchors {
+        let result = anchor
+            .call("sync_validator_stakes_of_anchor")
+            .gas(200_000_000_000_000)
+            .transact()
+            .await;
+        println!("{:?}", result);
+        println!();
+    }
+    Ok(())
+}
+
+// All of the change histories which need to be applied are approved in DAO contract,
+// and the members of role `council` in DAO contract are the same as council keeper.
+async fn assert_change_histories_approved(
+    council: &Contract,
+    dao_contract: &Contract,
+) -> anyhow::Result<()> {
+    let dao_contract_account = common::to_near_account_id(dao_contract.id());
+    let change_histories = common::get_council_change_histories(council).await?;
+    println!(
+        "Result of 'get_council_change_histories': {:?}",
+        serde_json::to_string::<Vec<CouncilChangeHistory>>(&change_histories).unwrap()
+    );
+    for change_history in change_histories {
+        assert!(matches!(
+            change_history.target_states.get(&dao_contract_account),
+            Some(CouncilChangeHistoryState::ProposalApproved(_))
+                | Some(CouncilChangeHistoryState::NoNeedToApply)
+        ));
+    }
+    assert_eq!(
+        common::get_council_members_in_dao(dao_contract).await?,
+        common::get_council_members(council).await?
+    );
+    Ok(())
+}
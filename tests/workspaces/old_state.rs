@@ -0,0 +1,133 @@
+use council_keeper::types::{CouncilChangeAction, CouncilChangeHistoryState};
+use council_keeper::StorageKey;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{env, testing_env, AccountId};
+
+// The structs in this file mirror the storage layout of council keeper
+// before the change histories are targeted to multiple DAO contracts.
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct OldCouncilChangeHistory {
+    index: U64,
+    action: CouncilChangeAction,
+    state: CouncilChangeHistoryState,
+    timestamp: U64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct OldInternalValidatorStake {
+    validator_id: AccountId,
+    stake_in_appchains: UnorderedMap<String, U128>,
+    total_stake: U128,
+    overall_rank: u32,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct LookupArray<T: BorshDeserialize + BorshSerialize> {
+    lookup_map: LookupMap<u64, T>,
+    start_index: u64,
+    end_index: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct RankedLookupArray<T: BorshDeserialize + BorshSerialize> {
+    lookup_map: LookupMap<u32, T>,
+    length: u32,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct OldOctopusCouncil {
+    owner: AccountId,
+    appchain_registry_account: AccountId,
+    dao_contract_account: AccountId,
+    living_appchain_ids: UnorderedSet<String>,
+    validator_stakes: LookupMap<AccountId, OldInternalValidatorStake>,
+    ranked_validators: RankedLookupArray<AccountId>,
+    max_number_of_council_members: u32,
+    latest_members: UnorderedSet<AccountId>,
+    excluding_validator_accounts: UnorderedSet<AccountId>,
+    change_histories: LookupArray<OldCouncilChangeHistory>,
+    validators_waiting_to_update_rank: UnorderedSet<AccountId>,
+}
+
+/// Generate the storage of council keeper in old layout, in which the validators
+/// (ordered by stake descending) stake in the appchain, and the top of them
+/// are the council members with change histories waiting for applying.
+pub fn old_state_of_council_keeper(
+    council_keeper: &AccountId,
+    appchain_registry: &AccountId,
+    dao_contract: &AccountId,
+    appchain_id: &str,
+    validator_stakes: &[(AccountId, u128)],
+    max_number_of_council_members: u32,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    testing_env!(VMContextBuilder::new()
+        .current_account_id(council_keeper.clone())
+        .build());
+    let mut living_appchain_ids = UnorderedSet::new(StorageKey::LivingAppchainIds);
+    living_appchain_ids.insert(&appchain_id.to_string());
+    let mut stakes = LookupMap::new(StorageKey::ValidatorStakes);
+    let mut ranked_validators = RankedLookupArray {
+        lookup_map: LookupMap::new(StorageKey::OrderedValidators),
+        length: 0,
+    };
+    let mut latest_members = UnorderedSet::new(StorageKey::LatestMembers);
+    let mut change_histories = LookupArray {
+        lookup_map: LookupMap::new(StorageKey::CouncilChangeHistories),
+        start_index: 0,
+        end_index: 0,
+    };
+    for (validator_id, stake) in validator_stakes {
+        let mut stake_in_appchains =
+            UnorderedMap::new(StorageKey::ValidatorStakeInAppchains(validator_id.clone()));
+        stake_in_appchains.insert(&appchain_id.to_string(), &U128(*stake));
+        stakes.insert(
+            validator_id,
+            &OldInternalValidatorStake {
+                validator_id: validator_id.clone(),
+                stake_in_appchains,
+                total_stake: U128(*stake),
+                overall_rank: ranked_validators.length,
+            },
+        );
+        ranked_validators
+            .lookup_map
+            .insert(&ranked_validators.length, validator_id);
+        ranked_validators.length += 1;
+        if ranked_validators.length <= max_number_of_council_members {
+            latest_members.insert(validator_id);
+            let index = ranked_validators.length as u64 - 1;
+            change_histories.lookup_map.insert(
+                &index,
+                &OldCouncilChangeHistory {
+                    index: U64(index),
+                    action: CouncilChangeAction::MemberAdded(validator_id.clone()),
+                    state: CouncilChangeHistoryState::WaitingForApplying,
+                    timestamp: U64(env::block_timestamp()),
+                },
+            );
+            change_histories.end_index = index;
+        }
+    }
+    env::state_write(&OldOctopusCouncil {
+        owner: council_keeper.clone(),
+        appchain_registry_account: appchain_registry.clone(),
+        dao_contract_account: dao_contract.clone(),
+        living_appchain_ids,
+        validator_stakes: stakes,
+        ranked_validators,
+        max_number_of_council_members,
+        latest_members,
+        excluding_validator_accounts: UnorderedSet::new(StorageKey::ExcludingValidatorAccounts),
+        change_histories,
+        validators_waiting_to_update_rank: UnorderedSet::new(
+            StorageKey::ValidatorsWaitingToUpdateRank,
+        ),
+    });
+    near_sdk::mock::with_mocked_blockchain(|blockchain| blockchain.take_storage())
+        .into_iter()
+        .collect()
+}